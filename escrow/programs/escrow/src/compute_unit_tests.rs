@@ -0,0 +1,137 @@
+//! Compute-unit budgets for the enhanced escrow instructions.
+//!
+//! Each test runs the built program in LiteSVM and fails if an instruction
+//! consumes more than its budget. Run with `--nocapture` to see the numbers.
+
+use litesvm::LiteSVM;
+use solana_instruction::Instruction;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+use crate::escrow_test_helpers::{
+    build_initialize_enhanced_instruction, build_verify_zk_proofs_instruction, enhanced_escrow_expiry,
+    escrow_validity_inputs, execute_initialize_enhanced, get_enhanced_escrow_pdas, initialize_proof_verifiers,
+    setup_escrow_test, verify_zk_proofs_compute_budget, InitializeEnhancedAccounts, TOKEN_A, TOKEN_B,
+};
+use crate::handlers::pubkey_field_element;
+use crate::state::{CommitmentScheme, PrivacyLevel, VALIDITY_PROOF_PUBLIC_INPUTS};
+
+/// Budget for `initialize_enhanced`, which creates two PDAs and the vault
+/// token account, then deposits
+const INITIALIZE_ENHANCED_MAX_UNITS: u64 = 90_000;
+
+/// Budgets for `verify_zk_proofs`, including creating the proof batch account,
+/// by routing input count. Each Groth16 check is a four-pair pairing of about
+/// 73k CU, plus a G1 scalar multiplication and addition of about 4k CU per
+/// input. No routing inputs skips the routing check.
+const VERIFY_ZK_PROOFS_MAX_UNITS: [(usize, u64); 3] = [(0, 125_000), (2, 220_000), (6, 245_000)];
+
+/// Sends `instructions` in one transaction and returns the compute units they consumed
+fn measure(litesvm: &mut LiteSVM, instructions: &[Instruction], signer: &Keypair) -> u64 {
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signer.pubkey()),
+        &[signer],
+        litesvm.latest_blockhash(),
+    );
+    match litesvm.send_transaction(transaction) {
+        Ok(metadata) => metadata.compute_units_consumed,
+        Err(failed) => panic!("transaction failed: {:?}\n{:#?}", failed.err, failed.meta.logs),
+    }
+}
+
+#[test]
+fn test_initialize_enhanced_compute_units() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    for (index, privacy_level) in [PrivacyLevel::Public, PrivacyLevel::Confidential, PrivacyLevel::ZeroKnowledge]
+        .into_iter()
+        .enumerate()
+    {
+        let commitment_hash = [index as u8 + 1; 32];
+        let (escrow_account, commitment_storage) =
+            get_enhanced_escrow_pdas(&test_environment.program_id, &alice.pubkey(), &commitment_hash);
+
+        let instruction = build_initialize_enhanced_instruction(
+            commitment_hash,
+            1 * TOKEN_B,
+            privacy_level,
+            CommitmentScheme::Sha256,
+            1 * TOKEN_A,
+            enhanced_escrow_expiry(&test_environment),
+            InitializeEnhancedAccounts {
+                escrow_account,
+                commitment_storage,
+                initializer: alice.pubkey(),
+                initializer_deposit_token_account: test_environment.alice_token_account_a,
+                initializer_receive_token_account: test_environment.alice_token_account_b,
+                deposit_mint: test_environment.token_mint_a.pubkey(),
+            },
+        );
+
+        let units = measure(&mut test_environment.litesvm, &[instruction], &alice);
+        println!("initialize_enhanced (privacy level {}): {} CU", index, units);
+        assert!(
+            units <= INITIALIZE_ENHANCED_MAX_UNITS,
+            "initialize_enhanced used {} CU, budget is {}",
+            units,
+            INITIALIZE_ENHANCED_MAX_UNITS
+        );
+    }
+}
+
+#[test]
+fn test_verify_zk_proofs_compute_units() {
+    // No routing inputs only runs the validity check. The routing key fixes
+    // its input count, so each count gets its own environment.
+    for (routing_input_count, budget) in VERIFY_ZK_PROOFS_MAX_UNITS {
+        let mut test_environment = setup_escrow_test();
+        let alice = test_environment.alice.insecure_clone();
+        let keys = initialize_proof_verifiers(&mut test_environment, routing_input_count.max(1));
+
+        let (escrow_account, _commitment_storage) = execute_initialize_enhanced(
+            &mut test_environment,
+            [0x10; 32],
+            1 * TOKEN_B,
+            PrivacyLevel::ZeroKnowledge,
+            CommitmentScheme::Sha256,
+            0,
+        )
+        .unwrap();
+
+        // The escrow's own validity inputs, so the binding checks pass, then
+        // routing inputs starting with the escrow's address
+        let mut public_inputs = escrow_validity_inputs(&test_environment, escrow_account);
+        if routing_input_count > 0 {
+            public_inputs.push(pubkey_field_element(&escrow_account));
+        }
+        public_inputs.resize(VALIDITY_PROOF_PUBLIC_INPUTS + routing_input_count, [7u8; 32]);
+        let (validity_proof, routing_proof) = keys.prove(&public_inputs);
+
+        let instruction = build_verify_zk_proofs_instruction(
+            escrow_account,
+            alice.pubkey(),
+            validity_proof,
+            routing_proof,
+            &public_inputs,
+        );
+
+        // Includes the 150 CU of the compute budget instruction
+        let units = measure(
+            &mut test_environment.litesvm,
+            &[verify_zk_proofs_compute_budget(), instruction],
+            &alice,
+        );
+        let input_count = public_inputs.len();
+        println!("verify_zk_proofs ({} public inputs): {} CU", input_count, units);
+        assert!(
+            units <= budget,
+            "verify_zk_proofs with {} public inputs used {} CU, budget is {}",
+            input_count,
+            units,
+            budget
+        );
+    }
+}
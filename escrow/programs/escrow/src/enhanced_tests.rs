@@ -0,0 +1,1444 @@
+use anchor_lang::AccountDeserialize;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+
+use crate::escrow_test_helpers::{
+    advance_clock, assert_anchor_error, build_cancel_enhanced_instruction, build_execute_atomic_swap_instruction,
+    build_expire_enhanced_instruction, build_initialize_enhanced_instruction,
+    build_initialize_order_verifier_instruction, build_initialize_proof_verifier_instruction,
+    build_prove_order_properties_instruction, build_reveal_and_verify_instruction, build_verify_zk_proofs_instruction,
+    compute_order_commitment, enhanced_escrow_expiry, escrow_validity_inputs, execute_initialize_enhanced, g1_generator,
+    g2_generator, generator_order_verifying_key, get_enhanced_escrow_pdas, get_enhanced_vault_pdas,
+    get_order_verifier_pda, get_proof_batch_pda, get_proof_verifier_pda, initialize_proof_verifiers,
+    order_verifying_key, proof_verifying_key, send_transaction_for_logs, setup_escrow_test, token_2022_balance,
+    use_token_2022_mints,
+    verify_zk_proofs_compute_budget, with_token_program, EscrowTestEnvironment, InitializeEnhancedAccounts, ZkProofKeys,
+    ENHANCED_ESCROW_LIFETIME, TOKEN_A, TOKEN_B,
+};
+use crate::state::{
+    CommitmentScheme, CommitmentStorage, EnhancedEscrowState, OrderDetails, PrivacyLevel, ProofCircuit,
+    ORDER_PROOF_PUBLIC_INPUTS, VALIDITY_PROOF_PUBLIC_INPUTS,
+};
+use crate::error::EscrowError;
+use crate::handlers::{order_proof_public_inputs, pubkey_field_element};
+use groth16_test_utils::InputsKeys;
+use solana_kite::{
+    assert_token_balance, check_account_is_closed, mint_tokens_to_account, send_transaction_from_instructions,
+};
+
+fn sample_order() -> OrderDetails {
+    OrderDetails {
+        token_amount: 2 * TOKEN_A,
+        min_receive_amount: 1 * TOKEN_B,
+        preferred_venue: 3,
+        max_slippage: 50,
+    }
+}
+
+// Nonces for Poseidon commitments must be field elements, so keep the top byte clear
+fn sample_nonce(seed: u8) -> [u8; 32] {
+    let mut nonce = [seed; 32];
+    nonce[0] = 0;
+    nonce
+}
+
+#[test]
+fn test_reveal_with_sha256_and_poseidon_commitments_succeeds() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    for scheme in [CommitmentScheme::Sha256, CommitmentScheme::Poseidon] {
+        let order = sample_order();
+        let nonce = sample_nonce(scheme as u8 + 1);
+        let commitment_hash = compute_order_commitment(&order, &nonce, scheme);
+
+        let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+            &mut test_environment,
+            commitment_hash,
+            1 * TOKEN_B,
+            PrivacyLevel::Confidential,
+            scheme,
+            0,
+        )
+        .unwrap();
+
+        let reveal_instruction = build_reveal_and_verify_instruction(
+            commitment_storage,
+            escrow_account,
+            alice.pubkey(),
+            test_environment.alice_token_account_a,
+            test_environment.token_mint_a.pubkey(),
+            &order,
+            nonce,
+        );
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![reveal_instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert!(result.is_ok(), "Reveal should match the commitment");
+
+        let storage_account = test_environment.litesvm.get_account(&commitment_storage).unwrap();
+        let storage = CommitmentStorage::try_deserialize(&mut storage_account.data.as_slice()).unwrap();
+        assert!(storage.revealed);
+        assert!(storage.scheme == scheme);
+
+        let escrow_data = test_environment.litesvm.get_account(&escrow_account).unwrap();
+        let escrow = EnhancedEscrowState::try_deserialize(&mut escrow_data.data.as_slice()).unwrap();
+        assert_eq!(escrow.initializer_amount, order.token_amount);
+        assert_eq!(escrow.optimal_venue_id, order.preferred_venue);
+    }
+}
+
+#[test]
+fn test_reveal_with_wrong_opening_fails() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    let order = sample_order();
+    let nonce = sample_nonce(7);
+    let commitment_hash = compute_order_commitment(&order, &nonce, CommitmentScheme::Poseidon);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::Confidential,
+        CommitmentScheme::Poseidon,
+        0,
+    )
+    .unwrap();
+
+    let mut wrong_order = sample_order();
+    wrong_order.max_slippage += 1;
+    let attempts = [
+        (wrong_order, nonce),
+        (sample_order(), sample_nonce(8)),
+        // Not a field element, so the Poseidon syscall rejects it
+        (sample_order(), [0xff; 32]),
+    ];
+    for (order, nonce) in attempts {
+        let reveal_instruction = build_reveal_and_verify_instruction(
+            commitment_storage,
+            escrow_account,
+            alice.pubkey(),
+            test_environment.alice_token_account_a,
+            test_environment.token_mint_a.pubkey(),
+            &order,
+            nonce,
+        );
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![reveal_instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert!(result.is_err(), "Reveal with the wrong opening should fail");
+    }
+
+    // The SHA-256 opening of the same order doesn't open a Poseidon commitment
+    let sha256_commitment = compute_order_commitment(&sample_order(), &nonce, CommitmentScheme::Sha256);
+    assert_ne!(sha256_commitment, commitment_hash);
+}
+
+#[test]
+fn test_reveal_deposits_into_vault() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    let order = sample_order();
+    let nonce = sample_nonce(11);
+    let commitment_hash = compute_order_commitment(&order, &nonce, CommitmentScheme::Sha256);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::Confidential,
+        CommitmentScheme::Sha256,
+        0,
+    )
+    .unwrap();
+
+    // The vault exists from initialization, owned by the vault authority
+    let (vault_account, vault_authority) = get_enhanced_vault_pdas(&test_environment.program_id, &escrow_account);
+    let vault = test_environment.litesvm.get_account(&vault_account).unwrap();
+    assert_eq!(&vault.data[32..64], vault_authority.as_ref());
+    assert_token_balance(&test_environment.litesvm, &vault_account, 0, "Nothing is deposited before the reveal");
+
+    let reveal_instruction = build_reveal_and_verify_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        &order,
+        nonce,
+    );
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![reveal_instruction], &[&alice], &alice.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &vault_account,
+        order.token_amount,
+        "The vault should hold the revealed amount",
+    );
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        10 * TOKEN_A - order.token_amount,
+        "Alice should have deposited the revealed amount",
+    );
+}
+
+#[test]
+fn test_deposit_at_initialization_must_match_reveal() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    let order = sample_order();
+    for (seed, deposit_amount) in [(12, order.token_amount - 1), (13, order.token_amount)] {
+        let nonce = sample_nonce(seed);
+        let commitment_hash = compute_order_commitment(&order, &nonce, CommitmentScheme::Poseidon);
+        let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+            &mut test_environment,
+            commitment_hash,
+            1 * TOKEN_B,
+            PrivacyLevel::Confidential,
+            CommitmentScheme::Poseidon,
+            deposit_amount,
+        )
+        .unwrap();
+
+        let (vault_account, _) = get_enhanced_vault_pdas(&test_environment.program_id, &escrow_account);
+        assert_token_balance(&test_environment.litesvm, &vault_account, deposit_amount, "Deposited at initialization");
+
+        let reveal_instruction = build_reveal_and_verify_instruction(
+            commitment_storage,
+            escrow_account,
+            alice.pubkey(),
+            test_environment.alice_token_account_a,
+            test_environment.token_mint_a.pubkey(),
+            &order,
+            nonce,
+        );
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![reveal_instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert_eq!(
+            result.is_ok(),
+            deposit_amount == order.token_amount,
+            "The reveal should only succeed if the deposit matches the order"
+        );
+        assert_token_balance(&test_environment.litesvm, &vault_account, deposit_amount, "The reveal deposits nothing more");
+    }
+}
+
+fn initialize_order_verifier(test_environment: &mut EscrowTestEnvironment) {
+    let authority = test_environment.program_authority.insecure_clone();
+    let instruction = build_initialize_order_verifier_instruction(authority.pubkey(), &generator_order_verifying_key());
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&authority], &authority.pubkey())
+        .unwrap();
+}
+
+#[test]
+fn test_order_verifier_can_only_be_initialized_once() {
+    let mut test_environment = setup_escrow_test();
+    initialize_order_verifier(&mut test_environment);
+
+    let authority = test_environment.program_authority.insecure_clone();
+    let instruction = build_initialize_order_verifier_instruction(authority.pubkey(), &generator_order_verifying_key());
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![instruction],
+        &[&authority],
+        &authority.pubkey(),
+    );
+    assert!(result.is_err(), "The order verifying key should not be replaceable");
+}
+
+#[test]
+fn test_order_verifier_needs_the_upgrade_authority() {
+    let mut test_environment = setup_escrow_test();
+
+    // Bob tries to front-run the deployer with a key he controls
+    let bob = test_environment.bob.insecure_clone();
+    let instruction = build_initialize_order_verifier_instruction(bob.pubkey(), &generator_order_verifying_key());
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Only the upgrade authority should set the order verifying key");
+    assert!(
+        test_environment.litesvm.get_account(&get_order_verifier_pda(&test_environment.program_id)).is_none(),
+        "A rejected initialization should not create the verifier"
+    );
+
+    // The deployer can still initialize it afterwards
+    initialize_order_verifier(&mut test_environment);
+}
+
+#[test]
+fn test_prove_order_properties_rejects_invalid_proofs() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    initialize_order_verifier(&mut test_environment);
+
+    let order = sample_order();
+    let commitment_hash = compute_order_commitment(&order, &sample_nonce(9), CommitmentScheme::Poseidon);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::ZeroKnowledge,
+        CommitmentScheme::Poseidon,
+        order.token_amount,
+    )
+    .unwrap();
+
+    let proofs = [
+        ([0u8; 64], [0u8; 128], [0u8; 64]),
+        (g1_generator(), g2_generator(), g1_generator()),
+        // Not points on the curve
+        ([0xff; 64], [0xff; 128], [0xff; 64]),
+    ];
+    for proof in proofs {
+        let instruction = build_prove_order_properties_instruction(
+            commitment_storage,
+            escrow_account,
+            alice.pubkey(),
+            proof,
+            order.max_slippage,
+        );
+        let result =
+            send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&alice], &alice.pubkey());
+        assert!(result.is_err(), "An invalid order proof should be rejected");
+    }
+
+    let storage_account = test_environment.litesvm.get_account(&commitment_storage).unwrap();
+    let storage = CommitmentStorage::try_deserialize(&mut storage_account.data.as_slice()).unwrap();
+    assert!(!storage.validated);
+    assert_eq!(storage.proven_slippage_cap, 0);
+}
+
+#[test]
+fn test_prove_order_properties_requires_poseidon_commitment() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    initialize_order_verifier(&mut test_environment);
+
+    let commitment_hash = compute_order_commitment(&sample_order(), &sample_nonce(10), CommitmentScheme::Sha256);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::ZeroKnowledge,
+        CommitmentScheme::Sha256,
+        0,
+    )
+    .unwrap();
+
+    let instruction = build_prove_order_properties_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        (g1_generator(), g2_generator(), g1_generator()),
+        50,
+    );
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&alice], &alice.pubkey());
+    assert!(result.is_err(), "SHA-256 commitments can't be opened in-circuit");
+}
+
+#[test]
+fn test_prove_order_properties_requires_deposit() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    initialize_order_verifier(&mut test_environment);
+
+    // The proof binds the vault's balance, so there must be one
+    let commitment_hash = compute_order_commitment(&sample_order(), &sample_nonce(14), CommitmentScheme::Poseidon);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::ZeroKnowledge,
+        CommitmentScheme::Poseidon,
+        0,
+    )
+    .unwrap();
+
+    let instruction = build_prove_order_properties_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        (g1_generator(), g2_generator(), g1_generator()),
+        50,
+    );
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&alice], &alice.pubkey());
+    assert!(result.is_err(), "Order properties can't be proven before the deposit");
+}
+
+/// Alice's escrow with `2 * TOKEN_A` deposited at initialization
+fn initialize_funded_escrow(test_environment: &mut EscrowTestEnvironment, seed: u8) -> (Pubkey, Pubkey) {
+    let commitment_hash = compute_order_commitment(&sample_order(), &sample_nonce(seed), CommitmentScheme::Sha256);
+    execute_initialize_enhanced(
+        test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::Confidential,
+        CommitmentScheme::Sha256,
+        2 * TOKEN_A,
+    )
+    .unwrap()
+}
+
+fn assert_enhanced_escrow_closed(
+    test_environment: &EscrowTestEnvironment,
+    escrow_account: Pubkey,
+    commitment_storage: Pubkey,
+) {
+    let (vault_account, _) = get_enhanced_vault_pdas(&test_environment.program_id, &escrow_account);
+    let proof_batch = get_proof_batch_pda(&test_environment.program_id, &escrow_account);
+    check_account_is_closed(&test_environment.litesvm, &escrow_account, "Escrow account should be closed");
+    check_account_is_closed(&test_environment.litesvm, &commitment_storage, "Commitment storage should be closed");
+    check_account_is_closed(&test_environment.litesvm, &proof_batch, "Proof batch should be closed");
+    check_account_is_closed(&test_environment.litesvm, &vault_account, "Vault should be closed");
+}
+
+/// Verifies Alice's proofs for `escrow_account`, which creates its proof batch
+fn verify_escrow_proofs(test_environment: &mut EscrowTestEnvironment, keys: &ZkProofKeys, escrow_account: Pubkey) {
+    let alice = test_environment.alice.insecure_clone();
+    let validity_inputs = escrow_validity_inputs(test_environment, escrow_account);
+    let (validity_proof, routing_proof) = keys.prove(&validity_inputs);
+    let verify_instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &validity_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), verify_instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_cancel_enhanced_refunds_and_closes_accounts() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = initialize_funded_escrow(&mut test_environment, 20);
+
+    // Create the proof batch too
+    verify_escrow_proofs(&mut test_environment, &keys, escrow_account);
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        8 * TOKEN_A,
+        "Alice should have deposited 2 token A",
+    );
+
+    // The proof batch can't be left behind
+    let cancel_without_batch = build_cancel_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    let result =
+        send_transaction_for_logs(&mut test_environment.litesvm, &[cancel_without_batch], &[&alice], &alice.pubkey());
+    assert_anchor_error(
+        result,
+        EscrowError::ProofBatchMissing.into(),
+        "Cancelling a verified escrow without its proof batch should fail",
+    );
+
+    let cancel_instruction = build_cancel_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        true,
+    );
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![cancel_instruction], &[&alice], &alice.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        10 * TOKEN_A,
+        "Alice should have her deposit back",
+    );
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_cancel_enhanced_requires_initializer() {
+    let mut test_environment = setup_escrow_test();
+    let bob = test_environment.bob.insecure_clone();
+    let (escrow_account, commitment_storage) = initialize_funded_escrow(&mut test_environment, 21);
+
+    let cancel_instruction = build_cancel_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        bob.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![cancel_instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Only the initializer can cancel");
+
+    let (vault_account, _) = get_enhanced_vault_pdas(&test_environment.program_id, &escrow_account);
+    assert_token_balance(&test_environment.litesvm, &vault_account, 2 * TOKEN_A, "The deposit should stay in the vault");
+}
+
+#[test]
+fn test_expire_enhanced_only_after_deadline() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+    let (escrow_account, commitment_storage) = initialize_funded_escrow(&mut test_environment, 22);
+
+    // Bob cranks, Alice gets everything back
+    let expire_instruction = build_expire_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![expire_instruction.clone()],
+        &[&bob],
+        &bob.pubkey(),
+    );
+    assert!(result.is_err(), "The escrow hasn't expired yet");
+
+    advance_clock(&mut test_environment, ENHANCED_ESCROW_LIFETIME);
+    let alice_lamports = test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports;
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![expire_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        10 * TOKEN_A,
+        "Alice should have her deposit back",
+    );
+    assert!(
+        test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports > alice_lamports,
+        "Alice should get the rent back"
+    );
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_expire_enhanced_closes_the_proof_batch() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = initialize_funded_escrow(&mut test_environment, 23);
+    verify_escrow_proofs(&mut test_environment, &keys, escrow_account);
+    advance_clock(&mut test_environment, ENHANCED_ESCROW_LIFETIME);
+
+    // Bob cranks, but leaves out the proof batch
+    let expire_without_batch = build_expire_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    let result = send_transaction_for_logs(&mut test_environment.litesvm, &[expire_without_batch], &[&bob], &bob.pubkey());
+    assert_anchor_error(
+        result,
+        EscrowError::ProofBatchMissing.into(),
+        "Expiring a verified escrow without its proof batch should fail",
+    );
+
+    let expire_instruction = build_expire_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        true,
+    );
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![expire_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        10 * TOKEN_A,
+        "Alice should have her deposit back",
+    );
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_initialize_enhanced_rejects_past_expiry() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    advance_clock(&mut test_environment, 1_000);
+
+    let commitment_hash = [0x42; 32];
+    let (escrow_account, commitment_storage) =
+        get_enhanced_escrow_pdas(&test_environment.program_id, &alice.pubkey(), &commitment_hash);
+    let instruction = build_initialize_enhanced_instruction(
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::Confidential,
+        CommitmentScheme::Sha256,
+        0,
+        1_000,
+        InitializeEnhancedAccounts {
+            escrow_account,
+            commitment_storage,
+            initializer: alice.pubkey(),
+            initializer_deposit_token_account: test_environment.alice_token_account_a,
+            initializer_receive_token_account: test_environment.alice_token_account_b,
+            deposit_mint: test_environment.token_mint_a.pubkey(),
+        },
+    );
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&alice], &alice.pubkey());
+    assert!(result.is_err(), "An escrow can't start out expired");
+}
+
+/// Alice's escrow for `order`, revealed and with its proofs verified, ready for Bob to take
+fn prepare_swap(
+    test_environment: &mut EscrowTestEnvironment,
+    keys: &ZkProofKeys,
+    order: &OrderDetails,
+    seed: u8,
+    taker_amount: u64,
+) -> (Pubkey, Pubkey) {
+    let alice = test_environment.alice.insecure_clone();
+    let nonce = sample_nonce(seed);
+    let commitment_hash = compute_order_commitment(order, &nonce, CommitmentScheme::Sha256);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        test_environment,
+        commitment_hash,
+        taker_amount,
+        PrivacyLevel::Confidential,
+        CommitmentScheme::Sha256,
+        order.token_amount,
+    )
+    .unwrap();
+
+    let reveal_instruction = build_reveal_and_verify_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        order,
+        nonce,
+    );
+    let validity_inputs = escrow_validity_inputs(test_environment, escrow_account);
+    let (validity_proof, routing_proof) = keys.prove(&validity_inputs);
+    let verify_instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &validity_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![reveal_instruction, verify_zk_proofs_compute_budget(), verify_instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+
+    (escrow_account, commitment_storage)
+}
+
+#[test]
+fn test_execute_atomic_swap_closes_all_accounts() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+
+    let order = sample_order();
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = prepare_swap(&mut test_environment, &keys, &order, 30, 1 * TOKEN_B);
+
+    let escrow_data = test_environment.litesvm.get_account(&escrow_account).unwrap();
+    let escrow = EnhancedEscrowState::try_deserialize(&mut escrow_data.data.as_slice()).unwrap();
+    assert_eq!(escrow.min_receive_amount, order.min_receive_amount);
+    assert_eq!(escrow.max_slippage, order.max_slippage);
+
+    let alice_lamports = test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports;
+    let swap_instruction = build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, true);
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_b,
+        1 * TOKEN_B,
+        "Alice should have received 1 token B",
+    );
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.bob_token_account_a,
+        order.token_amount,
+        "Bob should have received the deposit",
+    );
+
+    // All four accounts are gone, and their rent went to Alice
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+    assert!(
+        test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports > alice_lamports,
+        "Alice should get the rent back"
+    );
+}
+
+#[test]
+fn test_reveal_rejects_orders_the_swap_cannot_meet() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+
+    // The order wants at least 2 token B, but the escrow only asks Bob for 1
+    let mut below_minimum = sample_order();
+    below_minimum.min_receive_amount = 2 * TOKEN_B;
+    // More than 100% slippage
+    let mut unbounded_slippage = sample_order();
+    unbounded_slippage.max_slippage = 10_001;
+
+    for (seed, order) in [(31, below_minimum), (33, unbounded_slippage)] {
+        let nonce = sample_nonce(seed);
+        let commitment_hash = compute_order_commitment(&order, &nonce, CommitmentScheme::Sha256);
+        let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+            &mut test_environment,
+            commitment_hash,
+            1 * TOKEN_B,
+            PrivacyLevel::Confidential,
+            CommitmentScheme::Sha256,
+            order.token_amount,
+        )
+        .unwrap();
+
+        let reveal_instruction = build_reveal_and_verify_instruction(
+            commitment_storage,
+            escrow_account,
+            alice.pubkey(),
+            test_environment.alice_token_account_a,
+            test_environment.token_mint_a.pubkey(),
+            &order,
+            nonce,
+        );
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![reveal_instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert!(result.is_err(), "Reveal of an order the taker amount can't satisfy should fail");
+
+        let storage_account = test_environment.litesvm.get_account(&commitment_storage).unwrap();
+        let storage = CommitmentStorage::try_deserialize(&mut storage_account.data.as_slice()).unwrap();
+        assert!(!storage.revealed, "The commitment should stay unrevealed");
+    }
+}
+
+/// `swap_instruction` taken by Alice, paying from the account she receives into,
+/// so nothing actually arrives however much is sent
+fn taken_by_alice(test_environment: &EscrowTestEnvironment, mut swap_instruction: Instruction) -> Instruction {
+    swap_instruction.accounts[4] = AccountMeta::new(test_environment.alice.pubkey(), true);
+    swap_instruction.accounts[5].pubkey = test_environment.alice_token_account_b;
+    swap_instruction.accounts[6].pubkey = test_environment.alice_token_account_a;
+    swap_instruction
+}
+
+#[test]
+fn test_execute_atomic_swap_enforces_order_bounds_on_what_arrives() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let mint_authority = test_environment._mint_authority.insecure_clone();
+
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = prepare_swap(&mut test_environment, &keys, &sample_order(), 34, 1 * TOKEN_B);
+
+    // Alice takes her own escrow, so the token B she sends never leaves her
+    // receive account, and the order's minimum isn't met
+    mint_tokens_to_account(
+        &mut test_environment.litesvm,
+        &test_environment.token_mint_b.pubkey(),
+        &test_environment.alice_token_account_b,
+        5 * TOKEN_B,
+        &mint_authority,
+    )
+    .unwrap();
+    let swap_instruction = taken_by_alice(
+        &test_environment,
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, true),
+    );
+    let result = send_transaction_for_logs(&mut test_environment.litesvm, &[swap_instruction], &[&alice], &alice.pubkey());
+    assert_anchor_error(
+        result,
+        EscrowError::MinimumReceiveNotMet.into(),
+        "A swap that delivers less than the order's minimum should fail",
+    );
+    assert!(test_environment.litesvm.get_account(&escrow_account).is_some(), "Escrow should still be open");
+}
+
+#[test]
+fn test_execute_atomic_swap_rejects_other_receive_account() {
+    let mut test_environment = setup_escrow_test();
+    let bob = test_environment.bob.insecure_clone();
+
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = prepare_swap(&mut test_environment, &keys, &sample_order(), 32, 1 * TOKEN_B);
+
+    // Bob pays himself instead of Alice
+    let mut swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, true);
+    swap_instruction.accounts[7].pubkey = test_environment.bob_token_account_b;
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Swap must pay the initializer's receive account");
+}
+
+#[test]
+fn test_public_escrow_swaps_without_reveal_or_proofs() {
+    let mut test_environment = setup_escrow_test();
+    let bob = test_environment.bob.insecure_clone();
+
+    // Nothing to reveal, so the deposit can't wait for a reveal
+    let result = execute_initialize_enhanced(
+        &mut test_environment,
+        [40; 32],
+        1 * TOKEN_B,
+        PrivacyLevel::Public,
+        CommitmentScheme::Sha256,
+        0,
+    );
+    assert!(result.is_err(), "Public escrows must deposit at initialization");
+
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        [41; 32],
+        1 * TOKEN_B,
+        PrivacyLevel::Public,
+        CommitmentScheme::Sha256,
+        2 * TOKEN_A,
+    )
+    .unwrap();
+
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.bob_token_account_a,
+        2 * TOKEN_A,
+        "Bob should have received the deposit",
+    );
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_confidential_escrow_requires_reveal_but_no_proofs() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+
+    let order = sample_order();
+    let nonce = sample_nonce(42);
+    let (escrow_account, commitment_storage) = initialize_funded_escrow(&mut test_environment, 42);
+
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![swap_instruction.clone()],
+        &[&bob],
+        &bob.pubkey(),
+    );
+    assert!(result.is_err(), "Confidential escrows can't be taken before the reveal");
+
+    let reveal_instruction = build_reveal_and_verify_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        &order,
+        nonce,
+    );
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![reveal_instruction], &[&alice], &alice.pubkey())
+        .unwrap();
+
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_swap_must_close_existing_proof_batch() {
+    let mut test_environment = setup_escrow_test();
+    let bob = test_environment.bob.insecure_clone();
+
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let (escrow_account, commitment_storage) = prepare_swap(&mut test_environment, &keys, &sample_order(), 43, 1 * TOKEN_B);
+
+    // Leaving the proof batch out would strand Alice's rent
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "The proof batch should be required once it exists");
+}
+
+#[test]
+fn test_zero_knowledge_escrow_requires_order_proof() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+
+    let order = sample_order();
+    let nonce = sample_nonce(44);
+    let commitment_hash = compute_order_commitment(&order, &nonce, CommitmentScheme::Poseidon);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::ZeroKnowledge,
+        CommitmentScheme::Poseidon,
+        order.token_amount,
+    )
+    .unwrap();
+
+    // The opening is correct, but would put the amounts on-chain
+    let reveal_instruction = build_reveal_and_verify_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        &order,
+        nonce,
+    );
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![reveal_instruction], &[&alice], &alice.pubkey());
+    assert!(result.is_err(), "Zero-knowledge escrows can't be revealed");
+
+    // Verified proofs alone don't open the swap without the order proof
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+    let validity_inputs = escrow_validity_inputs(&test_environment, escrow_account);
+    let (validity_proof, routing_proof) = keys.prove(&validity_inputs);
+    let verify_instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &validity_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), verify_instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, true);
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Zero-knowledge escrows need a proven order");
+}
+
+#[test]
+fn test_zero_knowledge_escrow_with_invalid_proof_cannot_be_executed() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+    let authority = test_environment.program_authority.insecure_clone();
+
+    let order_keys = InputsKeys::setup(ORDER_PROOF_PUBLIC_INPUTS);
+    let instruction = build_initialize_order_verifier_instruction(authority.pubkey(), &order_verifying_key(&order_keys));
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&authority], &authority.pubkey())
+        .unwrap();
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+
+    let order = sample_order();
+    let commitment_hash = compute_order_commitment(&order, &sample_nonce(53), CommitmentScheme::Poseidon);
+    let (escrow_account, commitment_storage) = execute_initialize_enhanced(
+        &mut test_environment,
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::ZeroKnowledge,
+        CommitmentScheme::Poseidon,
+        order.token_amount,
+    )
+    .unwrap();
+
+    // The order is proven, so only the validity proof stands in the way
+    let order_inputs = order_proof_public_inputs(&commitment_hash, 1 * TOKEN_B, order.max_slippage, order.token_amount);
+    let order_proof = order_keys.prove(&order_inputs);
+    let instruction = build_prove_order_properties_instruction(
+        commitment_storage,
+        escrow_account,
+        alice.pubkey(),
+        (order_proof.a, order_proof.b, order_proof.c),
+        order.max_slippage,
+    );
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&alice], &alice.pubkey())
+        .unwrap();
+
+    // The hidden minimum is proven to be at most the taker amount, so the swap
+    // holds Alice to receiving all of it
+    let escrow_data = test_environment.litesvm.get_account(&escrow_account).unwrap();
+    let escrow = EnhancedEscrowState::try_deserialize(&mut escrow_data.data.as_slice()).unwrap();
+    assert_eq!(escrow.min_receive_amount, 1 * TOKEN_B);
+    assert_eq!(escrow.max_slippage, order.max_slippage);
+
+    // A validity proof under another key
+    let validity_inputs = escrow_validity_inputs(&test_environment, escrow_account);
+    let invalid_proof = InputsKeys::setup(VALIDITY_PROOF_PUBLIC_INPUTS).prove(&validity_inputs).to_bytes();
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), invalid_proof, [0u8; 256], &validity_inputs);
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert!(result.is_err(), "An invalid validity proof should be rejected");
+
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Zero-knowledge escrows need verified proofs");
+    assert_token_balance(&test_environment.litesvm, &test_environment.bob_token_account_a, 0, "Bob should get nothing");
+
+    // With a valid proof the same escrow can be taken
+    let (validity_proof, routing_proof) = keys.prove(&validity_inputs);
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &validity_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, true);
+    send_transaction_from_instructions(&mut test_environment.litesvm, vec![swap_instruction], &[&bob], &bob.pubkey())
+        .unwrap();
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_execute_atomic_swap_with_token_2022_transfer_fee() {
+    let mut test_environment = setup_escrow_test();
+    let bob = test_environment.bob.insecure_clone();
+
+    // Token B charges 1% on every transfer
+    use_token_2022_mints(&mut test_environment, 0, 100);
+
+    let (escrow_account, commitment_storage) = initialize_token_2022_escrow(&mut test_environment, 45);
+
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![with_token_program(swap_instruction, spl_token_2022::ID)],
+        &[&bob],
+        &bob.pubkey(),
+    )
+    .unwrap();
+
+    // Bob covers the fee, so Alice receives exactly the taker amount
+    let alice_received = token_2022_balance(&test_environment, &test_environment.alice_token_account_b);
+    assert_eq!(alice_received, 1 * TOKEN_B);
+    let bob_sent = 5 * TOKEN_B - token_2022_balance(&test_environment, &test_environment.bob_token_account_b);
+    assert!(bob_sent > 1 * TOKEN_B, "Bob should have paid the transfer fee");
+
+    assert_eq!(token_2022_balance(&test_environment, &test_environment.bob_token_account_a), 2 * TOKEN_A);
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+}
+
+#[test]
+fn test_execute_atomic_swap_checks_what_arrives_after_transfer_fees() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let mint_authority = test_environment._mint_authority.insecure_clone();
+
+    // Token B charges 1% on every transfer, which the swap sends on top of the
+    // taker amount
+    use_token_2022_mints(&mut test_environment, 0, 100);
+    let (escrow_account, commitment_storage) = initialize_token_2022_escrow(&mut test_environment, 47);
+
+    // Alice takes her own escrow. The grossed up amount is sent, but none of
+    // it arrives, so the swap must fail on the balance it measures.
+    let mint_instruction = spl_token_2022::instruction::mint_to(
+        &spl_token_2022::ID,
+        &test_environment.token_mint_b.pubkey(),
+        &test_environment.alice_token_account_b,
+        &mint_authority.pubkey(),
+        &[],
+        5 * TOKEN_B,
+    )
+    .unwrap();
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![mint_instruction],
+        &[&mint_authority],
+        &mint_authority.pubkey(),
+    )
+    .unwrap();
+
+    let swap_instruction = taken_by_alice(
+        &test_environment,
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false),
+    );
+    let result = send_transaction_for_logs(
+        &mut test_environment.litesvm,
+        &[with_token_program(swap_instruction, spl_token_2022::ID)],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert_anchor_error(
+        result,
+        EscrowError::SlippageExceeded.into(),
+        "A swap that delivers less than the taker amount should fail",
+    );
+    assert_eq!(token_2022_balance(&test_environment, &test_environment.alice_token_account_b), 5 * TOKEN_B);
+    assert!(test_environment.litesvm.get_account(&escrow_account).is_some(), "Escrow should still be open");
+}
+
+/// Initializes a public escrow of 2 token A for 1 token B through Token-2022
+fn initialize_token_2022_escrow(test_environment: &mut EscrowTestEnvironment, seed: u8) -> (Pubkey, Pubkey) {
+    let alice = test_environment.alice.insecure_clone();
+    let commitment_hash = [seed; 32];
+    let (escrow_account, commitment_storage) =
+        get_enhanced_escrow_pdas(&test_environment.program_id, &alice.pubkey(), &commitment_hash);
+    let initialize_instruction = build_initialize_enhanced_instruction(
+        commitment_hash,
+        1 * TOKEN_B,
+        PrivacyLevel::Public,
+        CommitmentScheme::Sha256,
+        2 * TOKEN_A,
+        enhanced_escrow_expiry(test_environment),
+        InitializeEnhancedAccounts {
+            escrow_account,
+            commitment_storage,
+            initializer: alice.pubkey(),
+            initializer_deposit_token_account: test_environment.alice_token_account_a,
+            initializer_receive_token_account: test_environment.alice_token_account_b,
+            deposit_mint: test_environment.token_mint_a.pubkey(),
+        },
+    );
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![with_token_program(initialize_instruction, spl_token_2022::ID)],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+    (escrow_account, commitment_storage)
+}
+
+#[test]
+fn test_vaults_close_with_withheld_transfer_fees() {
+    use spl_token_2022::extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions};
+
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+
+    // Token A, the deposit, charges 1% on every transfer, so each vault
+    // holds withheld fees once funded
+    use_token_2022_mints(&mut test_environment, 100, 0);
+
+    let (escrow_account, commitment_storage) = initialize_token_2022_escrow(&mut test_environment, 48);
+    let swap_instruction =
+        build_execute_atomic_swap_instruction(&test_environment, escrow_account, commitment_storage, false);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![with_token_program(swap_instruction, spl_token_2022::ID)],
+        &[&bob],
+        &bob.pubkey(),
+    )
+    .unwrap();
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+
+    let (escrow_account, commitment_storage) = initialize_token_2022_escrow(&mut test_environment, 49);
+    let cancel_instruction = build_cancel_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![with_token_program(cancel_instruction, spl_token_2022::ID)],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+
+    let (escrow_account, commitment_storage) = initialize_token_2022_escrow(&mut test_environment, 50);
+    advance_clock(&mut test_environment, ENHANCED_ESCROW_LIFETIME);
+    let expire_instruction = build_expire_enhanced_instruction(
+        escrow_account,
+        commitment_storage,
+        alice.pubkey(),
+        test_environment.alice_token_account_a,
+        test_environment.token_mint_a.pubkey(),
+        false,
+    );
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![with_token_program(expire_instruction, spl_token_2022::ID)],
+        &[&bob],
+        &bob.pubkey(),
+    )
+    .unwrap();
+    assert_enhanced_escrow_closed(&test_environment, escrow_account, commitment_storage);
+
+    // The fees withheld in the three vaults moved to the mint
+    let mint = test_environment.litesvm.get_account(&test_environment.token_mint_a.pubkey()).unwrap();
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data).unwrap();
+    let withheld_amount = u64::from(mint.get_extension::<TransferFeeConfig>().unwrap().withheld_amount);
+    assert!(withheld_amount > 0, "The vaults' withheld fees should have been harvested to the mint");
+}
+
+#[test]
+fn test_verify_zk_proofs_binds_inputs_to_escrow() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let keys = initialize_proof_verifiers(&mut test_environment, 1);
+
+    let (escrow_account, _) = initialize_funded_escrow(&mut test_environment, 46);
+    let (other_escrow_account, _) = initialize_funded_escrow(&mut test_environment, 47);
+    let validity_inputs = escrow_validity_inputs(&test_environment, escrow_account);
+
+    let mut wrong_taker_amount = validity_inputs.clone();
+    wrong_taker_amount[1][31] ^= 1;
+    let mut swapped_mints = validity_inputs.clone();
+    swapped_mints.swap(2, 3);
+    // Each proof is valid for its own inputs, only they aren't the escrow's
+    let attempts = [
+        // Proven about another escrow, with another commitment
+        escrow_validity_inputs(&test_environment, other_escrow_account),
+        wrong_taker_amount,
+        swapped_mints,
+    ];
+    for public_inputs in attempts {
+        let (validity_proof, routing_proof) = keys.prove(&public_inputs);
+        let instruction =
+            build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &public_inputs);
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![verify_zk_proofs_compute_budget(), instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert!(result.is_err(), "Inputs that aren't the escrow's should be rejected");
+    }
+
+    // Routing inputs only
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), [0u8; 256], [0u8; 256], &[[7u8; 32]; 2]);
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert!(result.is_err(), "The validity inputs can't be left out");
+    let proof_batch = get_proof_batch_pda(&test_environment.program_id, &escrow_account);
+    assert!(test_environment.litesvm.get_account(&proof_batch).is_none(), "No proof batch should be stored");
+
+    // A routing proof about another escrow
+    let mut other_routing_inputs = validity_inputs.clone();
+    other_routing_inputs.push(pubkey_field_element(&other_escrow_account));
+    let (validity_proof, routing_proof) = keys.prove(&other_routing_inputs);
+    let instruction = build_verify_zk_proofs_instruction(
+        escrow_account,
+        alice.pubkey(),
+        validity_proof,
+        routing_proof,
+        &other_routing_inputs,
+    );
+    let result = send_transaction_for_logs(
+        &mut test_environment.litesvm,
+        &[verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert_anchor_error(
+        result,
+        EscrowError::PublicInputsMismatch.into(),
+        "A routing proof about another escrow should be rejected",
+    );
+
+    // A routing proof passed without routing inputs would be stored unchecked
+    let (validity_proof, _) = keys.prove(&validity_inputs);
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &validity_inputs);
+    let result = send_transaction_for_logs(
+        &mut test_environment.litesvm,
+        &[verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert_anchor_error(
+        result,
+        EscrowError::InvalidRoutingProof.into(),
+        "A routing proof without routing inputs should be rejected",
+    );
+
+    // The escrow's own inputs, followed by routing inputs about the escrow
+    let mut public_inputs = validity_inputs;
+    public_inputs.push(pubkey_field_element(&escrow_account));
+    let (validity_proof, routing_proof) = keys.prove(&public_inputs);
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &public_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+
+    let escrow_data = test_environment.litesvm.get_account(&escrow_account).unwrap();
+    let escrow = EnhancedEscrowState::try_deserialize(&mut escrow_data.data.as_slice()).unwrap();
+    assert!(escrow.zk_proof_verified);
+}
+
+#[test]
+fn test_verify_zk_proofs_rejects_invalid_proofs() {
+    let mut test_environment = setup_escrow_test();
+    let alice = test_environment.alice.insecure_clone();
+    let keys = initialize_proof_verifiers(&mut test_environment, 2);
+
+    let (escrow_account, _) = initialize_funded_escrow(&mut test_environment, 51);
+    let (other_escrow_account, _) = initialize_funded_escrow(&mut test_environment, 52);
+    let mut public_inputs = escrow_validity_inputs(&test_environment, escrow_account);
+    public_inputs.extend([pubkey_field_element(&escrow_account), [7u8; 32]]);
+    let (validity_proof, routing_proof) = keys.prove(&public_inputs);
+
+    // Valid proofs, but of other inputs or under other keys
+    let mut other_escrow_inputs = escrow_validity_inputs(&test_environment, other_escrow_account);
+    other_escrow_inputs.extend([pubkey_field_element(&escrow_account), [7u8; 32]]);
+    let (other_escrow_proof, _) = keys.prove(&other_escrow_inputs);
+    let mut other_routing_inputs = public_inputs.clone();
+    other_routing_inputs[VALIDITY_PROOF_PUBLIC_INPUTS + 1] = [8u8; 32];
+    let (_, other_routing_proof) = keys.prove(&other_routing_inputs);
+    let other_key_proof = InputsKeys::setup(VALIDITY_PROOF_PUBLIC_INPUTS)
+        .prove(&public_inputs[..VALIDITY_PROOF_PUBLIC_INPUTS])
+        .to_bytes();
+
+    let attempts = [
+        (other_escrow_proof, routing_proof),
+        (other_key_proof, routing_proof),
+        (validity_proof, other_routing_proof),
+        // Each proof in the other's place
+        (routing_proof, validity_proof),
+        // Not points on the curve
+        ([0xff; 256], routing_proof),
+        (validity_proof, [0u8; 256]),
+    ];
+    for (validity_proof, routing_proof) in attempts {
+        let instruction =
+            build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &public_inputs);
+        let result = send_transaction_from_instructions(
+            &mut test_environment.litesvm,
+            vec![verify_zk_proofs_compute_budget(), instruction],
+            &[&alice],
+            &alice.pubkey(),
+        );
+        assert!(result.is_err(), "Proofs that don't verify should be rejected");
+    }
+    let proof_batch = get_proof_batch_pda(&test_environment.program_id, &escrow_account);
+    assert!(test_environment.litesvm.get_account(&proof_batch).is_none(), "No proof batch should be stored");
+
+    // Routing inputs the routing key doesn't take
+    let mut extra_routing_inputs = public_inputs.clone();
+    extra_routing_inputs.push([9u8; 32]);
+    let instruction = build_verify_zk_proofs_instruction(
+        escrow_account,
+        alice.pubkey(),
+        validity_proof,
+        routing_proof,
+        &extra_routing_inputs,
+    );
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert!(result.is_err(), "The routing proof should only verify for its own inputs");
+
+    let instruction =
+        build_verify_zk_proofs_instruction(escrow_account, alice.pubkey(), validity_proof, routing_proof, &public_inputs);
+    send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![verify_zk_proofs_compute_budget(), instruction],
+        &[&alice],
+        &alice.pubkey(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_proof_verifier_needs_the_upgrade_authority() {
+    let mut test_environment = setup_escrow_test();
+    let authority = test_environment.program_authority.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+    let validity_key = proof_verifying_key(&InputsKeys::setup(VALIDITY_PROOF_PUBLIC_INPUTS));
+
+    // Bob tries to front-run the deployer with a key he controls
+    let instruction = build_initialize_proof_verifier_instruction(bob.pubkey(), ProofCircuit::Validity, &validity_key);
+    let result =
+        send_transaction_from_instructions(&mut test_environment.litesvm, vec![instruction], &[&bob], &bob.pubkey());
+    assert!(result.is_err(), "Only the upgrade authority should set a proof verifying key");
+
+    // The validity key must take exactly the escrow's validity inputs
+    let short_key = proof_verifying_key(&InputsKeys::setup(VALIDITY_PROOF_PUBLIC_INPUTS - 1));
+    let instruction = build_initialize_proof_verifier_instruction(authority.pubkey(), ProofCircuit::Validity, &short_key);
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![instruction],
+        &[&authority],
+        &authority.pubkey(),
+    );
+    assert!(result.is_err(), "A validity key for other inputs should be rejected");
+    assert!(
+        test_environment
+            .litesvm
+            .get_account(&get_proof_verifier_pda(&test_environment.program_id, ProofCircuit::Validity))
+            .is_none(),
+        "A rejected initialization should not create the verifier"
+    );
+
+    // The deployer can still initialize both keys, once
+    initialize_proof_verifiers(&mut test_environment, 1);
+    let instruction = build_initialize_proof_verifier_instruction(authority.pubkey(), ProofCircuit::Validity, &validity_key);
+    let result = send_transaction_from_instructions(
+        &mut test_environment.litesvm,
+        vec![instruction],
+        &[&authority],
+        &authority.pubkey(),
+    );
+    assert!(result.is_err(), "The validity verifying key should not be replaceable");
+}
@@ -26,6 +26,24 @@ pub enum ErrorCode {
 
     #[msg("Failed to close vault during refund")]
     FailedRefundClosure,
+
+    #[msg("Offer id must be the maker's next offer id")]
+    InvalidOfferId,
+
+    #[msg("Offer expiry must be in the future")]
+    InvalidOfferExpiry,
+
+    #[msg("Offer has expired")]
+    OfferExpired,
+
+    #[msg("Offer has not expired")]
+    OfferNotExpired,
+
+    #[msg("Fill is larger than what is left of the offer")]
+    FillExceedsOffer,
+
+    #[msg("Fill is too small to pay any of the wanted token")]
+    FillTooSmall,
 }
 
 // Enhanced privacy-preserving error codes
@@ -64,9 +82,54 @@ pub enum EscrowError {
     #[msg("Insufficient public inputs")]
     InsufficientPublicInputs,
     
-    #[msg("Invalid token ratio")]
-    InvalidTokenRatio,
-    
     #[msg("Serialization error")]
     SerializationError,
+    
+    #[msg("Order proofs need a Poseidon commitment")]
+    CommitmentSchemeMismatch,
+    
+    #[msg("Invalid order properties proof")]
+    InvalidOrderProof,
+    
+    #[msg("Deposited amount does not match the order's token amount")]
+    DepositAmountMismatch,
+    
+    #[msg("Nothing has been deposited into the vault")]
+    NothingDeposited,
+    
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    
+    #[msg("Escrow has expired")]
+    EscrowExpired,
+    
+    #[msg("Escrow has not expired yet")]
+    EscrowNotExpired,
+    
+    #[msg("Initializer would receive less than the order's minimum")]
+    MinimumReceiveNotMet,
+    
+    #[msg("Slippage must be at most 10000 basis points")]
+    InvalidSlippage,
+    
+    #[msg("Zero-knowledge escrows can't reveal their order in plaintext")]
+    PlaintextRevealForbidden,
+    
+    #[msg("Order properties have not been proven")]
+    OrderNotProven,
+    
+    #[msg("The escrow's proof batch must be passed to close it")]
+    ProofBatchMissing,
+    
+    #[msg("Proof public inputs don't match the escrow")]
+    PublicInputsMismatch,
+    
+    #[msg("Only the program's upgrade authority can set a verifying key")]
+    UnauthorizedVerifierAuthority,
+    
+    #[msg("Verifying key doesn't match the circuit's public inputs")]
+    InvalidVerifyingKey,
+    
+    #[msg("Initializer would receive less than the order's slippage allows")]
+    SlippageExceeded,
 }
\ No newline at end of file
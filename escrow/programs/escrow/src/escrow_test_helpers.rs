@@ -1,14 +1,17 @@
 use solana_kite::{
-    create_associated_token_account, create_token_mint, deploy_program, mint_tokens_to_account,
-    send_transaction_from_instructions, get_pda_and_bump, SolanaKiteError,
+    create_associated_token_account, create_token_mint, mint_tokens_to_account,
+    send_transaction_from_instructions, SolanaKiteError,
 };
+use anchor_lang::solana_program::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use groth16_test_utils::InputsKeys;
 use litesvm::LiteSVM;
-use std::cell::Cell;
+use solana_account::Account;
 use solana_instruction::AccountMeta;
 use solana_instruction::Instruction;
 use solana_keypair::Keypair;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
+use solana_transaction::Transaction;
 use std::str::FromStr;
 
 pub const PROGRAM_ID: &str = "8jR5GeNzeweq35Uo84kGP3v1NcBaZWH5u62k7PxN4T2y";
@@ -19,6 +22,9 @@ pub const TOKEN_A: u64 = 1_000_000_000;
 /// Standard token unit for token B (1 token = 1_000_000_000 lamports for 9 decimals)
 pub const TOKEN_B: u64 = 1_000_000_000;
 
+/// How long enhanced escrows created by `execute_initialize_enhanced` stay open, in seconds
+pub const ENHANCED_ESCROW_LIFETIME: i64 = 24 * 60 * 60;
+
 /// Complete escrow test environment containing all necessary components for testing
 ///
 /// This struct holds all the accounts, keypairs, and state needed for comprehensive
@@ -29,6 +35,8 @@ pub struct EscrowTestEnvironment {
     pub litesvm: LiteSVM,
     /// The escrow program ID
     pub program_id: Pubkey,
+    /// The escrow program's upgrade authority
+    pub program_authority: Keypair,
     /// The mint authority that can create and mint tokens
     pub _mint_authority: Keypair,
     /// Token mint A (the first token in escrow trades)
@@ -53,7 +61,7 @@ pub struct EscrowTestEnvironment {
 ///
 /// This function performs the following setup steps:
 /// 1. Creates a new LiteSVM instance for transaction simulation
-/// 2. Deploys the escrow program to the test environment
+/// 2. Deploys the escrow program as an upgradeable program, with its own upgrade authority
 /// 3. Creates a mint authority and funds it with SOL
 /// 4. Creates two token mints (A and B) with 9 decimals
 /// 5. Creates Alice and Bob keypairs and funds them with SOL
@@ -72,12 +80,9 @@ pub struct EscrowTestEnvironment {
 /// ```rust
 /// let env = setup_escrow_test();
 ///
-/// // Create an offer using Alice
-/// let offer_id = 12345u64;
-/// let (offer_account, _) = Pubkey::find_program_address(
-///     &[b"offer", &offer_id.to_le_bytes()],
-///     &env.program_id
-/// );
+/// // Create an offer using Alice, with the next id from her counter
+/// let offer_id = next_offer_id(&env, &env.alice.pubkey());
+/// let offer_account = get_offer_pda(&env.program_id, &env.alice.pubkey(), offer_id);
 ///
 /// // Use the environment components for testing
 /// let make_offer_accounts = MakeOfferAccounts {
@@ -88,6 +93,7 @@ pub struct EscrowTestEnvironment {
 ///     token_mint_a: env.token_mint_a.pubkey(),
 ///     token_mint_b: env.token_mint_b.pubkey(),
 ///     maker_token_account_a: env.alice_token_account_a,
+///     offer_counter: get_offer_counter_pda(&env.program_id, &env.alice.pubkey()),
 ///     offer_account,
 ///     vault: spl_associated_token_account::get_associated_token_address(&offer_account, &env.token_mint_a.pubkey()),
 /// };
@@ -97,7 +103,14 @@ pub fn setup_escrow_test() -> EscrowTestEnvironment {
     let program_id = get_program_id();
 
     // Deploy the escrow program
-    deploy_program(&mut litesvm, &program_id, "../../target/deploy/escrow.so").unwrap();
+    let program_authority = Keypair::new();
+    litesvm.airdrop(&program_authority.pubkey(), 1_000_000_000).unwrap();
+    deploy_upgradeable_program(
+        &mut litesvm,
+        &program_id,
+        &program_authority.pubkey(),
+        "../../target/deploy/escrow.so",
+    );
 
     // Create and fund mint authority
     let mint_authority = Keypair::new();
@@ -162,6 +175,7 @@ pub fn setup_escrow_test() -> EscrowTestEnvironment {
     EscrowTestEnvironment {
         litesvm,
         program_id,
+        program_authority,
         _mint_authority: mint_authority,
         token_mint_a,
         token_mint_b,
@@ -178,20 +192,70 @@ pub fn get_program_id() -> Pubkey {
     Pubkey::from_str(PROGRAM_ID).unwrap()
 }
 
-thread_local! {
-    static OFFER_ID_COUNTER: Cell<u64> = Cell::new(1);
+/// Returns the ProgramData account of an upgradeable program
+pub fn get_program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::ID).0
 }
 
-/// Generates a unique offer ID for testing
-///
-/// This function returns incrementing offer IDs starting from 1, ensuring
-/// each test gets unique IDs to avoid conflicts between test cases.
-pub fn generate_offer_id() -> u64 {
-    OFFER_ID_COUNTER.with(|counter| {
-        let id = counter.get();
-        counter.set(id + 1);
-        id
-    })
+/// Deploys `program_path` under the upgradeable loader with `upgrade_authority`,
+/// as `anchor deploy` would, by writing the program and ProgramData accounts
+/// directly. Instructions that check the upgrade authority need this layout.
+pub fn deploy_upgradeable_program(
+    litesvm: &mut LiteSVM,
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+    program_path: &str,
+) {
+    let elf = std::fs::read(program_path).unwrap();
+    let program_data_address = get_program_data_address(program_id);
+
+    // UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address: Some(..) }, then the ELF
+    let mut program_data = Vec::with_capacity(UpgradeableLoaderState::size_of_programdata_metadata() + elf.len());
+    program_data.extend_from_slice(&3u32.to_le_bytes());
+    program_data.extend_from_slice(&0u64.to_le_bytes());
+    program_data.push(1);
+    program_data.extend_from_slice(upgrade_authority.as_ref());
+    program_data.extend_from_slice(&elf);
+
+    // UpgradeableLoaderState::Program { programdata_address }
+    let mut program = Vec::with_capacity(UpgradeableLoaderState::size_of_program());
+    program.extend_from_slice(&2u32.to_le_bytes());
+    program.extend_from_slice(program_data_address.as_ref());
+
+    // The ProgramData account must exist before the program account is loaded
+    for (address, data, executable) in [(program_data_address, program_data, false), (*program_id, program, true)] {
+        let account = Account {
+            lamports: litesvm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: bpf_loader_upgradeable::ID,
+            executable,
+            rent_epoch: 0,
+        };
+        litesvm.set_account(address, account).unwrap();
+    }
+}
+
+/// Returns the PDA of a maker's offer counter
+pub fn get_offer_counter_pda(program_id: &Pubkey, maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer_counter", maker.as_ref()], program_id).0
+}
+
+/// Returns the PDA of a maker's offer, offers are namespaced by maker
+pub fn get_offer_pda(program_id: &Pubkey, maker: &Pubkey, offer_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer", maker.as_ref(), &offer_id.to_le_bytes()], program_id).0
+}
+
+/// The id the maker's next offer must use: 0 before their first offer, then
+/// whatever their offer counter holds
+pub fn next_offer_id(test_env: &EscrowTestEnvironment, maker: &Pubkey) -> u64 {
+    use anchor_lang::AccountDeserialize;
+
+    match test_env.litesvm.get_account(&get_offer_counter_pda(&test_env.program_id, maker)) {
+        Some(account) => {
+            crate::state::OfferCounter::try_deserialize(&mut account.data.as_slice()).unwrap().next_id
+        }
+        None => 0,
+    }
 }
 
 pub fn get_make_offer_discriminator() -> Vec<u8> {
@@ -209,6 +273,11 @@ pub fn get_refund_offer_discriminator() -> Vec<u8> {
     anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
 }
 
+pub fn get_close_expired_offer_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:close_expired_offer";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
 pub struct MakeOfferAccounts {
     pub associated_token_program: Pubkey,
     pub token_program: Pubkey,
@@ -217,6 +286,7 @@ pub struct MakeOfferAccounts {
     pub token_mint_a: Pubkey,
     pub token_mint_b: Pubkey,
     pub maker_token_account_a: Pubkey,
+    pub offer_counter: Pubkey,
     pub offer_account: Pubkey,
     pub vault: Pubkey,
 }
@@ -243,6 +313,7 @@ pub fn build_make_offer_accounts(
         token_mint_a,
         token_mint_b,
         maker_token_account_a,
+        offer_counter: get_offer_counter_pda(&get_program_id(), &maker),
         offer_account,
         vault,
     }
@@ -252,12 +323,20 @@ pub fn build_make_offer_instruction(
     offer_id: u64,
     token_a_offered_amount: u64,
     token_b_wanted_amount: u64,
+    expires_at: Option<i64>,
     accounts: MakeOfferAccounts,
 ) -> Instruction {
     let mut instruction_data = get_make_offer_discriminator();
     instruction_data.extend_from_slice(&offer_id.to_le_bytes());
     instruction_data.extend_from_slice(&token_a_offered_amount.to_le_bytes());
     instruction_data.extend_from_slice(&token_b_wanted_amount.to_le_bytes());
+    match expires_at {
+        Some(expires_at) => {
+            instruction_data.push(1);
+            instruction_data.extend_from_slice(&expires_at.to_le_bytes());
+        }
+        None => instruction_data.push(0),
+    }
 
     let account_metas = vec![
         AccountMeta::new_readonly(accounts.associated_token_program, false),
@@ -267,6 +346,7 @@ pub fn build_make_offer_instruction(
         AccountMeta::new_readonly(accounts.token_mint_a, false),
         AccountMeta::new_readonly(accounts.token_mint_b, false),
         AccountMeta::new(accounts.maker_token_account_a, false),
+        AccountMeta::new(accounts.offer_counter, false),
         AccountMeta::new(accounts.offer_account, false),
         AccountMeta::new(accounts.vault, false),
     ];
@@ -293,8 +373,9 @@ pub struct TakeOfferAccounts {
     pub vault: Pubkey,
 }
 
-pub fn build_take_offer_instruction(accounts: TakeOfferAccounts) -> Instruction {
-    let instruction_data = get_take_offer_discriminator();
+pub fn build_take_offer_instruction(token_a_amount: u64, accounts: TakeOfferAccounts) -> Instruction {
+    let mut instruction_data = get_take_offer_discriminator();
+    instruction_data.extend_from_slice(&token_a_amount.to_le_bytes());
 
     let account_metas = vec![
         AccountMeta::new_readonly(accounts.associated_token_program, false),
@@ -302,7 +383,7 @@ pub fn build_take_offer_instruction(accounts: TakeOfferAccounts) -> Instruction
         AccountMeta::new_readonly(accounts.system_program, false),
         AccountMeta::new(accounts.taker, true),
         AccountMeta::new(accounts.maker, false),
-        AccountMeta::new_readonly(accounts.token_mint_a, false),
+        AccountMeta::new(accounts.token_mint_a, false),
         AccountMeta::new_readonly(accounts.token_mint_b, false),
         AccountMeta::new(accounts.taker_token_account_a, false),
         AccountMeta::new(accounts.taker_token_account_b, false),
@@ -335,7 +416,41 @@ pub fn build_refund_offer_instruction(accounts: RefundOfferAccounts) -> Instruct
         AccountMeta::new_readonly(accounts.token_program, false),
         AccountMeta::new_readonly(accounts.system_program, false),
         AccountMeta::new(accounts.maker, true),
-        AccountMeta::new_readonly(accounts.token_mint_a, false),
+        AccountMeta::new(accounts.token_mint_a, false),
+        AccountMeta::new(accounts.maker_token_account_a, false),
+        AccountMeta::new(accounts.offer_account, false),
+        AccountMeta::new(accounts.vault, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+pub struct CloseExpiredOfferAccounts {
+    pub associated_token_program: Pubkey,
+    pub token_program: Pubkey,
+    pub system_program: Pubkey,
+    pub closer: Pubkey,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub maker_token_account_a: Pubkey,
+    pub offer_account: Pubkey,
+    pub vault: Pubkey,
+}
+
+pub fn build_close_expired_offer_instruction(accounts: CloseExpiredOfferAccounts) -> Instruction {
+    let instruction_data = get_close_expired_offer_discriminator();
+
+    let account_metas = vec![
+        AccountMeta::new_readonly(accounts.associated_token_program, false),
+        AccountMeta::new_readonly(accounts.token_program, false),
+        AccountMeta::new_readonly(accounts.system_program, false),
+        AccountMeta::new(accounts.closer, true),
+        AccountMeta::new(accounts.maker, false),
+        AccountMeta::new(accounts.token_mint_a, false),
         AccountMeta::new(accounts.maker_token_account_a, false),
         AccountMeta::new(accounts.offer_account, false),
         AccountMeta::new(accounts.vault, false),
@@ -360,9 +475,10 @@ pub fn execute_make_offer(
     maker_token_account_a: Pubkey,
     token_a_offered_amount: u64,
     token_b_wanted_amount: u64,
+    expires_at: Option<i64>,
 ) -> Result<(Pubkey, Pubkey), SolanaKiteError> {
     // Create PDAs
-    let (offer_account, _offer_bump) = get_pda_and_bump(&[b"offer".as_ref().into(), offer_id.to_le_bytes().as_ref().into()], &test_env.program_id);
+    let offer_account = get_offer_pda(&test_env.program_id, &maker.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_env.token_mint_a.pubkey(),
@@ -383,6 +499,7 @@ pub fn execute_make_offer(
         offer_id,
         token_a_offered_amount,
         token_b_wanted_amount,
+        expires_at,
         make_offer_accounts,
     );
 
@@ -397,6 +514,8 @@ pub fn execute_make_offer(
 }
 
 /// Executes a complete take_offer flow: builds accounts and executes instruction
+///
+/// `token_a_amount` is how much of the offer to fill, the taker pays for it at the offer's price
 pub fn execute_take_offer(
     test_env: &mut EscrowTestEnvironment,
     taker: &Keypair,
@@ -406,7 +525,116 @@ pub fn execute_take_offer(
     maker_token_account_b: Pubkey,
     offer_account: Pubkey,
     vault: Pubkey,
+    token_a_amount: u64,
 ) -> Result<(), SolanaKiteError> {
+    let take_offer_instruction = take_offer_instruction(
+        test_env,
+        taker,
+        maker,
+        taker_token_account_a,
+        taker_token_account_b,
+        maker_token_account_b,
+        offer_account,
+        vault,
+        token_a_amount,
+    );
+
+    send_transaction_from_instructions(
+        &mut test_env.litesvm,
+        vec![take_offer_instruction],
+        &[taker],
+        &taker.pubkey(),
+    )
+}
+
+/// Executes take_offer like `execute_take_offer`, and returns the `OfferFilled`
+/// events it emitted. Panics with the logs if the transaction fails.
+pub fn execute_take_offer_for_events(
+    test_env: &mut EscrowTestEnvironment,
+    taker: &Keypair,
+    maker: &Keypair,
+    taker_token_account_a: Pubkey,
+    taker_token_account_b: Pubkey,
+    maker_token_account_b: Pubkey,
+    offer_account: Pubkey,
+    vault: Pubkey,
+    token_a_amount: u64,
+) -> Vec<crate::events::OfferFilled> {
+    let take_offer_instruction = take_offer_instruction(
+        test_env,
+        taker,
+        maker,
+        taker_token_account_a,
+        taker_token_account_b,
+        maker_token_account_b,
+        offer_account,
+        vault,
+        token_a_amount,
+    );
+
+    match send_transaction_for_logs(&mut test_env.litesvm, &[take_offer_instruction], &[taker], &taker.pubkey()) {
+        Ok(logs) => decode_offer_filled_events(&logs),
+        Err(logs) => panic!("transaction failed:\n{:#?}", logs),
+    }
+}
+
+/// Sends the instructions in one transaction, and returns its logs whether it
+/// succeeded or failed
+pub fn send_transaction_for_logs(
+    litesvm: &mut LiteSVM,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    payer: &Pubkey,
+) -> Result<Vec<String>, Vec<String>> {
+    let transaction =
+        Transaction::new_signed_with_payer(instructions, Some(payer), signers, litesvm.latest_blockhash());
+    litesvm
+        .send_transaction(transaction)
+        .map(|metadata| metadata.logs)
+        .map_err(|failed| failed.meta.logs)
+}
+
+/// Checks a transaction failed with the given Anchor error, eg `ErrorCode::FillTooSmall.into()`
+pub fn assert_anchor_error(result: Result<Vec<String>, Vec<String>>, error_number: u32, message: &str) {
+    let logs = result.expect_err(message);
+    let expected = format!("Error Number: {}.", error_number);
+    assert!(
+        logs.iter().any(|log| log.contains(&expected)),
+        "{}: expected error {} in logs {:#?}",
+        message,
+        error_number,
+        logs
+    );
+}
+
+/// The `OfferFilled` events in a transaction's logs. Anchor logs each event as
+/// `Program data: ` followed by the base64 of its discriminator and Borsh encoding.
+pub fn decode_offer_filled_events(logs: &[String]) -> Vec<crate::events::OfferFilled> {
+    use anchor_lang::{AnchorDeserialize, Discriminator};
+    use base64::Engine;
+
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .filter_map(|data| base64::engine::general_purpose::STANDARD.decode(data).ok())
+        .filter_map(|data| {
+            data.strip_prefix(crate::events::OfferFilled::DISCRIMINATOR)
+                .map(|event| crate::events::OfferFilled::try_from_slice(event).unwrap())
+        })
+        .collect()
+}
+
+/// Builds the take_offer instruction `execute_take_offer` sends
+pub fn take_offer_instruction(
+    test_env: &EscrowTestEnvironment,
+    taker: &Keypair,
+    maker: &Keypair,
+    taker_token_account_a: Pubkey,
+    taker_token_account_b: Pubkey,
+    maker_token_account_b: Pubkey,
+    offer_account: Pubkey,
+    vault: Pubkey,
+    token_a_amount: u64,
+) -> Instruction {
     let take_offer_accounts = TakeOfferAccounts {
         associated_token_program: spl_associated_token_account::ID,
         token_program: spl_token::ID,
@@ -422,14 +650,7 @@ pub fn execute_take_offer(
         vault,
     };
 
-    let take_offer_instruction = build_take_offer_instruction(take_offer_accounts);
-    
-    send_transaction_from_instructions(
-        &mut test_env.litesvm,
-        vec![take_offer_instruction],
-        &[taker],
-        &taker.pubkey(),
-    )
+    build_take_offer_instruction(token_a_amount, take_offer_accounts)
 }
 
 /// Executes a complete refund_offer flow: builds accounts and executes instruction
@@ -459,3 +680,756 @@ pub fn execute_refund_offer(
         &maker.pubkey(),
     )
 }
+
+/// Executes close_expired_offer, with `closer` paying for the transaction
+pub fn execute_close_expired_offer(
+    test_env: &mut EscrowTestEnvironment,
+    closer: &Keypair,
+    maker: &Pubkey,
+    maker_token_account_a: Pubkey,
+    offer_account: Pubkey,
+    vault: Pubkey,
+) -> Result<(), SolanaKiteError> {
+    let close_expired_offer_accounts = CloseExpiredOfferAccounts {
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: spl_token::ID,
+        system_program: anchor_lang::system_program::ID,
+        closer: closer.pubkey(),
+        maker: *maker,
+        token_mint_a: test_env.token_mint_a.pubkey(),
+        maker_token_account_a,
+        offer_account,
+        vault,
+    };
+
+    let close_instruction = build_close_expired_offer_instruction(close_expired_offer_accounts);
+
+    send_transaction_from_instructions(
+        &mut test_env.litesvm,
+        vec![close_instruction],
+        &[closer],
+        &closer.pubkey(),
+    )
+}
+
+pub fn get_initialize_enhanced_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:initialize_enhanced";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn get_verify_zk_proofs_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:verify_zk_proofs";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+/// Returns the enhanced escrow and commitment storage PDAs for a commitment
+pub fn get_enhanced_escrow_pdas(program_id: &Pubkey, initializer: &Pubkey, commitment_hash: &[u8; 32]) -> (Pubkey, Pubkey) {
+    let (escrow_account, _) = Pubkey::find_program_address(&[b"escrow", initializer.as_ref(), commitment_hash.as_ref()], program_id);
+    let (commitment_storage, _) = Pubkey::find_program_address(&[b"commitment", initializer.as_ref(), commitment_hash.as_ref()], program_id);
+    (escrow_account, commitment_storage)
+}
+
+pub fn get_proof_batch_pda(program_id: &Pubkey, escrow_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"proofs", escrow_account.as_ref()], program_id).0
+}
+
+/// Returns an enhanced escrow's vault token account and the PDA that owns it
+pub fn get_enhanced_vault_pdas(program_id: &Pubkey, escrow_account: &Pubkey) -> (Pubkey, Pubkey) {
+    let (vault_account, _) = Pubkey::find_program_address(&[b"vault", escrow_account.as_ref()], program_id);
+    let (vault_authority, _) = Pubkey::find_program_address(&[b"vault_authority", escrow_account.as_ref()], program_id);
+    (vault_account, vault_authority)
+}
+
+pub struct InitializeEnhancedAccounts {
+    pub escrow_account: Pubkey,
+    pub commitment_storage: Pubkey,
+    pub initializer: Pubkey,
+    pub initializer_deposit_token_account: Pubkey,
+    pub initializer_receive_token_account: Pubkey,
+    pub deposit_mint: Pubkey,
+}
+
+pub fn build_initialize_enhanced_instruction(
+    commitment_hash: [u8; 32],
+    taker_amount: u64,
+    privacy_level: crate::state::PrivacyLevel,
+    commitment_scheme: crate::state::CommitmentScheme,
+    deposit_amount: u64,
+    expires_at: i64,
+    accounts: InitializeEnhancedAccounts,
+) -> Instruction {
+    let mut instruction_data = get_initialize_enhanced_discriminator();
+    instruction_data.extend_from_slice(&commitment_hash);
+    instruction_data.extend_from_slice(&taker_amount.to_le_bytes());
+    instruction_data.push(privacy_level as u8);
+    instruction_data.push(commitment_scheme as u8);
+    instruction_data.extend_from_slice(&deposit_amount.to_le_bytes());
+    instruction_data.extend_from_slice(&expires_at.to_le_bytes());
+
+    let (vault_account, vault_authority) = get_enhanced_vault_pdas(&get_program_id(), &accounts.escrow_account);
+
+    let account_metas = vec![
+        AccountMeta::new(accounts.escrow_account, false),
+        AccountMeta::new(accounts.commitment_storage, false),
+        AccountMeta::new(accounts.initializer, true),
+        AccountMeta::new(accounts.initializer_deposit_token_account, false),
+        AccountMeta::new_readonly(accounts.initializer_receive_token_account, false),
+        AccountMeta::new_readonly(accounts.deposit_mint, false),
+        AccountMeta::new(vault_account, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(anchor_lang::solana_program::sysvar::rent::ID, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+pub fn build_verify_zk_proofs_instruction(
+    escrow_account: Pubkey,
+    initializer: Pubkey,
+    validity_proof: [u8; 256],
+    routing_proof: [u8; 256],
+    public_inputs: &[[u8; 32]],
+) -> Instruction {
+    let mut instruction_data = get_verify_zk_proofs_discriminator();
+    instruction_data.extend_from_slice(&validity_proof);
+    instruction_data.extend_from_slice(&routing_proof);
+    instruction_data.extend_from_slice(&(public_inputs.len() as u32).to_le_bytes());
+    for input in public_inputs {
+        instruction_data.extend_from_slice(input);
+    }
+
+    // The routing verifier is only passed along routing inputs
+    let program_id = get_program_id();
+    let routing_verifier = if public_inputs.len() > crate::state::VALIDITY_PROOF_PUBLIC_INPUTS {
+        get_proof_verifier_pda(&program_id, crate::state::ProofCircuit::Routing)
+    } else {
+        program_id
+    };
+
+    let account_metas = vec![
+        AccountMeta::new(escrow_account, false),
+        AccountMeta::new(get_proof_batch_pda(&program_id, &escrow_account), false),
+        AccountMeta::new_readonly(get_proof_verifier_pda(&program_id, crate::state::ProofCircuit::Validity), false),
+        AccountMeta::new_readonly(routing_verifier, false),
+        AccountMeta::new(initializer, true),
+        AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+/// Compute unit limit for transactions running `verify_zk_proofs`. Checking
+/// both Groth16 proofs can take more than the default 200k per instruction,
+/// see `compute_unit_tests`.
+pub const VERIFY_ZK_PROOFS_COMPUTE_UNIT_LIMIT: u32 = 400_000;
+
+/// Raises the transaction's compute unit limit so `verify_zk_proofs` fits
+pub fn verify_zk_proofs_compute_budget() -> Instruction {
+    solana_compute_budget_interface::ComputeBudgetInstruction::set_compute_unit_limit(
+        VERIFY_ZK_PROOFS_COMPUTE_UNIT_LIMIT,
+    )
+}
+
+pub fn get_initialize_proof_verifier_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:initialize_proof_verifier";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn get_proof_verifier_pda(program_id: &Pubkey, circuit: crate::state::ProofCircuit) -> Pubkey {
+    Pubkey::find_program_address(&[b"proof_verifier", &[circuit as u8]], program_id).0
+}
+
+pub fn build_initialize_proof_verifier_instruction(
+    authority: Pubkey,
+    circuit: crate::state::ProofCircuit,
+    verifying_key: &crate::state::ProofVerifyingKey,
+) -> Instruction {
+    let mut instruction_data = get_initialize_proof_verifier_discriminator();
+    instruction_data.push(circuit as u8);
+    instruction_data.extend_from_slice(&verifying_key.vk_alpha_g1);
+    instruction_data.extend_from_slice(&verifying_key.vk_beta_g2);
+    instruction_data.extend_from_slice(&verifying_key.vk_gamma_g2);
+    instruction_data.extend_from_slice(&verifying_key.vk_delta_g2);
+    instruction_data.extend_from_slice(&(verifying_key.vk_ic.len() as u32).to_le_bytes());
+    for ic in &verifying_key.vk_ic {
+        instruction_data.extend_from_slice(ic);
+    }
+
+    let account_metas = vec![
+        AccountMeta::new(get_proof_verifier_pda(&get_program_id(), circuit), false),
+        AccountMeta::new(authority, true),
+        AccountMeta::new_readonly(get_program_id(), false),
+        AccountMeta::new_readonly(get_program_data_address(&get_program_id()), false),
+        AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+/// The verifying key of `keys` as `initialize_proof_verifier` takes it
+pub fn proof_verifying_key(keys: &InputsKeys) -> crate::state::ProofVerifyingKey {
+    let verifying_key = keys.verifying_key();
+    crate::state::ProofVerifyingKey {
+        vk_alpha_g1: verifying_key.alpha_g1,
+        vk_beta_g2: verifying_key.beta_g2,
+        vk_gamma_g2: verifying_key.gamma_g2,
+        vk_delta_g2: verifying_key.delta_g2,
+        vk_ic: verifying_key.ic,
+    }
+}
+
+/// Proving keys for the circuits `verify_zk_proofs` checks. Test circuits
+/// that can prove any inputs stand in for the real ones.
+pub struct ZkProofKeys {
+    pub validity: InputsKeys,
+    pub routing: InputsKeys,
+}
+
+impl ZkProofKeys {
+    /// Validity and routing proofs for `public_inputs`, split the way
+    /// `verify_zk_proofs` splits them. Without routing inputs the routing
+    /// proof is left zeroed.
+    pub fn prove(&self, public_inputs: &[[u8; 32]]) -> ([u8; 256], [u8; 256]) {
+        let (validity_inputs, routing_inputs) = public_inputs.split_at(crate::state::VALIDITY_PROOF_PUBLIC_INPUTS);
+        let validity_proof = self.validity.prove(validity_inputs).to_bytes();
+        let routing_proof = if routing_inputs.is_empty() {
+            [0u8; 256]
+        } else {
+            self.routing.prove(routing_inputs).to_bytes()
+        };
+        (validity_proof, routing_proof)
+    }
+}
+
+/// Sets up keys for both circuits, the routing circuit taking
+/// `routing_public_inputs` inputs, and registers their verifying keys
+/// through the upgrade authority
+pub fn initialize_proof_verifiers(test_env: &mut EscrowTestEnvironment, routing_public_inputs: usize) -> ZkProofKeys {
+    let authority = test_env.program_authority.insecure_clone();
+    let keys = ZkProofKeys {
+        validity: InputsKeys::setup(crate::state::VALIDITY_PROOF_PUBLIC_INPUTS),
+        routing: InputsKeys::setup(routing_public_inputs),
+    };
+    // One transaction each, as both keys don't fit in one
+    for (circuit, circuit_keys) in [
+        (crate::state::ProofCircuit::Validity, &keys.validity),
+        (crate::state::ProofCircuit::Routing, &keys.routing),
+    ] {
+        let instruction =
+            build_initialize_proof_verifier_instruction(authority.pubkey(), circuit, &proof_verifying_key(circuit_keys));
+        send_transaction_from_instructions(&mut test_env.litesvm, vec![instruction], &[&authority], &authority.pubkey())
+            .unwrap();
+    }
+    keys
+}
+
+/// The validity proof inputs `verify_zk_proofs` expects for `escrow_account`, read from its state
+pub fn escrow_validity_inputs(test_env: &EscrowTestEnvironment, escrow_account: Pubkey) -> Vec<[u8; 32]> {
+    use anchor_lang::AccountDeserialize;
+
+    let account = test_env.litesvm.get_account(&escrow_account).unwrap();
+    let escrow = crate::state::EnhancedEscrowState::try_deserialize(&mut account.data.as_slice()).unwrap();
+    crate::handlers::validity_proof_public_inputs(&escrow).to_vec()
+}
+
+/// Executes initialize_enhanced for Alice, depositing token A and receiving token B
+///
+/// A `deposit_amount` of 0 leaves the deposit to the reveal. The escrow
+/// expires `ENHANCED_ESCROW_LIFETIME` seconds from the current clock.
+/// Returns the escrow account and commitment storage PDAs.
+pub fn execute_initialize_enhanced(
+    test_env: &mut EscrowTestEnvironment,
+    commitment_hash: [u8; 32],
+    taker_amount: u64,
+    privacy_level: crate::state::PrivacyLevel,
+    commitment_scheme: crate::state::CommitmentScheme,
+    deposit_amount: u64,
+) -> Result<(Pubkey, Pubkey), SolanaKiteError> {
+    let alice = test_env.alice.insecure_clone();
+    let (escrow_account, commitment_storage) =
+        get_enhanced_escrow_pdas(&test_env.program_id, &alice.pubkey(), &commitment_hash);
+
+    let instruction = build_initialize_enhanced_instruction(
+        commitment_hash,
+        taker_amount,
+        privacy_level,
+        commitment_scheme,
+        deposit_amount,
+        enhanced_escrow_expiry(test_env),
+        InitializeEnhancedAccounts {
+            escrow_account,
+            commitment_storage,
+            initializer: alice.pubkey(),
+            initializer_deposit_token_account: test_env.alice_token_account_a,
+            initializer_receive_token_account: test_env.alice_token_account_b,
+            deposit_mint: test_env.token_mint_a.pubkey(),
+        },
+    );
+
+    send_transaction_from_instructions(&mut test_env.litesvm, vec![instruction], &[&alice], &alice.pubkey())?;
+
+    Ok((escrow_account, commitment_storage))
+}
+
+/// The expiry `execute_initialize_enhanced` uses
+pub fn enhanced_escrow_expiry(test_env: &EscrowTestEnvironment) -> i64 {
+    let clock: anchor_lang::prelude::Clock = test_env.litesvm.get_sysvar();
+    clock.unix_timestamp + ENHANCED_ESCROW_LIFETIME
+}
+
+/// Moves the clock `seconds` forward
+pub fn advance_clock(test_env: &mut EscrowTestEnvironment, seconds: i64) {
+    let mut clock: anchor_lang::prelude::Clock = test_env.litesvm.get_sysvar();
+    clock.unix_timestamp += seconds;
+    test_env.litesvm.set_sysvar(&clock);
+}
+
+pub fn get_reveal_and_verify_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:reveal_and_verify";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn build_reveal_and_verify_instruction(
+    commitment_storage: Pubkey,
+    escrow_account: Pubkey,
+    initializer: Pubkey,
+    initializer_deposit_token_account: Pubkey,
+    deposit_mint: Pubkey,
+    order_details: &crate::state::OrderDetails,
+    nonce: [u8; 32],
+) -> Instruction {
+    let mut instruction_data = get_reveal_and_verify_discriminator();
+    instruction_data.extend_from_slice(&order_details.token_amount.to_le_bytes());
+    instruction_data.extend_from_slice(&order_details.min_receive_amount.to_le_bytes());
+    instruction_data.push(order_details.preferred_venue);
+    instruction_data.extend_from_slice(&order_details.max_slippage.to_le_bytes());
+    instruction_data.extend_from_slice(&nonce);
+
+    let (vault_account, _) = get_enhanced_vault_pdas(&get_program_id(), &escrow_account);
+    let account_metas = vec![
+        AccountMeta::new(commitment_storage, false),
+        AccountMeta::new(escrow_account, false),
+        AccountMeta::new_readonly(initializer, true),
+        AccountMeta::new(initializer_deposit_token_account, false),
+        AccountMeta::new(vault_account, false),
+        AccountMeta::new_readonly(deposit_mint, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+/// Commitment to `(order_details, nonce)` under `scheme`, as the program recomputes it
+pub fn compute_order_commitment(
+    order_details: &crate::state::OrderDetails,
+    nonce: &[u8; 32],
+    scheme: crate::state::CommitmentScheme,
+) -> [u8; 32] {
+    match scheme {
+        crate::state::CommitmentScheme::Sha256 => {
+            let mut order_bytes = Vec::new();
+            order_bytes.extend_from_slice(&order_details.token_amount.to_le_bytes());
+            order_bytes.extend_from_slice(&order_details.min_receive_amount.to_le_bytes());
+            order_bytes.push(order_details.preferred_venue);
+            order_bytes.extend_from_slice(&order_details.max_slippage.to_le_bytes());
+            anchor_lang::solana_program::hash::hashv(&[&order_bytes, nonce]).to_bytes()
+        }
+        crate::state::CommitmentScheme::Poseidon => {
+            let inputs = order_details.poseidon_inputs(nonce);
+            let parts: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+            solana_poseidon::hashv(solana_poseidon::Parameters::Bn254X5, solana_poseidon::Endianness::BigEndian, &parts)
+                .unwrap()
+                .to_bytes()
+        }
+    }
+}
+
+pub fn get_initialize_order_verifier_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:initialize_order_verifier";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn get_prove_order_properties_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:prove_order_properties";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn get_order_verifier_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"order_verifier"], program_id).0
+}
+
+pub fn build_initialize_order_verifier_instruction(
+    authority: Pubkey,
+    verifying_key: &crate::state::OrderVerifyingKey,
+) -> Instruction {
+    let mut instruction_data = get_initialize_order_verifier_discriminator();
+    instruction_data.extend_from_slice(&verifying_key.vk_alpha_g1);
+    instruction_data.extend_from_slice(&verifying_key.vk_beta_g2);
+    instruction_data.extend_from_slice(&verifying_key.vk_gamma_g2);
+    instruction_data.extend_from_slice(&verifying_key.vk_delta_g2);
+    for ic in &verifying_key.vk_ic {
+        instruction_data.extend_from_slice(ic);
+    }
+
+    let account_metas = vec![
+        AccountMeta::new(get_order_verifier_pda(&get_program_id()), false),
+        AccountMeta::new(authority, true),
+        AccountMeta::new_readonly(get_program_id(), false),
+        AccountMeta::new_readonly(get_program_data_address(&get_program_id()), false),
+        AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+pub fn build_prove_order_properties_instruction(
+    commitment_storage: Pubkey,
+    escrow_account: Pubkey,
+    initializer: Pubkey,
+    proof: ([u8; 64], [u8; 128], [u8; 64]),
+    slippage_cap: u16,
+) -> Instruction {
+    let mut instruction_data = get_prove_order_properties_discriminator();
+    instruction_data.extend_from_slice(&proof.0);
+    instruction_data.extend_from_slice(&proof.1);
+    instruction_data.extend_from_slice(&proof.2);
+    instruction_data.extend_from_slice(&slippage_cap.to_le_bytes());
+
+    let account_metas = vec![
+        AccountMeta::new(commitment_storage, false),
+        AccountMeta::new(escrow_account, false),
+        AccountMeta::new_readonly(get_order_verifier_pda(&get_program_id()), false),
+        AccountMeta::new_readonly(initializer, true),
+    ];
+
+    Instruction {
+        program_id: get_program_id(),
+        accounts: account_metas,
+        data: instruction_data,
+    }
+}
+
+/// BN254 G1 generator in the alt_bn128 syscall encoding
+pub fn g1_generator() -> [u8; 64] {
+    let mut point = [0u8; 64];
+    point[31] = 1;
+    point[63] = 2;
+    point
+}
+
+/// BN254 G2 generator in the alt_bn128 syscall encoding (`x.c1 || x.c0 || y.c1 || y.c0`)
+pub fn g2_generator() -> [u8; 128] {
+    let hex = concat!(
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+    );
+    let mut point = [0u8; 128];
+    for (i, byte) in point.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    point
+}
+
+/// A well-formed verifying key made of generators. No real proof verifies
+/// against it, which is what the rejection tests need.
+pub fn generator_order_verifying_key() -> crate::state::OrderVerifyingKey {
+    crate::state::OrderVerifyingKey {
+        vk_alpha_g1: g1_generator(),
+        vk_beta_g2: g2_generator(),
+        vk_gamma_g2: g2_generator(),
+        vk_delta_g2: g2_generator(),
+        vk_ic: [g1_generator(); crate::state::ORDER_PROOF_PUBLIC_INPUTS + 1],
+    }
+}
+
+/// The verifying key of `keys`, which must take `ORDER_PROOF_PUBLIC_INPUTS`
+/// inputs, as `initialize_order_verifier` takes it
+pub fn order_verifying_key(keys: &InputsKeys) -> crate::state::OrderVerifyingKey {
+    let verifying_key = keys.verifying_key();
+    crate::state::OrderVerifyingKey {
+        vk_alpha_g1: verifying_key.alpha_g1,
+        vk_beta_g2: verifying_key.beta_g2,
+        vk_gamma_g2: verifying_key.gamma_g2,
+        vk_delta_g2: verifying_key.delta_g2,
+        vk_ic: verifying_key.ic.try_into().unwrap(),
+    }
+}
+
+pub fn get_cancel_enhanced_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:cancel_enhanced";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+pub fn get_expire_enhanced_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:expire_enhanced";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+/// Accounts shared by `cancel_enhanced` and `expire_enhanced`. The proof batch
+/// is passed if it exists, otherwise the program ID stands in for it.
+fn enhanced_refund_account_metas(
+    escrow_account: Pubkey,
+    commitment_storage: Pubkey,
+    initializer: AccountMeta,
+    initializer_deposit_token_account: Pubkey,
+    deposit_mint: Pubkey,
+    proof_batch_exists: bool,
+) -> Vec<AccountMeta> {
+    let program_id = get_program_id();
+    let proof_batch = if proof_batch_exists {
+        AccountMeta::new(get_proof_batch_pda(&program_id, &escrow_account), false)
+    } else {
+        AccountMeta::new_readonly(program_id, false)
+    };
+    let (vault_account, vault_authority) = get_enhanced_vault_pdas(&program_id, &escrow_account);
+
+    vec![
+        AccountMeta::new(escrow_account, false),
+        AccountMeta::new(commitment_storage, false),
+        proof_batch,
+        initializer,
+        AccountMeta::new(initializer_deposit_token_account, false),
+        AccountMeta::new(vault_account, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new(deposit_mint, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+    ]
+}
+
+pub fn build_cancel_enhanced_instruction(
+    escrow_account: Pubkey,
+    commitment_storage: Pubkey,
+    initializer: Pubkey,
+    initializer_deposit_token_account: Pubkey,
+    deposit_mint: Pubkey,
+    proof_batch_exists: bool,
+) -> Instruction {
+    Instruction {
+        program_id: get_program_id(),
+        accounts: enhanced_refund_account_metas(
+            escrow_account,
+            commitment_storage,
+            AccountMeta::new(initializer, true),
+            initializer_deposit_token_account,
+            deposit_mint,
+            proof_batch_exists,
+        ),
+        data: get_cancel_enhanced_discriminator(),
+    }
+}
+
+/// `expire_enhanced` needs no signature: whoever pays the fee cranks it
+pub fn build_expire_enhanced_instruction(
+    escrow_account: Pubkey,
+    commitment_storage: Pubkey,
+    initializer: Pubkey,
+    initializer_deposit_token_account: Pubkey,
+    deposit_mint: Pubkey,
+    proof_batch_exists: bool,
+) -> Instruction {
+    Instruction {
+        program_id: get_program_id(),
+        accounts: enhanced_refund_account_metas(
+            escrow_account,
+            commitment_storage,
+            AccountMeta::new(initializer, false),
+            initializer_deposit_token_account,
+            deposit_mint,
+            proof_batch_exists,
+        ),
+        data: get_expire_enhanced_discriminator(),
+    }
+}
+
+pub fn get_execute_atomic_swap_discriminator() -> Vec<u8> {
+    let discriminator_input = b"global:execute_atomic_swap";
+    anchor_lang::solana_program::hash::hash(discriminator_input).to_bytes()[..8].to_vec()
+}
+
+/// Bob takes Alice's enhanced escrow: he pays token B and receives the token A in the vault.
+/// As for the refunds, the program ID stands in for a proof batch that doesn't exist.
+pub fn build_execute_atomic_swap_instruction(
+    test_env: &EscrowTestEnvironment,
+    escrow_account: Pubkey,
+    commitment_storage: Pubkey,
+    proof_batch_exists: bool,
+) -> Instruction {
+    let program_id = get_program_id();
+    let (vault_account, vault_authority) = get_enhanced_vault_pdas(&program_id, &escrow_account);
+    let proof_batch = if proof_batch_exists {
+        AccountMeta::new(get_proof_batch_pda(&program_id, &escrow_account), false)
+    } else {
+        AccountMeta::new_readonly(program_id, false)
+    };
+
+    let account_metas = vec![
+        AccountMeta::new(escrow_account, false),
+        AccountMeta::new(commitment_storage, false),
+        proof_batch,
+        AccountMeta::new(test_env.alice.pubkey(), false),
+        AccountMeta::new(test_env.bob.pubkey(), true),
+        AccountMeta::new(test_env.bob_token_account_b, false),
+        AccountMeta::new(test_env.bob_token_account_a, false),
+        AccountMeta::new(test_env.alice_token_account_b, false),
+        AccountMeta::new(vault_account, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new(test_env.token_mint_a.pubkey(), false),
+        AccountMeta::new_readonly(test_env.token_mint_b.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+    ];
+
+    Instruction {
+        program_id,
+        accounts: account_metas,
+        data: get_execute_atomic_swap_discriminator(),
+    }
+}
+
+/// Replaces the environment's mints and token accounts with Token-2022 ones,
+/// with the same balances as `setup_escrow_test`. Each mint charges a transfer
+/// fee of the given basis points, with no maximum; a mint given 0 has no
+/// transfer fee extension at all.
+///
+/// The instruction builders pass the classic token program, so swap it with
+/// `with_token_program` before sending.
+pub fn use_token_2022_mints(
+    test_env: &mut EscrowTestEnvironment,
+    token_a_fee_basis_points: u16,
+    token_b_fee_basis_points: u16,
+) {
+    use spl_token_2022::extension::ExtensionType;
+
+    let mint_authority = test_env._mint_authority.insecure_clone();
+    let token_mint_a = Keypair::new();
+    let token_mint_b = Keypair::new();
+
+    let mut instructions = Vec::new();
+    for (mint, fee_basis_points) in [(&token_mint_a, token_a_fee_basis_points), (&token_mint_b, token_b_fee_basis_points)] {
+        let extensions = if fee_basis_points > 0 { vec![ExtensionType::TransferFeeConfig] } else { vec![] };
+        let space =
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extensions).unwrap();
+        instructions.push(anchor_lang::solana_program::system_instruction::create_account(
+            &mint_authority.pubkey(),
+            &mint.pubkey(),
+            test_env.litesvm.minimum_balance_for_rent_exemption(space),
+            space as u64,
+            &spl_token_2022::ID,
+        ));
+        if !extensions.is_empty() {
+            instructions.push(
+                spl_token_2022::extension::transfer_fee::instruction::initialize_transfer_fee_config(
+                    &spl_token_2022::ID,
+                    &mint.pubkey(),
+                    None,
+                    None,
+                    fee_basis_points,
+                    u64::MAX,
+                )
+                .unwrap(),
+            );
+        }
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(
+                &spl_token_2022::ID,
+                &mint.pubkey(),
+                &mint_authority.pubkey(),
+                None,
+                9,
+            )
+            .unwrap(),
+        );
+    }
+    send_transaction_from_instructions(
+        &mut test_env.litesvm,
+        instructions,
+        &[&mint_authority, &token_mint_a, &token_mint_b],
+        &mint_authority.pubkey(),
+    )
+    .unwrap();
+
+    let mut instructions = Vec::new();
+    let mut token_accounts = Vec::new();
+    for owner in [test_env.alice.pubkey(), test_env.bob.pubkey()] {
+        for mint in [token_mint_a.pubkey(), token_mint_b.pubkey()] {
+            instructions.push(spl_associated_token_account::instruction::create_associated_token_account(
+                &mint_authority.pubkey(),
+                &owner,
+                &mint,
+                &spl_token_2022::ID,
+            ));
+            token_accounts.push(spl_associated_token_account::get_associated_token_address_with_program_id(
+                &owner,
+                &mint,
+                &spl_token_2022::ID,
+            ));
+        }
+    }
+    // Alice: 10 token A, 0 token B. Bob: 0 token A, 5 token B.
+    for (mint, token_account, amount) in [
+        (token_mint_a.pubkey(), token_accounts[0], 10 * TOKEN_A),
+        (token_mint_b.pubkey(), token_accounts[3], 5 * TOKEN_B),
+    ] {
+        instructions.push(
+            spl_token_2022::instruction::mint_to(
+                &spl_token_2022::ID,
+                &mint,
+                &token_account,
+                &mint_authority.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
+        );
+    }
+    send_transaction_from_instructions(&mut test_env.litesvm, instructions, &[&mint_authority], &mint_authority.pubkey())
+        .unwrap();
+
+    test_env.token_mint_a = token_mint_a;
+    test_env.token_mint_b = token_mint_b;
+    test_env.alice_token_account_a = token_accounts[0];
+    test_env.alice_token_account_b = token_accounts[1];
+    test_env.bob_token_account_a = token_accounts[2];
+    test_env.bob_token_account_b = token_accounts[3];
+}
+
+/// `instruction` with the classic token program swapped for `token_program`
+pub fn with_token_program(mut instruction: Instruction, token_program: Pubkey) -> Instruction {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == spl_token::ID {
+            account.pubkey = token_program;
+        }
+    }
+    instruction
+}
+
+/// Balance of a Token-2022 account, which may carry extensions
+pub fn token_2022_balance(test_env: &EscrowTestEnvironment, token_account: &Pubkey) -> u64 {
+    use spl_token_2022::extension::StateWithExtensions;
+
+    let account = test_env.litesvm.get_account(token_account).unwrap();
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap().base.amount
+}
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+// Emitted by take_offer for every fill, partial or not
+#[event]
+pub struct OfferFilled {
+    pub offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    // What the taker received and paid in this fill
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    // What is left of the offer afterwards, the offer is closed once these reach 0
+    pub token_a_remaining_amount: u64,
+    pub token_b_remaining_amount: u64,
+}
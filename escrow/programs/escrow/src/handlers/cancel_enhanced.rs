@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use super::shared::{close_token_account, transfer_tokens};
+use crate::state::{CommitmentStorage, EnhancedEscrowState, ProofBatch};
+use crate::error::EscrowError;
+
+#[derive(Accounts)]
+pub struct CancelEnhanced<'info> {
+    #[account(
+        mut,
+        constraint = escrow_account.initializer_key == initializer.key() @ EscrowError::InvalidInitializer,
+        seeds = [
+            b"escrow",
+            escrow_account.initializer_key.as_ref(),
+            escrow_account.commitment_hash.as_ref()
+        ],
+        bump = escrow_account.bump,
+        close = initializer
+    )]
+    pub escrow_account: Account<'info, EnhancedEscrowState>,
+    
+    #[account(
+        mut,
+        constraint = commitment_storage.escrow_pda == escrow_account.key() @ EscrowError::EscrowMismatch,
+        close = initializer
+    )]
+    pub commitment_storage: Account<'info, CommitmentStorage>,
+    
+    // Only exists once verify_zk_proofs has run
+    #[account(
+        mut,
+        seeds = [b"proofs", escrow_account.key().as_ref()],
+        bump,
+        close = initializer
+    )]
+    pub proof_batch: Option<Account<'info, ProofBatch>>,
+    
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+    
+    #[account(
+        mut,
+        address = escrow_account.initializer_deposit_token_account @ EscrowError::InvalidInitializer
+    )]
+    pub initializer_deposit_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"vault", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Vault authority PDA
+    #[account(
+        seeds = [b"vault_authority", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut, address = escrow_account.deposit_mint @ EscrowError::EscrowMismatch)]
+    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// The maker can withdraw an escrow nobody has taken at any time
+pub fn cancel_enhanced(ctx: Context<CancelEnhanced>) -> Result<()> {
+    require_proof_batch(&ctx.accounts.escrow_account, ctx.accounts.proof_batch.as_deref())?;
+    
+    refund_enhanced_vault(
+        &ctx.accounts.escrow_account.key(),
+        ctx.bumps.vault_authority,
+        &ctx.accounts.vault_account,
+        &ctx.accounts.initializer_deposit_token_account,
+        &ctx.accounts.initializer.to_account_info(),
+        &ctx.accounts.vault_authority,
+        &ctx.accounts.deposit_mint,
+        &ctx.accounts.token_program,
+    )?;
+    
+    msg!("Enhanced escrow cancelled by the initializer");
+    Ok(())
+}
+
+/// A verified escrow has a proof batch, which must be passed so it is closed
+/// along with the escrow rather than left holding its rent
+pub fn require_proof_batch(escrow_account: &EnhancedEscrowState, proof_batch: Option<&ProofBatch>) -> Result<()> {
+    require!(
+        proof_batch.is_some() || !escrow_account.zk_proof_verified,
+        EscrowError::ProofBatchMissing
+    );
+    Ok(())
+}
+
+/// Returns whatever the vault holds to the initializer's deposit account, then
+/// closes the vault and sends its rent to `initializer`
+pub fn refund_enhanced_vault<'info>(
+    escrow_key: &Pubkey,
+    vault_authority_bump: u8,
+    vault_account: &InterfaceAccount<'info, TokenAccount>,
+    initializer_deposit_token_account: &InterfaceAccount<'info, TokenAccount>,
+    initializer: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    deposit_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let bump = [vault_authority_bump];
+    let seeds: &[&[u8]] = &[b"vault_authority", escrow_key.as_ref(), &bump];
+    
+    if vault_account.amount > 0 {
+        transfer_tokens(
+            vault_account,
+            initializer_deposit_token_account,
+            &vault_account.amount,
+            deposit_mint,
+            vault_authority,
+            token_program,
+            Some(seeds),
+        )?;
+        msg!("Returned {} to the initializer", vault_account.amount);
+    }
+    
+    close_token_account(vault_account, deposit_mint, initializer, vault_authority, token_program, Some(seeds))
+}
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use super::refund_offer::refund_offer_vault;
+use crate::{error::ErrorCode, state::Offer};
+
+#[derive(Accounts)]
+pub struct CloseExpiredOffer<'info> {
+    // Used to manage associated token accounts
+    // ie where a wallet holds a specific type of token
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    // Work with either the classic token program or
+    // the newer token extensions program
+    pub token_program: Interface<'info, TokenInterface>,
+
+    // Used to create accounts
+    pub system_program: Program<'info, System>,
+
+    // Anyone can close an expired offer, they only pay for the maker's ATA if it's gone
+    #[account(mut)]
+    pub closer: Signer<'info>,
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = closer,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = token_mint_a,
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = offer,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+// Handle the close expired offer instruction by:
+// 1. Checking the offer has a deadline and it has passed
+// 2. Returning the tokens from the vault to the maker's ATA
+// 3. Closing the vault and the offer, returning the rent to the maker
+pub fn close_expired_offer(context: Context<CloseExpiredOffer>) -> Result<()> {
+    let expires_at = context.accounts.offer.expires_at.ok_or(ErrorCode::OfferNotExpired)?;
+    require!(Clock::get()?.unix_timestamp >= expires_at, ErrorCode::OfferNotExpired);
+
+    refund_offer_vault(
+        &context.accounts.offer,
+        &context.accounts.vault,
+        &context.accounts.maker_token_account_a,
+        &context.accounts.maker.to_account_info(),
+        &context.accounts.token_mint_a,
+        &context.accounts.token_program,
+    )
+}
@@ -1,14 +1,14 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-use crate::state::{EnhancedEscrowState, CommitmentStorage, ProofBatch};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use super::cancel_enhanced::require_proof_batch;
+use super::shared::{amount_with_transfer_fee, close_token_account, transfer_tokens};
+use crate::state::{EnhancedEscrowState, CommitmentStorage, PrivacyLevel, ProofBatch};
 use crate::error::EscrowError;
 
 #[derive(Accounts)]
 pub struct ExecuteAtomicSwap<'info> {
     #[account(
         mut,
-        constraint = escrow_account.zk_proof_verified @ EscrowError::ProofsNotVerified,
-        constraint = commitment_storage.revealed @ EscrowError::CommitmentNotRevealed,
         seeds = [
             b"escrow",
             escrow_account.initializer_key.as_ref(),
@@ -20,40 +20,49 @@ pub struct ExecuteAtomicSwap<'info> {
     pub escrow_account: Account<'info, EnhancedEscrowState>,
     
     #[account(
-        constraint = commitment_storage.escrow_pda == escrow_account.key() @ EscrowError::EscrowMismatch
+        mut,
+        constraint = commitment_storage.escrow_pda == escrow_account.key() @ EscrowError::EscrowMismatch,
+        close = initializer
     )]
     pub commitment_storage: Account<'info, CommitmentStorage>,
     
+    // Only exists once verify_zk_proofs has run
     #[account(
-        constraint = proof_batch.verified @ EscrowError::ProofsNotVerified
+        mut,
+        seeds = [b"proofs", escrow_account.key().as_ref()],
+        bump,
+        close = initializer
     )]
-    pub proof_batch: Account<'info, ProofBatch>,
+    pub proof_batch: Option<Account<'info, ProofBatch>>,
     
     /// CHECK: Initializer account
-    #[account(mut)]
+    #[account(
+        mut,
+        address = escrow_account.initializer_key @ EscrowError::InvalidInitializer
+    )]
     pub initializer: AccountInfo<'info>,
     
     #[account(mut)]
     pub taker: Signer<'info>,
     
     #[account(mut)]
-    pub taker_deposit_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub taker_receive_token_account: Account<'info, TokenAccount>,
+    pub taker_deposit_token_account: InterfaceAccount<'info, TokenAccount>,
     
     #[account(mut)]
-    pub initializer_deposit_token_account: Account<'info, TokenAccount>,
+    pub taker_receive_token_account: InterfaceAccount<'info, TokenAccount>,
     
-    #[account(mut)]
-    pub initializer_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        address = escrow_account.initializer_receive_token_account @ EscrowError::InvalidInitializer
+    )]
+    pub initializer_receive_token_account: InterfaceAccount<'info, TokenAccount>,
     
     #[account(
         mut,
         seeds = [b"vault", escrow_account.key().as_ref()],
         bump
     )]
-    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_account: InterfaceAccount<'info, TokenAccount>,
     
     /// CHECK: Vault authority PDA
     #[account(
@@ -62,12 +71,26 @@ pub struct ExecuteAtomicSwap<'info> {
     )]
     pub vault_authority: AccountInfo<'info>,
     
-    pub token_program: Program<'info, Token>,
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut, address = escrow_account.deposit_mint @ EscrowError::EscrowMismatch)]
+    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(address = escrow_account.receive_mint @ EscrowError::EscrowMismatch)]
+    pub receive_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 pub fn execute_atomic_swap(ctx: Context<ExecuteAtomicSwap>) -> Result<()> {
+    check_privacy_requirements(
+        &ctx.accounts.escrow_account,
+        &ctx.accounts.commitment_storage,
+        ctx.accounts.proof_batch.as_deref(),
+    )?;
+    
     let escrow_account = &mut ctx.accounts.escrow_account;
     let clock = Clock::get()?;
+    require!(clock.unix_timestamp < escrow_account.expires_at, EscrowError::EscrowExpired);
     
     // Generate settlement proof hash for audit trail
     let settlement_data = format!(
@@ -83,40 +106,97 @@ pub fn execute_atomic_swap(ctx: Context<ExecuteAtomicSwap>) -> Result<()> {
     escrow_account.execution_timestamp = clock.unix_timestamp;
     
     // Execute atomic token swaps
-    // 1. Taker sends tokens to initializer
-    let cpi_accounts_taker = Transfer {
-        from: ctx.accounts.taker_deposit_token_account.to_account_info(),
-        to: ctx.accounts.initializer_receive_token_account.to_account_info(),
-        authority: ctx.accounts.taker.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx_taker = CpiContext::new(cpi_program.clone(), cpi_accounts_taker);
-    token::transfer(cpi_ctx_taker, escrow_account.taker_amount)?;
-    
-    // 2. Vault sends initializer's tokens to taker
+    // 1. Taker sends tokens to initializer, covering the mint's transfer fee so
+    // taker_amount arrives
+    let taker_sends = amount_with_transfer_fee(&ctx.accounts.receive_mint, escrow_account.taker_amount)?;
+    let received_before = ctx.accounts.initializer_receive_token_account.amount;
+    transfer_tokens(
+        &ctx.accounts.taker_deposit_token_account,
+        &ctx.accounts.initializer_receive_token_account,
+        &taker_sends,
+        &ctx.accounts.receive_mint,
+        &ctx.accounts.taker.to_account_info(),
+        &ctx.accounts.token_program,
+        None,
+    )?;
+    
+    // Hold what actually arrived, not what was sent, to the order's bounds
+    ctx.accounts.initializer_receive_token_account.reload()?;
+    let received = ctx.accounts.initializer_receive_token_account.amount.saturating_sub(received_before);
+    check_received_amount(escrow_account, received)?;
+    
+    // 2. Vault sends everything it holds to taker. With a transfer fee on the
+    // deposit mint that is less than initializer_amount.
     let binding = escrow_account.key();
-    let seeds = &[
-        b"vault_authority",
-        binding.as_ref(),
-        &[ctx.bumps.vault_authority]
-    ];
-    let signer = &[&seeds[..]];
-    
-    let cpi_accounts_vault = Transfer {
-        from: ctx.accounts.vault_account.to_account_info(),
-        to: ctx.accounts.taker_receive_token_account.to_account_info(),
-        authority: ctx.accounts.vault_authority.to_account_info(),
-    };
-    let cpi_ctx_vault = CpiContext::new_with_signer(
-        cpi_program,
-        cpi_accounts_vault,
-        signer
-    );
-    token::transfer(cpi_ctx_vault, escrow_account.initializer_amount)?;
+    let bump = [ctx.bumps.vault_authority];
+    let seeds: &[&[u8]] = &[b"vault_authority", binding.as_ref(), &bump];
+    
+    transfer_tokens(
+        &ctx.accounts.vault_account,
+        &ctx.accounts.taker_receive_token_account,
+        &ctx.accounts.vault_account.amount,
+        &ctx.accounts.deposit_mint,
+        &ctx.accounts.vault_authority,
+        &ctx.accounts.token_program,
+        Some(seeds),
+    )?;
+    
+    // 3. Close the now empty vault. Anchor closes the escrow, commitment
+    // storage and proof batch accounts on exit, all rent going to the initializer.
+    close_token_account(
+        &ctx.accounts.vault_account,
+        &ctx.accounts.deposit_mint,
+        &ctx.accounts.initializer,
+        &ctx.accounts.vault_authority,
+        &ctx.accounts.token_program,
+        Some(seeds),
+    )?;
     
     msg!("Atomic swap executed successfully");
     msg!("Settlement hash: {:?}", settlement_hash.to_bytes());
     msg!("Venue ID: {}", escrow_account.optimal_venue_id);
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// What each privacy level needs done before the escrow can be taken
+fn check_privacy_requirements(
+    escrow_account: &EnhancedEscrowState,
+    commitment_storage: &CommitmentStorage,
+    proof_batch: Option<&ProofBatch>,
+) -> Result<()> {
+    // A proof batch, even an optional one, is closed along with the escrow
+    require_proof_batch(escrow_account, proof_batch)?;
+    
+    match escrow_account.privacy_level {
+        PrivacyLevel::Public => {}
+        PrivacyLevel::Confidential => {
+            require!(commitment_storage.revealed, EscrowError::CommitmentNotRevealed);
+        }
+        PrivacyLevel::ZeroKnowledge => {
+            require!(commitment_storage.validated, EscrowError::OrderNotProven);
+            require!(
+                escrow_account.zk_proof_verified && proof_batch.is_some_and(|batch| batch.verified),
+                EscrowError::ProofsNotVerified
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `received` must reach the order's `min_receive_amount`, and fall short of
+/// `taker_amount` by at most `max_slippage` basis points
+fn check_received_amount(escrow_account: &EnhancedEscrowState, received: u64) -> Result<()> {
+    require!(
+        received >= escrow_account.min_receive_amount,
+        EscrowError::MinimumReceiveNotMet
+    );
+    
+    // Round the tolerance down, against the taker
+    let tolerance = escrow_account.taker_amount as u128 * escrow_account.max_slippage as u128 / 10_000;
+    require!(
+        received as u128 + tolerance >= escrow_account.taker_amount as u128,
+        EscrowError::SlippageExceeded
+    );
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use super::cancel_enhanced::{refund_enhanced_vault, require_proof_batch};
+use crate::state::{CommitmentStorage, EnhancedEscrowState, ProofBatch};
+use crate::error::EscrowError;
+
+#[derive(Accounts)]
+pub struct ExpireEnhanced<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"escrow",
+            escrow_account.initializer_key.as_ref(),
+            escrow_account.commitment_hash.as_ref()
+        ],
+        bump = escrow_account.bump,
+        close = initializer
+    )]
+    pub escrow_account: Account<'info, EnhancedEscrowState>,
+    
+    #[account(
+        mut,
+        constraint = commitment_storage.escrow_pda == escrow_account.key() @ EscrowError::EscrowMismatch,
+        close = initializer
+    )]
+    pub commitment_storage: Account<'info, CommitmentStorage>,
+    
+    // Only exists once verify_zk_proofs has run
+    #[account(
+        mut,
+        seeds = [b"proofs", escrow_account.key().as_ref()],
+        bump,
+        close = initializer
+    )]
+    pub proof_batch: Option<Account<'info, ProofBatch>>,
+    
+    /// CHECK: Initializer account, receives the rent
+    #[account(
+        mut,
+        address = escrow_account.initializer_key @ EscrowError::InvalidInitializer
+    )]
+    pub initializer: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        address = escrow_account.initializer_deposit_token_account @ EscrowError::InvalidInitializer
+    )]
+    pub initializer_deposit_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"vault", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Vault authority PDA
+    #[account(
+        seeds = [b"vault_authority", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut, address = escrow_account.deposit_mint @ EscrowError::EscrowMismatch)]
+    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// Anyone can crank an escrow past its deadline: everything goes back to the
+// initializer, so there is nothing to gain but the cleanup
+pub fn expire_enhanced(ctx: Context<ExpireEnhanced>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= ctx.accounts.escrow_account.expires_at, EscrowError::EscrowNotExpired);
+    require_proof_batch(&ctx.accounts.escrow_account, ctx.accounts.proof_batch.as_deref())?;
+    
+    refund_enhanced_vault(
+        &ctx.accounts.escrow_account.key(),
+        ctx.bumps.vault_authority,
+        &ctx.accounts.vault_account,
+        &ctx.accounts.initializer_deposit_token_account,
+        &ctx.accounts.initializer,
+        &ctx.accounts.vault_authority,
+        &ctx.accounts.deposit_mint,
+        &ctx.accounts.token_program,
+    )?;
+    
+    msg!("Enhanced escrow expired at {}", ctx.accounts.escrow_account.expires_at);
+    Ok(())
+}
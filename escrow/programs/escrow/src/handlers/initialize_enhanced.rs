@@ -1,6 +1,8 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount, Mint};
-use crate::state::{EnhancedEscrowState, CommitmentStorage, PrivacyLevel};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use super::shared::transfer_tokens;
+use crate::state::{EnhancedEscrowState, CommitmentStorage, CommitmentScheme, PrivacyLevel};
+use crate::error::EscrowError;
 
 #[derive(Accounts)]
 #[instruction(commitment_hash: [u8; 32])]
@@ -39,13 +41,34 @@ pub struct InitializeEnhanced<'info> {
         constraint = initializer_deposit_token_account.owner == initializer.key(),
         constraint = initializer_deposit_token_account.mint == deposit_mint.key()
     )]
-    pub initializer_deposit_token_account: Account<'info, TokenAccount>,
+    pub initializer_deposit_token_account: InterfaceAccount<'info, TokenAccount>,
     
-    pub initializer_receive_token_account: Account<'info, TokenAccount>,
+    pub initializer_receive_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(mint::token_program = token_program)]
+    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = initializer,
+        token::mint = deposit_mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [b"vault", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Vault authority PDA
+    #[account(
+        seeds = [b"vault_authority", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
     
-    pub deposit_mint: Account<'info, Mint>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    // Either the classic token program or the token extensions program
+    pub token_program: Interface<'info, TokenInterface>,
     pub rent: Sysvar<'info, Rent>,
 }
 
@@ -54,7 +77,17 @@ pub fn initialize_enhanced(
     commitment_hash: [u8; 32],
     taker_amount: u64,
     privacy_level: PrivacyLevel,
+    commitment_scheme: CommitmentScheme,
+    deposit_amount: u64,
+    expires_at: i64,
 ) -> Result<()> {
+    require!(expires_at > Clock::get()?.unix_timestamp, EscrowError::InvalidExpiry);
+    // Public escrows are never revealed, so they can't defer the deposit
+    require!(
+        privacy_level != PrivacyLevel::Public || deposit_amount > 0,
+        EscrowError::NothingDeposited
+    );
+    
     let escrow_account = &mut ctx.accounts.escrow_account;
     let commitment_storage = &mut ctx.accounts.commitment_storage;
     
@@ -62,9 +95,13 @@ pub fn initialize_enhanced(
     escrow_account.initializer_key = ctx.accounts.initializer.key();
     escrow_account.initializer_deposit_token_account = ctx.accounts.initializer_deposit_token_account.key();
     escrow_account.initializer_receive_token_account = ctx.accounts.initializer_receive_token_account.key();
+    escrow_account.deposit_mint = ctx.accounts.deposit_mint.key();
+    escrow_account.receive_mint = ctx.accounts.initializer_receive_token_account.mint;
     escrow_account.taker_amount = taker_amount;
+    escrow_account.min_receive_amount = 0;
+    escrow_account.max_slippage = 0;
     escrow_account.bump = ctx.bumps.escrow_account;
-    escrow_account.initializer_amount = 0; // Set during reveal
+    escrow_account.initializer_amount = deposit_amount; // 0 defers the deposit to the reveal
     
     // Privacy-preserving integration fields
     escrow_account.commitment_hash = commitment_hash;
@@ -74,6 +111,7 @@ pub fn initialize_enhanced(
     escrow_account.routing_proof_hash = [0u8; 32];
     escrow_account.settlement_proof_hash = [0u8; 32];
     escrow_account.optimal_venue_id = 0;
+    escrow_account.expires_at = expires_at;
     
     // Initialize commitment storage
     commitment_storage.commitment_hash = commitment_hash;
@@ -81,6 +119,23 @@ pub fn initialize_enhanced(
     commitment_storage.timestamp = Clock::get()?.unix_timestamp;
     commitment_storage.revealed = false;
     commitment_storage.escrow_pda = escrow_account.key();
+    commitment_storage.scheme = commitment_scheme;
+    commitment_storage.validated = false;
+    commitment_storage.proven_slippage_cap = 0;
+    
+    // Deposit now if the maker doesn't mind the amount being visible before the reveal
+    if deposit_amount > 0 {
+        transfer_tokens(
+            &ctx.accounts.initializer_deposit_token_account,
+            &ctx.accounts.vault_account,
+            &deposit_amount,
+            &ctx.accounts.deposit_mint,
+            &ctx.accounts.initializer.to_account_info(),
+            &ctx.accounts.token_program,
+            None,
+        )?;
+        msg!("Deposited {} into the vault", deposit_amount);
+    }
     
     msg!("Enhanced escrow initialized with commitment: {:?}", commitment_hash);
     Ok(())
@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use crate::error::EscrowError;
+use crate::program::Escrow;
+use crate::state::{OrderVerifier, OrderVerifyingKey};
+
+#[derive(Accounts)]
+pub struct InitializeOrderVerifier<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OrderVerifier::INIT_SPACE,
+        seeds = [b"order_verifier"],
+        bump
+    )]
+    pub order_verifier: Account<'info, OrderVerifier>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Escrow>,
+    
+    // Only the upgrade authority can set the key, so nobody can front-run the
+    // deployer with a key of their own
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ EscrowError::UnauthorizedVerifierAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+// The key can only be set once, by the program's upgrade authority, so this
+// should run right after deployment
+pub fn initialize_order_verifier(
+    ctx: Context<InitializeOrderVerifier>,
+    verifying_key: OrderVerifyingKey,
+) -> Result<()> {
+    let order_verifier = &mut ctx.accounts.order_verifier;
+    order_verifier.authority = ctx.accounts.authority.key();
+    order_verifier.verifying_key = verifying_key;
+    order_verifier.bump = ctx.bumps.order_verifier;
+    
+    msg!("Order verifying key initialized");
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use crate::error::EscrowError;
+use crate::program::Escrow;
+use crate::state::{
+    ProofCircuit, ProofVerifier, ProofVerifyingKey, MAX_ROUTING_PROOF_PUBLIC_INPUTS, VALIDITY_PROOF_PUBLIC_INPUTS,
+};
+
+#[derive(Accounts)]
+#[instruction(circuit: ProofCircuit)]
+pub struct InitializeProofVerifier<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProofVerifier::INIT_SPACE,
+        seeds = [b"proof_verifier", &[circuit as u8]],
+        bump
+    )]
+    pub proof_verifier: Account<'info, ProofVerifier>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Escrow>,
+    
+    // As for the order verifier, only the upgrade authority can set the key
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ EscrowError::UnauthorizedVerifierAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+// Each circuit's key can only be set once, by the program's upgrade authority.
+// The validity key must take exactly the escrow's validity inputs; the routing
+// key fixes how many routing inputs `verify_zk_proofs` accepts.
+pub fn initialize_proof_verifier(
+    ctx: Context<InitializeProofVerifier>,
+    circuit: ProofCircuit,
+    verifying_key: ProofVerifyingKey,
+) -> Result<()> {
+    let public_inputs = verifying_key.vk_ic.len().saturating_sub(1);
+    let fits_circuit = match circuit {
+        ProofCircuit::Validity => public_inputs == VALIDITY_PROOF_PUBLIC_INPUTS,
+        ProofCircuit::Routing => (1..=MAX_ROUTING_PROOF_PUBLIC_INPUTS).contains(&public_inputs),
+    };
+    require!(fits_circuit, EscrowError::InvalidVerifyingKey);
+    
+    let proof_verifier = &mut ctx.accounts.proof_verifier;
+    proof_verifier.authority = ctx.accounts.authority.key();
+    proof_verifier.circuit = circuit;
+    proof_verifier.verifying_key = verifying_key;
+    proof_verifier.bump = ctx.bumps.proof_verifier;
+    
+    msg!("Proof verifying key initialized for {} public inputs", public_inputs);
+    Ok(())
+}
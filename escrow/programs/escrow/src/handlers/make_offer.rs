@@ -1,5 +1,8 @@
 use super::shared::transfer_tokens;
-use crate::{error::ErrorCode, state::Offer};
+use crate::{
+    error::ErrorCode,
+    state::{Offer, OfferCounter},
+};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
@@ -38,11 +41,22 @@ pub struct MakeOffer<'info> {
     )]
     pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>,
 
+    // Created with the maker's first offer
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = OfferCounter::DISCRIMINATOR.len() + OfferCounter::INIT_SPACE,
+        seeds = [b"offer_counter", maker.key().as_ref()],
+        bump
+    )]
+    pub offer_counter: Account<'info, OfferCounter>,
+
+    // Offers are namespaced by maker, so one maker can't squat another's ids
     #[account(
         init,
         payer = maker,
         space = Offer::DISCRIMINATOR.len() + Offer::INIT_SPACE,
-        seeds = [b"offer", id.to_le_bytes().as_ref()],
+        seeds = [b"offer", maker.key().as_ref(), id.to_le_bytes().as_ref()],
         bump
     )]
     pub offer: Account<'info, Offer>,
@@ -58,24 +72,37 @@ pub struct MakeOffer<'info> {
 }
 
 // Handle the make offer instruction by:
-// 1. Moving the tokens from the maker's ATA to the vault
-// 2. Saving the details of the offer to the offer account
+// 1. Taking the maker's next offer id from their counter
+// 2. Moving the tokens from the maker's ATA to the vault
+// 3. Saving the details of the offer to the offer account
 pub fn make_offer(
     context: Context<MakeOffer>,
     id: u64,
     token_a_offered_amount: u64,
     token_b_wanted_amount: u64,
+    expires_at: Option<i64>,
 ) -> Result<()> {
     // Validate amounts
     require!(token_a_offered_amount > 0, ErrorCode::InvalidAmount);
     require!(token_b_wanted_amount > 0, ErrorCode::InvalidAmount);
 
+    // An offer that is already expired could only ever be closed
+    if let Some(expires_at) = expires_at {
+        require!(expires_at > Clock::get()?.unix_timestamp, ErrorCode::InvalidOfferExpiry);
+    }
+
     // Validate token mints are different
     require!(
         context.accounts.token_mint_a.key() != context.accounts.token_mint_b.key(),
         ErrorCode::InvalidTokenMint
     );
 
+    // Take the next id, so each one is used once
+    let offer_counter = &mut context.accounts.offer_counter;
+    require!(id == offer_counter.next_id, ErrorCode::InvalidOfferId);
+    offer_counter.next_id += 1;
+    offer_counter.bump = context.bumps.offer_counter;
+
     // Move the tokens from the maker's ATA to the vault
     transfer_tokens(
         &context.accounts.maker_token_account_a,
@@ -88,13 +115,23 @@ pub fn make_offer(
     )
     .map_err(|_| ErrorCode::InsufficientMakerBalance)?;
 
+    // Takers fill from what actually reached the vault, which is less than
+    // token_a_offered_amount if the mint charges a transfer fee
+    context.accounts.vault.reload()?;
+    let token_a_offered_amount = context.accounts.vault.amount;
+    require!(token_a_offered_amount > 0, ErrorCode::InvalidAmount);
+
     // Save the details of the offer to the offer account
     context.accounts.offer.set_inner(Offer {
         id,
         maker: context.accounts.maker.key(),
         token_mint_a: context.accounts.token_mint_a.key(),
         token_mint_b: context.accounts.token_mint_b.key(),
+        token_a_offered_amount,
         token_b_wanted_amount,
+        token_a_remaining_amount: token_a_offered_amount,
+        token_b_remaining_amount: token_b_wanted_amount,
+        expires_at,
         bump: context.bumps.offer,
     });
     Ok(())
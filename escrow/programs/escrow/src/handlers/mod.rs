@@ -7,6 +7,9 @@ pub use take_offer::*;
 pub mod refund_offer;
 pub use refund_offer::*;
 
+pub mod close_expired_offer;
+pub use close_expired_offer::*;
+
 pub mod shared;
 pub use shared::*;
 
@@ -21,4 +24,19 @@ pub mod reveal_and_verify;
 pub use reveal_and_verify::*;
 
 pub mod execute_atomic_swap;
-pub use execute_atomic_swap::*;
\ No newline at end of file
+pub use execute_atomic_swap::*;
+
+pub mod initialize_order_verifier;
+pub use initialize_order_verifier::*;
+
+pub mod initialize_proof_verifier;
+pub use initialize_proof_verifier::*;
+
+pub mod prove_order_properties;
+pub use prove_order_properties::*;
+
+pub mod cancel_enhanced;
+pub use cancel_enhanced::*;
+
+pub mod expire_enhanced;
+pub use expire_enhanced::*;
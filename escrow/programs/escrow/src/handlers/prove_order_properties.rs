@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use groth16_verifier::{prepare_public_inputs, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
+use crate::state::{
+    CommitmentScheme, CommitmentStorage, EnhancedEscrowState, OrderVerifier, MAX_SLIPPAGE_BPS, ORDER_PROOF_PUBLIC_INPUTS,
+};
+use crate::error::EscrowError;
+
+#[derive(Accounts)]
+pub struct ProveOrderProperties<'info> {
+    #[account(
+        mut,
+        constraint = commitment_storage.user == initializer.key() @ EscrowError::InvalidInitializer,
+        constraint = !commitment_storage.revealed @ EscrowError::AlreadyRevealed,
+        constraint = commitment_storage.scheme == CommitmentScheme::Poseidon @ EscrowError::CommitmentSchemeMismatch
+    )]
+    pub commitment_storage: Account<'info, CommitmentStorage>,
+    
+    #[account(
+        mut,
+        constraint = escrow_account.commitment_hash == commitment_storage.commitment_hash @ EscrowError::CommitmentMismatch,
+        constraint = commitment_storage.escrow_pda == escrow_account.key() @ EscrowError::EscrowMismatch
+    )]
+    pub escrow_account: Account<'info, EnhancedEscrowState>,
+    
+    #[account(
+        seeds = [b"order_verifier"],
+        bump = order_verifier.bump
+    )]
+    pub order_verifier: Account<'info, OrderVerifier>,
+    
+    pub initializer: Signer<'info>,
+}
+
+/// Marks the commitment as validated from a Groth16 proof that the committed
+/// `OrderDetails` satisfy `token_amount >= taker_amount`,
+/// `min_receive_amount <= taker_amount` and `max_slippage <= slippage_cap`,
+/// without revealing them. The proof also
+/// shows `token_amount` is what the vault holds, so the maker must deposit
+/// at initialization.
+///
+/// `proof_a` is negated, as for proof-verify. The public inputs are
+/// `order_proof_public_inputs(commitment_hash, taker_amount, slippage_cap, initializer_amount)`.
+pub fn prove_order_properties(
+    ctx: Context<ProveOrderProperties>,
+    proof_a: [u8; 64],
+    proof_b: [u8; 128],
+    proof_c: [u8; 64],
+    slippage_cap: u16,
+) -> Result<()> {
+    let commitment_storage = &mut ctx.accounts.commitment_storage;
+    let escrow_account = &mut ctx.accounts.escrow_account;
+    let verifying_key = &ctx.accounts.order_verifier.verifying_key;
+    require!(escrow_account.initializer_amount > 0, EscrowError::NothingDeposited);
+    require!(slippage_cap <= MAX_SLIPPAGE_BPS, EscrowError::InvalidSlippage);
+    
+    let public_inputs = order_proof_public_inputs(
+        &commitment_storage.commitment_hash,
+        escrow_account.taker_amount,
+        slippage_cap,
+        escrow_account.initializer_amount,
+    );
+    let prepared_public_inputs = prepare_public_inputs(&verifying_key.vk_ic, &public_inputs)
+        .map_err(|_| EscrowError::InvalidOrderProof)?;
+    
+    let verifier = Groth16VerifierPrepared {
+        proof_a,
+        proof_b,
+        proof_c,
+        prepared_public_inputs,
+        verifying_key: Box::new(Groth16VerifyingKeyPrepared {
+            vk_alpha_g1: verifying_key.vk_alpha_g1,
+            vk_beta_g2: verifying_key.vk_beta_g2,
+            vk_gamma_g2: verifying_key.vk_gamma_g2,
+            vk_delta_g2: verifying_key.vk_delta_g2,
+        }),
+    };
+    let valid = verifier.verify().map_err(|_| EscrowError::InvalidOrderProof)?;
+    require!(valid, EscrowError::InvalidOrderProof);
+    
+    commitment_storage.validated = true;
+    commitment_storage.proven_slippage_cap = slippage_cap;
+    // The order stays hidden, so the escrow records the proven bounds instead:
+    // the hidden minimum is at most taker_amount, so the swap holds the
+    // initializer to receiving all of it
+    escrow_account.min_receive_amount = escrow_account.taker_amount;
+    escrow_account.max_slippage = slippage_cap;
+    
+    msg!("Order properties proven: taker amount {}, slippage cap {}", escrow_account.taker_amount, slippage_cap);
+    Ok(())
+}
+
+/// The order properties circuit's public inputs as 32-byte big-endian field
+/// elements: the Poseidon commitment, the taker amount, the slippage cap and
+/// the deposited amount
+pub fn order_proof_public_inputs(
+    commitment_hash: &[u8; 32],
+    taker_amount: u64,
+    slippage_cap: u16,
+    deposit_amount: u64,
+) -> [[u8; 32]; ORDER_PROOF_PUBLIC_INPUTS] {
+    let mut taker_amount_input = [0u8; 32];
+    taker_amount_input[24..].copy_from_slice(&taker_amount.to_be_bytes());
+    let mut slippage_cap_input = [0u8; 32];
+    slippage_cap_input[30..].copy_from_slice(&slippage_cap.to_be_bytes());
+    let mut deposit_amount_input = [0u8; 32];
+    deposit_amount_input[24..].copy_from_slice(&deposit_amount.to_be_bytes());
+    
+    [*commitment_hash, taker_amount_input, slippage_cap_input, deposit_amount_input]
+}
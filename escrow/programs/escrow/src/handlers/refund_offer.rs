@@ -16,6 +16,8 @@ pub struct RefundOffer<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
 
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut)]
     pub token_mint_a: InterfaceAccount<'info, Mint>,
 
     #[account(
@@ -30,7 +32,7 @@ pub struct RefundOffer<'info> {
         mut,
         close = maker,
         has_one = maker,
-        seeds = [b"offer", offer.id.to_le_bytes().as_ref()],
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
         bump = offer.bump
     )]
     pub offer: Account<'info, Offer>,
@@ -48,34 +50,48 @@ pub struct RefundOffer<'info> {
 // 1. Returning the tokens from the vault to the maker's account
 // 2. Closing the vault and returning the rent to the maker
 pub fn refund_offer(context: Context<RefundOffer>) -> Result<()> {
+    refund_offer_vault(
+        &context.accounts.offer,
+        &context.accounts.vault,
+        &context.accounts.maker_token_account_a,
+        &context.accounts.maker.to_account_info(),
+        &context.accounts.token_mint_a,
+        &context.accounts.token_program,
+    )
+}
+
+// Shared with close_expired_offer, which returns the vault the same way
+pub fn refund_offer_vault<'info>(
+    offer: &Account<'info, Offer>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    maker_token_account_a: &InterfaceAccount<'info, TokenAccount>,
+    maker: &AccountInfo<'info>,
+    token_mint_a: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
     let offer_account_seeds = &[
         b"offer",
-        &context.accounts.offer.id.to_le_bytes()[..],
-        &[context.accounts.offer.bump],
+        offer.maker.as_ref(),
+        &offer.id.to_le_bytes()[..],
+        &[offer.bump],
     ];
     let signers_seeds = Some(&offer_account_seeds[..]);
 
     // Return the tokens from the vault to the maker's account
     transfer_tokens(
-        &context.accounts.vault,
-        &context.accounts.maker_token_account_a,
-        &context.accounts.vault.amount,
-        &context.accounts.token_mint_a,
-        &context.accounts.offer.to_account_info(),
-        &context.accounts.token_program,
+        vault,
+        maker_token_account_a,
+        &vault.amount,
+        token_mint_a,
+        &offer.to_account_info(),
+        token_program,
         signers_seeds,
     )
     .map_err(|_| ErrorCode::FailedRefundTransfer)?;
 
     // Close the vault and return the rent to the maker
-    close_token_account(
-        &context.accounts.vault,
-        &context.accounts.maker.to_account_info(),
-        &context.accounts.offer.to_account_info(),
-        &context.accounts.token_program,
-        signers_seeds,
-    )
-    .map_err(|_| ErrorCode::FailedRefundClosure)?;
+    close_token_account(vault, token_mint_a, maker, &offer.to_account_info(), token_program, signers_seeds)
+        .map_err(|_| ErrorCode::FailedRefundClosure)?;
 
     Ok(())
 }
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
-use crate::state::{EnhancedEscrowState, CommitmentStorage, OrderDetails};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use super::shared::transfer_tokens;
+use crate::state::{EnhancedEscrowState, CommitmentStorage, CommitmentScheme, OrderDetails, PrivacyLevel, MAX_SLIPPAGE_BPS};
 use crate::error::EscrowError;
 
 #[derive(Accounts)]
@@ -13,11 +15,30 @@ pub struct RevealAndVerify<'info> {
     
     #[account(
         mut,
-        constraint = escrow_account.commitment_hash == commitment_storage.commitment_hash @ EscrowError::CommitmentMismatch
+        constraint = escrow_account.commitment_hash == commitment_storage.commitment_hash @ EscrowError::CommitmentMismatch,
+        constraint = escrow_account.privacy_level != PrivacyLevel::ZeroKnowledge @ EscrowError::PlaintextRevealForbidden
     )]
     pub escrow_account: Account<'info, EnhancedEscrowState>,
     
     pub initializer: Signer<'info>,
+    
+    #[account(
+        mut,
+        address = escrow_account.initializer_deposit_token_account @ EscrowError::InvalidInitializer
+    )]
+    pub initializer_deposit_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"vault", escrow_account.key().as_ref()],
+        bump
+    )]
+    pub vault_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = escrow_account.deposit_mint @ EscrowError::EscrowMismatch)]
+    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 pub fn reveal_and_verify(
@@ -28,16 +49,48 @@ pub fn reveal_and_verify(
     let commitment_storage = &mut ctx.accounts.commitment_storage;
     let escrow_account = &mut ctx.accounts.escrow_account;
     
-    // Verify commitment using Blake2b hash
-    let computed_hash = compute_commitment_hash(&order_details, &nonce)?;
+    // Recompute the commitment with the scheme it was made with
+    let computed_hash = match commitment_storage.scheme {
+        CommitmentScheme::Sha256 => compute_commitment_hash(&order_details, &nonce)?,
+        CommitmentScheme::Poseidon => compute_poseidon_commitment(&order_details, &nonce)?,
+    };
     require!(
         computed_hash == commitment_storage.commitment_hash,
         EscrowError::InvalidCommitmentReveal
     );
     
+    // The taker pays exactly taker_amount, so the order must accept it
+    require!(
+        order_details.min_receive_amount <= escrow_account.taker_amount,
+        EscrowError::MinimumReceiveNotMet
+    );
+    require!(order_details.max_slippage <= MAX_SLIPPAGE_BPS, EscrowError::InvalidSlippage);
+    
+    // The vault must end up holding exactly the revealed amount: check a
+    // deposit made at initialization, or make it now
+    if escrow_account.initializer_amount > 0 {
+        require!(
+            escrow_account.initializer_amount == order_details.token_amount,
+            EscrowError::DepositAmountMismatch
+        );
+    } else {
+        require!(order_details.token_amount > 0, EscrowError::NothingDeposited);
+        transfer_tokens(
+            &ctx.accounts.initializer_deposit_token_account,
+            &ctx.accounts.vault_account,
+            &order_details.token_amount,
+            &ctx.accounts.deposit_mint,
+            &ctx.accounts.initializer.to_account_info(),
+            &ctx.accounts.token_program,
+            None,
+        )?;
+        escrow_account.initializer_amount = order_details.token_amount;
+    }
+    
     // Update escrow with revealed order details
-    escrow_account.initializer_amount = order_details.token_amount;
     escrow_account.optimal_venue_id = order_details.preferred_venue;
+    escrow_account.min_receive_amount = order_details.min_receive_amount;
+    escrow_account.max_slippage = order_details.max_slippage;
     
     // Mark as revealed
     commitment_storage.revealed = true;
@@ -60,4 +113,16 @@ fn compute_commitment_hash(order: &OrderDetails, nonce: &[u8; 32]) -> Result<[u8
     
     let hash = hashv(&[&order_bytes, nonce]);
     Ok(hash.to_bytes())
-}
\ No newline at end of file
+}
+
+fn compute_poseidon_commitment(order: &OrderDetails, nonce: &[u8; 32]) -> Result<[u8; 32]> {
+    use solana_poseidon::{hashv, Endianness, Parameters};
+
+    let inputs = order.poseidon_inputs(nonce);
+    let parts: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+
+    // Fails if the nonce is not a canonical field element
+    let hash = hashv(Parameters::Bn254X5, Endianness::BigEndian, &parts)
+        .map_err(|_| EscrowError::InvalidCommitmentReveal)?;
+    Ok(hash.to_bytes())
+}
@@ -1,5 +1,13 @@
 use anchor_lang::prelude::*;
 
+use anchor_lang::solana_program::program::invoke;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::{instruction::harvest_withheld_tokens_to_mint, TransferFeeAmount, TransferFeeConfig},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+};
 use anchor_spl::token_interface::{
     close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
     TransferChecked,
@@ -45,13 +53,23 @@ pub fn transfer_tokens<'info>(
 
 // Close a token account and send the rent to the specified destination
 // If the token account is owned by a PDA, owning_pda_seeds must be provided.
+// Token-2022 won't close an account holding withheld transfer fees, so those
+// are harvested to the mint first, which must be writable.
 pub fn close_token_account<'info>(
     token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
     destination: &AccountInfo<'info>,
     authority: &AccountInfo<'info>,
     token_program: &Interface<'info, TokenInterface>,
     owning_pda_seeds: Option<&[&[u8]]>,
 ) -> Result<()> {
+    if withheld_transfer_fees(token_account)? > 0 {
+        invoke(
+            &harvest_withheld_tokens_to_mint(&token_program.key(), &mint.key(), &[&token_account.key()])?,
+            &[mint.to_account_info(), token_account.to_account_info(), token_program.to_account_info()],
+        )?;
+    }
+
     let close_accounts = CloseAccount {
         account: token_account.to_account_info(),
         destination: destination.to_account_info(),
@@ -67,3 +85,35 @@ pub fn close_token_account<'info>(
         CpiContext::new(token_program.to_account_info(), close_accounts)
     })
 }
+
+// Transfer fees withheld in a token account, which only Token-2022 accounts
+// with the transfer fee extension can hold
+fn withheld_transfer_fees(token_account: &InterfaceAccount<TokenAccount>) -> Result<u64> {
+    let account_info = token_account.to_account_info();
+    if *account_info.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+    let account_data = account_info.try_borrow_data()?;
+    let account_state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account_data)?;
+
+    let Ok(transfer_fee_amount) = account_state.get_extension::<TransferFeeAmount>() else {
+        return Ok(0);
+    };
+    Ok(u64::from(transfer_fee_amount.withheld_amount))
+}
+
+// The amount to send so that `amount` arrives after the mint's transfer fee
+// Mints without the transfer fee extension, including classic token mints, charge nothing
+pub fn amount_with_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    let Ok(transfer_fee_config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let fee = transfer_fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(amount.checked_add(fee).ok_or(ProgramError::ArithmeticOverflow)?)
+}
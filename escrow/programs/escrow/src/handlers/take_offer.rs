@@ -1,5 +1,5 @@
 use super::shared::{close_token_account, transfer_tokens};
-use crate::{error::ErrorCode, state::Offer};
+use crate::{error::ErrorCode, events::OfferFilled, state::Offer};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
@@ -25,6 +25,8 @@ pub struct TakeOffer<'info> {
     #[account(mut)]
     pub maker: SystemAccount<'info>,
 
+    // Writable so withheld transfer fees can be harvested before the vault closes
+    #[account(mut)]
     pub token_mint_a: InterfaceAccount<'info, Mint>,
 
     pub token_mint_b: InterfaceAccount<'info, Mint>,
@@ -55,12 +57,12 @@ pub struct TakeOffer<'info> {
     )]
     pub maker_token_account_b: InterfaceAccount<'info, TokenAccount>,
 
+    // Closed by the handler once the last of it is filled
     #[account(
         mut,
-        close = maker,
         has_one = maker,
         has_one = token_mint_b,
-        seeds = [b"offer", offer.id.to_le_bytes().as_ref()],
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
         bump = offer.bump
     )]
     offer: Account<'info, Offer>,
@@ -75,24 +77,41 @@ pub struct TakeOffer<'info> {
 }
 
 // Handle the take offer instruction by:
-// 1. Withdrawing the offered tokens from the vault to the taker and closing the vault
-// 2. Sending the wanted tokens from the taker to the maker
-pub fn take_offer(context: Context<TakeOffer>) -> Result<()> {
+// 1. Checking the offer hasn't expired and has token_a_amount left
+// 2. Withdrawing token_a_amount from the vault to the taker
+// 3. Sending the proportional amount of the wanted tokens from the taker to the maker
+// 4. Closing the vault and the offer once the offer is fully filled
+pub fn take_offer(context: Context<TakeOffer>, token_a_amount: u64) -> Result<()> {
+    if let Some(expires_at) = context.accounts.offer.expires_at {
+        require!(Clock::get()?.unix_timestamp < expires_at, ErrorCode::OfferExpired);
+    }
+
+    require!(token_a_amount > 0, ErrorCode::InvalidAmount);
+    let offer = &context.accounts.offer;
+    require!(token_a_amount <= offer.token_a_remaining_amount, ErrorCode::FillExceedsOffer);
+
+    // Each fill is priced rounded up, and capped at what is left to pay so the
+    // fills never add up to more than token_b_wanted_amount
+    let token_b_amount = token_b_price(offer, token_a_amount).min(offer.token_b_remaining_amount);
+    require!(token_b_amount > 0, ErrorCode::FillTooSmall);
+    let token_a_remaining_amount = offer.token_a_remaining_amount - token_a_amount;
+    let token_b_remaining_amount = offer.token_b_remaining_amount - token_b_amount;
+
     // Since the Offer account owns the Vault, we will say
     // there is one signer (the offer), with the seeds of the specific offer account
     // We can use these signer seeds to withdraw the token from the vault
-    let offer_account_seeds = &[
-        b"offer",
-        &context.accounts.offer.id.to_le_bytes()[..],
-        &[context.accounts.offer.bump],
-    ];
-    let signers_seeds = Some(&offer_account_seeds[..]);
-
-    // Withdraw the offered tokens from the vault to the taker
+    // They are copied out as the offer is updated before the vault is closed
+    let maker_key = context.accounts.offer.maker;
+    let offer_id = context.accounts.offer.id.to_le_bytes();
+    let offer_bump = [context.accounts.offer.bump];
+    let offer_account_seeds: &[&[u8]] = &[b"offer", maker_key.as_ref(), &offer_id, &offer_bump];
+    let signers_seeds = Some(offer_account_seeds);
+
+    // Withdraw the filled tokens from the vault to the taker
     transfer_tokens(
         &context.accounts.vault,
         &context.accounts.taker_token_account_a,
-        &context.accounts.vault.amount,
+        &token_a_amount,
         &context.accounts.token_mint_a,
         &context.accounts.offer.to_account_info(),
         &context.accounts.token_program,
@@ -100,21 +119,11 @@ pub fn take_offer(context: Context<TakeOffer>) -> Result<()> {
     )
     .map_err(|_| ErrorCode::FailedVaultWithdrawal)?;
 
-    // Close the vault and return the rent to the maker
-    close_token_account(
-        &context.accounts.vault,
-        &context.accounts.taker.to_account_info(),
-        &context.accounts.offer.to_account_info(),
-        &context.accounts.token_program,
-        signers_seeds,
-    )
-    .map_err(|_| ErrorCode::FailedVaultClosure)?;
-
     // Send the wanted tokens from the taker to the maker
     transfer_tokens(
         &context.accounts.taker_token_account_b,
         &context.accounts.maker_token_account_b,
-        &context.accounts.offer.token_b_wanted_amount,
+        &token_b_amount,
         &context.accounts.token_mint_b,
         &context.accounts.taker.to_account_info(),
         &context.accounts.token_program,
@@ -122,5 +131,42 @@ pub fn take_offer(context: Context<TakeOffer>) -> Result<()> {
     )
     .map_err(|_| ErrorCode::InsufficientTakerBalance)?;
 
+    let offer = &mut context.accounts.offer;
+    offer.token_a_remaining_amount = token_a_remaining_amount;
+    offer.token_b_remaining_amount = token_b_remaining_amount;
+
+    emit!(OfferFilled {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        taker: context.accounts.taker.key(),
+        token_a_amount,
+        token_b_amount,
+        token_a_remaining_amount,
+        token_b_remaining_amount,
+    });
+
+    if token_a_remaining_amount == 0 {
+        // Close the vault and send its rent to the taker
+        close_token_account(
+            &context.accounts.vault,
+            &context.accounts.token_mint_a,
+            &context.accounts.taker.to_account_info(),
+            &context.accounts.offer.to_account_info(),
+            &context.accounts.token_program,
+            signers_seeds,
+        )
+        .map_err(|_| ErrorCode::FailedVaultClosure)?;
+
+        context.accounts.offer.close(context.accounts.maker.to_account_info())?;
+    }
+
     Ok(())
 }
+
+// What token_a_amount costs at the offer's price, rounded up so a fill never
+// costs the taker less than its share of token_b_wanted_amount
+fn token_b_price(offer: &Offer, token_a_amount: u64) -> u64 {
+    (token_a_amount as u128 * offer.token_b_wanted_amount as u128)
+        .div_ceil(offer.token_a_offered_amount as u128) as u64
+}
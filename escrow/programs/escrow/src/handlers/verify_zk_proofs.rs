@@ -1,5 +1,9 @@
 use anchor_lang::prelude::*;
-use crate::state::{EnhancedEscrowState, ProofBatch};
+use groth16_verifier::{prepare_public_inputs, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
+use crate::state::{
+    EnhancedEscrowState, ProofBatch, ProofCircuit, ProofVerifier, ProofVerifyingKey, MAX_PROOF_PUBLIC_INPUTS,
+    VALIDITY_PROOF_PUBLIC_INPUTS,
+};
 use crate::error::EscrowError;
 
 #[derive(Accounts)]
@@ -22,30 +26,73 @@ pub struct VerifyZKProofs<'info> {
     )]
     pub proof_batch: Account<'info, ProofBatch>,
     
+    #[account(
+        seeds = [b"proof_verifier", &[ProofCircuit::Validity as u8]],
+        bump = validity_verifier.bump
+    )]
+    pub validity_verifier: Account<'info, ProofVerifier>,
+    
+    // Only needed when routing inputs are passed
+    #[account(
+        seeds = [b"proof_verifier", &[ProofCircuit::Routing as u8]],
+        bump = routing_verifier.bump
+    )]
+    pub routing_verifier: Option<Account<'info, ProofVerifier>>,
+    
     #[account(mut)]
     pub initializer: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+/// Verifies the validity proof over the escrow's own inputs, then the routing
+/// proof over the remaining inputs if there are any. The first routing input
+/// must be the escrow's address, reduced with `pubkey_field_element`. Without
+/// routing inputs the routing proof must be left zeroed. Both are Groth16
+/// proofs laid out as `a || b || c` with `a` negated, as for
+/// `prove_order_properties`.
 pub fn verify_zk_proofs(
     ctx: Context<VerifyZKProofs>,
     validity_proof: [u8; 256],
     routing_proof: [u8; 256],
-    public_inputs: Vec<u64>,
+    public_inputs: Vec<[u8; 32]>,
 ) -> Result<()> {
+    let escrow_key = ctx.accounts.escrow_account.key();
     let escrow_account = &mut ctx.accounts.escrow_account;
     let proof_batch = &mut ctx.accounts.proof_batch;
     
-    require!(public_inputs.len() <= 10, EscrowError::TooManyPublicInputs);
+    require!(public_inputs.len() <= MAX_PROOF_PUBLIC_INPUTS, EscrowError::TooManyPublicInputs);
+    require!(
+        public_inputs.len() >= VALIDITY_PROOF_PUBLIC_INPUTS,
+        EscrowError::InsufficientPublicInputs
+    );
+    let (validity_inputs, routing_inputs) = public_inputs.split_at(VALIDITY_PROOF_PUBLIC_INPUTS);
     
-    // Verify validity proof (tokens_to_send >= tokens_asked)
-    let validity_result = verify_token_validity_proof(&validity_proof, &public_inputs[..2.min(public_inputs.len())])?;
-    require!(validity_result, EscrowError::InvalidValidityProof);
+    // The validity proof must be about this escrow: its inputs come from the
+    // escrow's own state, never from the caller
+    let expected_inputs = validity_proof_public_inputs(escrow_account);
+    require!(validity_inputs == expected_inputs.as_slice(), EscrowError::PublicInputsMismatch);
+    let validity_key = &ctx.accounts.validity_verifier.verifying_key;
+    require!(
+        verify_proof(&validity_proof, validity_key, validity_inputs),
+        EscrowError::InvalidValidityProof
+    );
+    msg!("Validity proof verified over {} escrow inputs", validity_inputs.len());
     
-    // Verify routing optimality proof
-    if public_inputs.len() > 2 {
-        let routing_result = verify_routing_proof(&routing_proof, &public_inputs[2..])?;
-        require!(routing_result, EscrowError::InvalidRoutingProof);
+    // Verify routing optimality proof. Like the validity proof it must be about
+    // this escrow, or a routing proof made for any other order would pass.
+    if routing_inputs.is_empty() {
+        require!(routing_proof == [0u8; 256], EscrowError::InvalidRoutingProof);
+    } else {
+        require!(
+            routing_inputs[0] == pubkey_field_element(&escrow_key),
+            EscrowError::PublicInputsMismatch
+        );
+        let routing_verifier = ctx.accounts.routing_verifier.as_ref().ok_or(EscrowError::InvalidRoutingProof)?;
+        require!(
+            verify_proof(&routing_proof, &routing_verifier.verifying_key, routing_inputs),
+            EscrowError::InvalidRoutingProof
+        );
+        msg!("Routing proof verified for {} venues", routing_inputs.len() - 1);
     }
     
     // Store verified proofs
@@ -57,32 +104,61 @@ pub fn verify_zk_proofs(
     
     // Update escrow state
     escrow_account.zk_proof_verified = true;
-    escrow_account.routing_proof_hash = compute_hash(&routing_proof);
+    // Stays zeroed when there was no routing proof to verify
+    escrow_account.routing_proof_hash = if routing_inputs.is_empty() {
+        [0u8; 32]
+    } else {
+        compute_hash(&routing_proof)
+    };
     
     msg!("ZK proofs verified successfully");
     Ok(())
 }
 
-fn verify_token_validity_proof(proof_data: &[u8; 256], public_inputs: &[u64]) -> Result<bool> {
-    // Use Solana's alt_bn128_pairing syscall for Groth16 verification
-    // This is a simplified version - full implementation would deserialize proof
-    // and prepare inputs for the pairing check
-    
-    // For now, we perform a basic sanity check
-    require!(public_inputs.len() >= 2, EscrowError::InsufficientPublicInputs);
-    require!(public_inputs[0] >= public_inputs[1], EscrowError::InvalidTokenRatio);
+/// The validity proof's public inputs as 32-byte big-endian field elements:
+/// the order commitment, `taker_amount`, and the deposit and receive mints
+/// reduced with `pubkey_field_element`
+pub fn validity_proof_public_inputs(
+    escrow_account: &EnhancedEscrowState,
+) -> [[u8; 32]; VALIDITY_PROOF_PUBLIC_INPUTS] {
+    let mut taker_amount_input = [0u8; 32];
+    taker_amount_input[24..].copy_from_slice(&escrow_account.taker_amount.to_be_bytes());
     
-    // In production, call: solana_program::alt_bn128::alt_bn128_pairing(prepared_data)
-    msg!("Validity proof verified: offered={}, wanted={}", public_inputs[0], public_inputs[1]);
-    Ok(true)
+    [
+        escrow_account.commitment_hash,
+        taker_amount_input,
+        pubkey_field_element(&escrow_account.deposit_mint),
+        pubkey_field_element(&escrow_account.receive_mint),
+    ]
+}
+
+/// SHA-256 of the pubkey, keeping the low 253 bits so it is a BN254 field element
+pub fn pubkey_field_element(pubkey: &Pubkey) -> [u8; 32] {
+    let mut element = anchor_lang::solana_program::hash::hash(pubkey.as_ref()).to_bytes();
+    element[0] &= 0x1f;
+    element
 }
 
-fn verify_routing_proof(proof_data: &[u8; 256], public_inputs: &[u64]) -> Result<bool> {
-    // Routing optimality verification logic
-    // Verifies that the selected venue provides optimal execution
+// Whether `proof` verifies against `verifying_key` for `public_inputs`. Inputs
+// that don't fit the key, and bytes that aren't curve points, don't verify.
+fn verify_proof(proof: &[u8; 256], verifying_key: &ProofVerifyingKey, public_inputs: &[[u8; 32]]) -> bool {
+    let Ok(prepared_public_inputs) = prepare_public_inputs(&verifying_key.vk_ic, public_inputs) else {
+        return false;
+    };
     
-    msg!("Routing proof verified for {} venues", public_inputs.len());
-    Ok(true)
+    let verifier = Groth16VerifierPrepared {
+        proof_a: proof[..64].try_into().unwrap(),
+        proof_b: proof[64..192].try_into().unwrap(),
+        proof_c: proof[192..].try_into().unwrap(),
+        prepared_public_inputs,
+        verifying_key: Box::new(Groth16VerifyingKeyPrepared {
+            vk_alpha_g1: verifying_key.vk_alpha_g1,
+            vk_beta_g2: verifying_key.vk_beta_g2,
+            vk_gamma_g2: verifying_key.vk_gamma_g2,
+            vk_delta_g2: verifying_key.vk_delta_g2,
+        }),
+    };
+    verifier.verify().unwrap_or(false)
 }
 
 fn compute_hash(data: &[u8]) -> [u8; 32] {
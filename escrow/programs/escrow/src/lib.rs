@@ -7,6 +7,7 @@ use handlers::*;
 
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod handlers;
 pub mod state;
 
@@ -22,33 +23,49 @@ pub mod escrow {
         id: u64,
         token_a_offered_amount: u64,
         token_b_wanted_amount: u64,
+        expires_at: Option<i64>,
     ) -> Result<()> {
-        handlers::make_offer::make_offer(context, id, token_a_offered_amount, token_b_wanted_amount)
+        handlers::make_offer::make_offer(context, id, token_a_offered_amount, token_b_wanted_amount, expires_at)
     }
 
-    pub fn take_offer(context: Context<TakeOffer>) -> Result<()> {
-        handlers::take_offer::take_offer(context)
+    pub fn take_offer(context: Context<TakeOffer>, token_a_amount: u64) -> Result<()> {
+        handlers::take_offer::take_offer(context, token_a_amount)
     }
 
     pub fn refund_offer(context: Context<RefundOffer>) -> Result<()> {
         handlers::refund_offer::refund_offer(context)
     }
 
+    pub fn close_expired_offer(context: Context<CloseExpiredOffer>) -> Result<()> {
+        handlers::close_expired_offer::close_expired_offer(context)
+    }
+
     // Enhanced privacy-preserving functions
     pub fn initialize_enhanced(
         ctx: Context<InitializeEnhanced>,
         commitment_hash: [u8; 32],
         taker_amount: u64,
         privacy_level: state::PrivacyLevel,
+        commitment_scheme: state::CommitmentScheme,
+        deposit_amount: u64,
+        expires_at: i64,
     ) -> Result<()> {
-        handlers::initialize_enhanced::initialize_enhanced(ctx, commitment_hash, taker_amount, privacy_level)
+        handlers::initialize_enhanced::initialize_enhanced(
+            ctx,
+            commitment_hash,
+            taker_amount,
+            privacy_level,
+            commitment_scheme,
+            deposit_amount,
+            expires_at,
+        )
     }
 
     pub fn verify_zk_proofs(
         ctx: Context<VerifyZKProofs>,
         validity_proof: [u8; 256],
         routing_proof: [u8; 256],
-        public_inputs: Vec<u64>,
+        public_inputs: Vec<[u8; 32]>,
     ) -> Result<()> {
         handlers::verify_zk_proofs::verify_zk_proofs(ctx, validity_proof, routing_proof, public_inputs)
     }
@@ -64,9 +81,46 @@ pub mod escrow {
     pub fn execute_atomic_swap(ctx: Context<ExecuteAtomicSwap>) -> Result<()> {
         handlers::execute_atomic_swap::execute_atomic_swap(ctx)
     }
+
+    pub fn initialize_order_verifier(
+        ctx: Context<InitializeOrderVerifier>,
+        verifying_key: state::OrderVerifyingKey,
+    ) -> Result<()> {
+        handlers::initialize_order_verifier::initialize_order_verifier(ctx, verifying_key)
+    }
+
+    pub fn initialize_proof_verifier(
+        ctx: Context<InitializeProofVerifier>,
+        circuit: state::ProofCircuit,
+        verifying_key: state::ProofVerifyingKey,
+    ) -> Result<()> {
+        handlers::initialize_proof_verifier::initialize_proof_verifier(ctx, circuit, verifying_key)
+    }
+
+    pub fn prove_order_properties(
+        ctx: Context<ProveOrderProperties>,
+        proof_a: [u8; 64],
+        proof_b: [u8; 128],
+        proof_c: [u8; 64],
+        slippage_cap: u16,
+    ) -> Result<()> {
+        handlers::prove_order_properties::prove_order_properties(ctx, proof_a, proof_b, proof_c, slippage_cap)
+    }
+
+    pub fn cancel_enhanced(ctx: Context<CancelEnhanced>) -> Result<()> {
+        handlers::cancel_enhanced::cancel_enhanced(ctx)
+    }
+
+    pub fn expire_enhanced(ctx: Context<ExpireEnhanced>) -> Result<()> {
+        handlers::expire_enhanced::expire_enhanced(ctx)
+    }
 }
 
 #[cfg(test)]
 mod escrow_test_helpers;
 #[cfg(test)]
-mod tests;
\ No newline at end of file
+mod tests;
+#[cfg(test)]
+mod compute_unit_tests;
+#[cfg(test)]
+mod enhanced_tests;
\ No newline at end of file
@@ -7,24 +7,34 @@ pub struct EnhancedEscrowState {
     pub initializer_key: Pubkey,
     pub initializer_deposit_token_account: Pubkey,
     pub initializer_receive_token_account: Pubkey,
-    pub initializer_amount: u64,
+    pub deposit_mint: Pubkey,
+    pub receive_mint: Pubkey,
+    pub initializer_amount: u64,             // Sent to the vault, 0 until deposited. A transfer fee leaves the vault less
     pub taker_amount: u64,
+    pub min_receive_amount: u64,             // Revealed or proven order bound, 0 until then
+    pub max_slippage: u16,                   // Revealed or proven, in basis points of taker_amount
     pub bump: u8,
     
     // Integration fields for privacy-preserving trading
-    pub commitment_hash: [u8; 32],           // Blake2b commitment hash
+    pub commitment_hash: [u8; 32],           // Order commitment, see CommitmentScheme
     pub zk_proof_verified: bool,             // ZK proof validation status
     pub routing_proof_hash: [u8; 32],        // Routing optimality proof
     pub settlement_proof_hash: [u8; 32],     // Settlement audit proof
     pub execution_timestamp: i64,            // Atomic execution timestamp
     pub optimal_venue_id: u8,                // Selected DEX venue
     pub privacy_level: PrivacyLevel,         // Privacy configuration
+    pub expires_at: i64,                     // After this, anyone can return the deposit
 }
 
+/// What `execute_atomic_swap` requires before the escrow can be taken
 #[derive(AnchorSerialize, AnchorDeserialize,Clone, Copy, PartialEq, Eq)]
 pub enum PrivacyLevel {
+    /// No commitment reveal or proofs, the deposit is made at initialization
     Public,
+    /// The order is revealed with `reveal_and_verify`, proofs are optional
     Confidential,
+    /// The order is never revealed: `prove_order_properties` proves it in
+    /// Groth16 and `verify_zk_proofs` must have run
     ZeroKnowledge,
 }
 
@@ -34,6 +44,21 @@ impl anchor_lang::Space for PrivacyLevel {
     const INIT_SPACE: usize = 1; // 1 byte for enum discriminant
 }
 
+/// How `commitment_hash` commits to `(OrderDetails, nonce)`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentScheme {
+    /// SHA-256 over the Borsh-encoded order followed by the nonce
+    Sha256,
+    /// Poseidon (BN254, circom parameters) over the order fields and the
+    /// nonce as big-endian field elements, see `OrderDetails::poseidon_inputs`.
+    /// Cheap to prove in-circuit, so the order can be opened by a ZK proof.
+    Poseidon,
+}
+
+impl anchor_lang::Space for CommitmentScheme {
+    const INIT_SPACE: usize = 1;
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct CommitmentStorage {
@@ -42,8 +67,19 @@ pub struct CommitmentStorage {
     pub timestamp: i64,
     pub revealed: bool,
     pub escrow_pda: Pubkey,
+    pub scheme: CommitmentScheme,
+    pub validated: bool,                     // Order properties proven without a reveal
+    pub proven_slippage_cap: u16,            // Cap the proof showed max_slippage is under
 }
 
+/// Number of validity proof public inputs, bound to the escrow by
+/// `validity_proof_public_inputs`. Routing inputs follow them, the first being
+/// the escrow's address reduced with `pubkey_field_element`.
+pub const VALIDITY_PROOF_PUBLIC_INPUTS: usize = 4;
+
+/// Most public inputs `verify_zk_proofs` takes, validity and routing together
+pub const MAX_PROOF_PUBLIC_INPUTS: usize = 10;
+
 #[account]
 #[derive(InitSpace)]
 
@@ -52,7 +88,7 @@ pub struct ProofBatch {
     pub routing_proof: [u8; 256],      // Routing optimality proof
     pub settlement_proof: [u8; 256],   // Settlement verification proof
     #[max_len(10)]
-    pub public_inputs: Vec<u64>,       // Public proof inputs
+    pub public_inputs: Vec<[u8; 32]>,  // Public proof inputs, big-endian field elements
     pub verified: bool,                // Batch verification status
 }
 
@@ -61,6 +97,6 @@ impl ProofBatch {
         256 + // validity_proof
         256 + // routing_proof  
         256 + // settlement_proof
-        4 + (8 * 10) + // public_inputs vec
+        4 + (32 * MAX_PROOF_PUBLIC_INPUTS) + // public_inputs vec
         1; // verified bool
 }
\ No newline at end of file
@@ -1,7 +1,11 @@
 pub mod offer;
 pub mod enhanced_escrow;
 pub mod order_details;
+pub mod order_verifier;
+pub mod proof_verifier;
 
 pub use offer::*;
 pub use enhanced_escrow::*;
-pub use order_details::*;
\ No newline at end of file
+pub use order_details::*;
+pub use order_verifier::*;
+pub use proof_verifier::*;
\ No newline at end of file
@@ -13,8 +13,26 @@ pub struct Offer {
     pub token_mint_a: Pubkey,
     // The token mint of the token wanted
     pub token_mint_b: Pubkey,
-    // The amount of token b being wanted
+    // The amount of token a that went into the vault
+    pub token_a_offered_amount: u64,
+    // The amount of token b being wanted for all of token a
     pub token_b_wanted_amount: u64,
+    // Token a left in the vault for takers to fill
+    pub token_a_remaining_amount: u64,
+    // What taking all of the token a left costs, rounded up like every fill
+    pub token_b_remaining_amount: u64,
+    // After this, the offer can't be taken and anyone can close it, None for no deadline
+    pub expires_at: Option<i64>,
     // Used to calculate the address for this account, we save it as a performance optimization
     pub bump: u8,
 }
+
+// Hands out a maker's offer ids in order, so nobody else can take the id a maker is about to use
+// There is one per maker, at the PDA [b"offer_counter", maker]
+#[account]
+#[derive(InitSpace)]
+pub struct OfferCounter {
+    // The id the maker's next offer must use
+    pub next_id: u64,
+    pub bump: u8,
+}
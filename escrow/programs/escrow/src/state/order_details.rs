@@ -1,9 +1,32 @@
 use anchor_lang::prelude::*;
 
+/// Largest `max_slippage`, in basis points
+pub const MAX_SLIPPAGE_BPS: u16 = 10_000;
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct OrderDetails {
     pub token_amount: u64,
     pub min_receive_amount: u64,
     pub preferred_venue: u8,
     pub max_slippage: u16,
-}
\ No newline at end of file
+}
+
+impl OrderDetails {
+    /// The order fields followed by the nonce as 32-byte big-endian field
+    /// elements, the inputs of a `CommitmentScheme::Poseidon` commitment
+    pub fn poseidon_inputs(&self, nonce: &[u8; 32]) -> [[u8; 32]; 5] {
+        [
+            u64_field_element(self.token_amount),
+            u64_field_element(self.min_receive_amount),
+            u64_field_element(self.preferred_venue as u64),
+            u64_field_element(self.max_slippage as u64),
+            *nonce,
+        ]
+    }
+}
+
+fn u64_field_element(value: u64) -> [u8; 32] {
+    let mut element = [0u8; 32];
+    element[24..].copy_from_slice(&value.to_be_bytes());
+    element
+}
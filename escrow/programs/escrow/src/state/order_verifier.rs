@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+/// Number of public inputs of the order properties circuit:
+/// `[commitment_hash, taker_amount, slippage_cap, deposit_amount]`
+pub const ORDER_PROOF_PUBLIC_INPUTS: usize = 4;
+
+/// Groth16 verifying key for the order properties circuit, in the alt_bn128
+/// syscall encoding (big-endian, G2 as `c1 || c0`)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct OrderVerifyingKey {
+    pub vk_alpha_g1: [u8; 64],
+    pub vk_beta_g2: [u8; 128],
+    pub vk_gamma_g2: [u8; 128],
+    pub vk_delta_g2: [u8; 128],
+    pub vk_ic: [[u8; 64]; ORDER_PROOF_PUBLIC_INPUTS + 1],
+}
+
+// Holds the verifying key `prove_order_properties` checks proofs against.
+// There is one per program, at the PDA `[b"order_verifier"]`.
+#[account]
+#[derive(InitSpace)]
+pub struct OrderVerifier {
+    // Who initialized the key
+    pub authority: Pubkey,
+    pub verifying_key: OrderVerifyingKey,
+    pub bump: u8,
+}
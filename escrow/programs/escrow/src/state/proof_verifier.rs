@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use super::{MAX_PROOF_PUBLIC_INPUTS, VALIDITY_PROOF_PUBLIC_INPUTS};
+
+/// Most public inputs the routing proof takes, after the validity inputs
+pub const MAX_ROUTING_PROOF_PUBLIC_INPUTS: usize = MAX_PROOF_PUBLIC_INPUTS - VALIDITY_PROOF_PUBLIC_INPUTS;
+
+/// The circuits `verify_zk_proofs` checks proofs of, each with its own verifying key
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ProofCircuit {
+    /// Proves the escrow's tokens are valid, over `validity_proof_public_inputs`
+    Validity,
+    /// Proves the selected venue gives the best execution, over the routing inputs
+    Routing,
+}
+
+/// Groth16 verifying key for a `ProofCircuit`, in the alt_bn128 syscall
+/// encoding (big-endian, G2 as `c1 || c0`). `vk_ic` has one more entry than
+/// the circuit has public inputs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ProofVerifyingKey {
+    pub vk_alpha_g1: [u8; 64],
+    pub vk_beta_g2: [u8; 128],
+    pub vk_gamma_g2: [u8; 128],
+    pub vk_delta_g2: [u8; 128],
+    #[max_len(7)] // MAX_ROUTING_PROOF_PUBLIC_INPUTS + 1
+    pub vk_ic: Vec<[u8; 64]>,
+}
+
+// Holds the verifying key `verify_zk_proofs` checks one circuit's proofs
+// against, at the PDA `[b"proof_verifier", &[circuit as u8]]`.
+#[account]
+#[derive(InitSpace)]
+pub struct ProofVerifier {
+    // Who initialized the key
+    pub authority: Pubkey,
+    pub circuit: ProofCircuit,
+    pub verifying_key: ProofVerifyingKey,
+    pub bump: u8,
+}
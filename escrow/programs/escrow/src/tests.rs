@@ -1,21 +1,23 @@
 use solana_signer::Signer;
 
+use crate::error::ErrorCode;
 use crate::escrow_test_helpers::{
     build_make_offer_accounts, build_make_offer_instruction, build_refund_offer_instruction, build_take_offer_instruction,
-    execute_make_offer, execute_take_offer, execute_refund_offer, generate_offer_id,
-    setup_escrow_test, RefundOfferAccounts, TakeOfferAccounts,
+    advance_clock, execute_close_expired_offer, execute_make_offer, execute_take_offer, execute_take_offer_for_events,
+    execute_refund_offer, assert_anchor_error, send_transaction_for_logs, take_offer_instruction,
+    get_offer_pda, next_offer_id, setup_escrow_test, RefundOfferAccounts, TakeOfferAccounts,
     TOKEN_A, TOKEN_B,
 };
 use solana_kite::{
-    assert_token_balance, check_account_is_closed, get_pda_and_bump, seeds, send_transaction_from_instructions,
+    assert_token_balance, check_account_is_closed, send_transaction_from_instructions,
 };
 
 #[test]
 fn test_make_offer_succeeds() {
     let mut test_environment = setup_escrow_test();
 
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -34,6 +36,7 @@ fn test_make_offer_succeeds() {
         offer_id,
         1 * TOKEN_A,
         1 * TOKEN_B,
+        None,
         make_offer_accounts,
     );
 
@@ -51,60 +54,99 @@ fn test_make_offer_succeeds() {
 fn test_duplicate_offer_id_fails() {
     let mut test_environment = setup_escrow_test();
 
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
-    let vault = spl_associated_token_account::get_associated_token_address(
-        &offer_account,
-        &test_environment.token_mint_a.pubkey(),
-    );
-
-    let make_offer_accounts = build_make_offer_accounts(
-        test_environment.alice.pubkey(),
-        test_environment.token_mint_a.pubkey(),
-        test_environment.token_mint_b.pubkey(),
-        test_environment.alice_token_account_a,
-        offer_account,
-        vault,
-    );
-
-    let make_offer_instruction = build_make_offer_instruction(
+    let alice = test_environment.alice.insecure_clone();
+    let bob = test_environment.bob.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let offer_id = next_offer_id(&test_environment, &alice.pubkey());
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
         offer_id,
+        &alice,
+        alice_token_account_a,
         1 * TOKEN_A,
         1 * TOKEN_B,
-        make_offer_accounts,
+        None,
+    )
+    .unwrap();
+    assert_eq!(next_offer_id(&test_environment, &alice.pubkey()), offer_id + 1);
+
+    // Alice can't use an ID again once it has been handed out, even after its
+    // offer is refunded and the address it derives is free again
+    let stale_offer_id = offer_id + 1;
+    let (stale_offer_account, stale_vault) = execute_make_offer(
+        &mut test_environment,
+        stale_offer_id,
+        &alice,
+        alice_token_account_a,
+        1 * TOKEN_A,
+        1 * TOKEN_B,
+        None,
+    )
+    .unwrap();
+    execute_refund_offer(
+        &mut test_environment,
+        &alice,
+        alice_token_account_a,
+        stale_offer_account,
+        stale_vault,
+    )
+    .unwrap();
+    let make_offer_accounts_with_used_offer_id = build_make_offer_accounts(
+        alice.pubkey(),
+        test_environment.token_mint_a.pubkey(),
+        test_environment.token_mint_b.pubkey(),
+        alice_token_account_a,
+        stale_offer_account,
+        stale_vault,
     );
-
-    let result = send_transaction_from_instructions(
+    let make_offer_instruction_with_used_offer_id = build_make_offer_instruction(
+        stale_offer_id,
+        2 * TOKEN_A,
+        2 * TOKEN_B,
+        None,
+        make_offer_accounts_with_used_offer_id,
+    );
+    let result = send_transaction_for_logs(
         &mut test_environment.litesvm,
-        vec![make_offer_instruction],
-        &[&test_environment.alice],
-        &test_environment.alice.pubkey(),
+        &[make_offer_instruction_with_used_offer_id],
+        &[&alice],
+        &alice.pubkey(),
+    );
+    assert_anchor_error(
+        result,
+        ErrorCode::InvalidOfferId.into(),
+        "An offer ID that was already handed out should be rejected",
     );
-    assert!(result.is_ok(), "First offer should succeed");
 
-    let make_offer_accounts_with_existing_offer_id = build_make_offer_accounts(
-        test_environment.bob.pubkey(),
+    // Bob can't squat Alice's offer, the address is derived from her key
+    let alices_next_offer_id = next_offer_id(&test_environment, &alice.pubkey());
+    let make_offer_accounts_with_alices_offer = build_make_offer_accounts(
+        bob.pubkey(),
         test_environment.token_mint_a.pubkey(),
         test_environment.token_mint_b.pubkey(),
         test_environment.bob_token_account_a,
-        offer_account,
+        get_offer_pda(&test_environment.program_id, &alice.pubkey(), alices_next_offer_id),
         vault,
     );
-
-    let make_offer_instruction_with_existing_offer_id = build_make_offer_instruction(
-        offer_id,
+    let make_offer_instruction_with_alices_offer = build_make_offer_instruction(
+        0,
         1 * TOKEN_A,
         1 * TOKEN_B,
-        make_offer_accounts_with_existing_offer_id,
+        None,
+        make_offer_accounts_with_alices_offer,
     );
-
     let result = send_transaction_from_instructions(
         &mut test_environment.litesvm,
-        vec![make_offer_instruction_with_existing_offer_id],
-        &[&test_environment.bob],
-        &test_environment.bob.pubkey(),
+        vec![make_offer_instruction_with_alices_offer],
+        &[&bob],
+        &bob.pubkey(),
     );
-    assert!(result.is_err(), "Second offer with same ID should fail");
+    assert!(result.is_err(), "Bob should not be able to create an offer at Alice's address");
+
+    // Bob's own IDs start at 0 too, at an address that doesn't collide with Alice's
+    assert_eq!(next_offer_id(&test_environment, &bob.pubkey()), offer_id);
+    assert_ne!(get_offer_pda(&test_environment.program_id, &bob.pubkey(), offer_id), offer_account);
+    assert!(test_environment.litesvm.get_account(&offer_account).is_some(), "Alice's offer should be untouched");
 }
 
 #[test]
@@ -112,8 +154,8 @@ fn test_insufficient_funds_fails() {
     let mut test_environment = setup_escrow_test();
 
     // Try to create offer with more tokens than Alice owns
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -132,6 +174,7 @@ fn test_insufficient_funds_fails() {
         offer_id,
         1000 * TOKEN_A, // Try to offer 1000 tokens (Alice only has 10)
         1 * TOKEN_B,
+        None,
         make_offer_accounts,
     );
 
@@ -149,8 +192,8 @@ fn test_same_token_mints_fails() {
     let mut test_environment = setup_escrow_test();
 
     // Try to create offer with same token mint for both token_a and token_b
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -166,7 +209,7 @@ fn test_same_token_mints_fails() {
     );
 
     let make_offer_instruction =
-        build_make_offer_instruction(offer_id, 1 * TOKEN_A, 1 * TOKEN_B, make_offer_accounts);
+        build_make_offer_instruction(offer_id, 1 * TOKEN_A, 1 * TOKEN_B, None, make_offer_accounts);
 
     let result = send_transaction_from_instructions(
         &mut test_environment.litesvm,
@@ -182,8 +225,8 @@ fn test_zero_token_b_wanted_amount_fails() {
     let mut test_environment = setup_escrow_test();
 
     // Try to create offer with zero token_b_wanted_amount
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -202,6 +245,7 @@ fn test_zero_token_b_wanted_amount_fails() {
         offer_id,
         1 * TOKEN_A,
         0, // Zero wanted amount
+        None,
         make_offer_accounts,
     );
 
@@ -222,8 +266,8 @@ fn test_zero_token_a_offered_amount_fails() {
     let mut test_environment = setup_escrow_test();
 
     // Try to create offer with zero token_a_offered_amount
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -242,6 +286,7 @@ fn test_zero_token_a_offered_amount_fails() {
         offer_id,
         0, // Zero offered amount
         1 * TOKEN_B,
+        None,
         make_offer_accounts,
     );
 
@@ -262,7 +307,7 @@ fn test_take_offer_success() {
     let mut test_environment = setup_escrow_test();
 
     // Alice creates an offer: 3 token A for 2 token B
-    let offer_id = generate_offer_id();
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
     let alice = test_environment.alice.insecure_clone();
     let alice_token_account_a = test_environment.alice_token_account_a;
     let (offer_account, vault) = execute_make_offer(
@@ -272,6 +317,7 @@ fn test_take_offer_success() {
         alice_token_account_a,
         3 * TOKEN_A,
         2 * TOKEN_B,
+        None,
     ).unwrap();
 
     // Bob takes the offer
@@ -288,6 +334,7 @@ fn test_take_offer_success() {
         alice_token_account_b,
         offer_account,
         vault,
+        3 * TOKEN_A,
     ).unwrap();
 
     // Check balances
@@ -329,7 +376,7 @@ fn test_refund_offer_success() {
     let mut test_environment = setup_escrow_test();
 
     // Alice creates an offer: 3 token A for 2 token B
-    let offer_id = generate_offer_id();
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
     let alice = test_environment.alice.insecure_clone();
     let alice_token_account_a = test_environment.alice_token_account_a;
     let (offer_account, vault) = execute_make_offer(
@@ -339,6 +386,7 @@ fn test_refund_offer_success() {
         alice_token_account_a,
         3 * TOKEN_A,
         2 * TOKEN_B,
+        None,
     ).unwrap();
 
     // Check that Alice's balance decreased after creating the offer
@@ -379,8 +427,8 @@ fn test_non_maker_cannot_refund_offer() {
     let mut test_environment = setup_escrow_test();
 
     // Alice creates an offer: 3 token A for 2 token B
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -399,6 +447,7 @@ fn test_non_maker_cannot_refund_offer() {
         offer_id,
         3 * TOKEN_A,
         2 * TOKEN_B,
+        None,
         make_offer_accounts,
     );
 
@@ -455,8 +504,8 @@ fn test_take_offer_insufficient_funds_fails() {
 
     // Create an offer from Alice for a large amount of token B
     let large_token_b_amount = 1000 * TOKEN_B; // Much larger than Bob's balance (he has 5)
-    let offer_id = generate_offer_id();
-    let (offer_account, _offer_bump) = get_pda_and_bump(&seeds!["offer", offer_id], &test_environment.program_id);
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let offer_account = get_offer_pda(&test_environment.program_id, &test_environment.alice.pubkey(), offer_id);
     let vault = spl_associated_token_account::get_associated_token_address(
         &offer_account,
         &test_environment.token_mint_a.pubkey(),
@@ -475,6 +524,7 @@ fn test_take_offer_insufficient_funds_fails() {
         offer_id,
         1 * TOKEN_A,
         large_token_b_amount,
+        None,
         make_offer_accounts,
     );
 
@@ -502,7 +552,7 @@ fn test_take_offer_insufficient_funds_fails() {
         vault,
     };
 
-    let take_offer_instruction = build_take_offer_instruction(take_offer_accounts);
+    let take_offer_instruction = build_take_offer_instruction(1 * TOKEN_A, take_offer_accounts);
     let result = send_transaction_from_instructions(
         &mut test_environment.litesvm,
         vec![take_offer_instruction],
@@ -514,3 +564,403 @@ fn test_take_offer_insufficient_funds_fails() {
         "Take offer with insufficient funds should fail"
     );
 }
+
+#[test]
+fn test_take_expired_offer_fails() {
+    let mut test_environment = setup_escrow_test();
+
+    // Alice creates an offer that expires in an hour
+    let clock: anchor_lang::prelude::Clock = test_environment.litesvm.get_sysvar();
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        3 * TOKEN_A,
+        2 * TOKEN_B,
+        Some(clock.unix_timestamp + 60 * 60),
+    ).unwrap();
+
+    advance_clock(&mut test_environment, 60 * 60);
+
+    // Bob is too late to take it
+    let bob = test_environment.bob.insecure_clone();
+    let bob_token_account_a = test_environment.bob_token_account_a;
+    let bob_token_account_b = test_environment.bob_token_account_b;
+    let alice_token_account_b = test_environment.alice_token_account_b;
+    let result = execute_take_offer(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        3 * TOKEN_A,
+    );
+    assert!(result.is_err(), "Taking an expired offer should fail");
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.bob_token_account_b,
+        5 * TOKEN_B,
+        "Bob should still have all 5 token B",
+    );
+}
+
+#[test]
+fn test_anyone_can_close_expired_offer() {
+    let mut test_environment = setup_escrow_test();
+
+    // Alice creates an offer that expires in an hour
+    let clock: anchor_lang::prelude::Clock = test_environment.litesvm.get_sysvar();
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        3 * TOKEN_A,
+        2 * TOKEN_B,
+        Some(clock.unix_timestamp + 60 * 60),
+    ).unwrap();
+
+    // Bob can't close it before the deadline
+    let bob = test_environment.bob.insecure_clone();
+    let result = execute_close_expired_offer(
+        &mut test_environment,
+        &bob,
+        &alice.pubkey(),
+        alice_token_account_a,
+        offer_account,
+        vault,
+    );
+    assert!(result.is_err(), "Closing an offer before it expires should fail");
+
+    advance_clock(&mut test_environment, 60 * 60);
+
+    // Once it has expired, Bob closes it and Alice gets her tokens and rent back
+    let alice_lamports_before = test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports;
+    execute_close_expired_offer(
+        &mut test_environment,
+        &bob,
+        &alice.pubkey(),
+        alice_token_account_a,
+        offer_account,
+        vault,
+    ).unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        10 * TOKEN_A,
+        "Alice should have all 10 token A back after the offer expired",
+    );
+    assert!(
+        test_environment.litesvm.get_account(&alice.pubkey()).unwrap().lamports > alice_lamports_before,
+        "Alice should get the rent back"
+    );
+    check_account_is_closed(
+        &test_environment.litesvm,
+        &offer_account,
+        "Offer account should be closed after expiring",
+    );
+    check_account_is_closed(
+        &test_environment.litesvm,
+        &vault,
+        "Vault should be closed after expiring",
+    );
+}
+
+#[test]
+fn test_close_offer_without_expiry_fails() {
+    let mut test_environment = setup_escrow_test();
+
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        3 * TOKEN_A,
+        2 * TOKEN_B,
+        None,
+    ).unwrap();
+
+    // Offers without a deadline never expire
+    advance_clock(&mut test_environment, 365 * 24 * 60 * 60);
+    let bob = test_environment.bob.insecure_clone();
+    let result = execute_close_expired_offer(
+        &mut test_environment,
+        &bob,
+        &alice.pubkey(),
+        alice_token_account_a,
+        offer_account,
+        vault,
+    );
+    assert!(result.is_err(), "Closing an offer without an expiry should fail");
+}
+
+#[test]
+fn test_partial_fills() {
+    let mut test_environment = setup_escrow_test();
+
+    // Alice creates an offer: 3 token A for 2 token B
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        3 * TOKEN_A,
+        2 * TOKEN_B,
+        None,
+    ).unwrap();
+
+    // Bob takes 1 token A, and pays 2/3 of a token B rounded up
+    let bob = test_environment.bob.insecure_clone();
+    let bob_token_account_a = test_environment.bob_token_account_a;
+    let bob_token_account_b = test_environment.bob_token_account_b;
+    let alice_token_account_b = test_environment.alice_token_account_b;
+    let events = execute_take_offer_for_events(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        1 * TOKEN_A,
+    );
+
+    assert_eq!(events.len(), 1, "Each fill should emit one OfferFilled event");
+    let event = &events[0];
+    assert_eq!(event.offer, offer_account);
+    assert_eq!(event.id, offer_id);
+    assert_eq!(event.maker, alice.pubkey());
+    assert_eq!(event.taker, bob.pubkey());
+    assert_eq!(event.token_a_amount, 1 * TOKEN_A);
+    assert_eq!(event.token_b_amount, 666_666_667);
+    assert_eq!(event.token_a_remaining_amount, 2 * TOKEN_A);
+    assert_eq!(event.token_b_remaining_amount, 1_333_333_333);
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_b,
+        666_666_667,
+        "Alice should have received 2/3 of a token B, rounded up",
+    );
+    assert_token_balance(
+        &test_environment.litesvm,
+        &vault,
+        2 * TOKEN_A,
+        "The vault should have 2 token A left",
+    );
+    let offer = {
+        use anchor_lang::AccountDeserialize;
+        let account = test_environment.litesvm.get_account(&offer_account).unwrap();
+        crate::state::Offer::try_deserialize(&mut account.data.as_slice()).unwrap()
+    };
+    assert_eq!(offer.token_a_remaining_amount, 2 * TOKEN_A);
+    assert_eq!(offer.token_b_remaining_amount, 1_333_333_333);
+
+    // Bob can't take more than what's left
+    let result = execute_take_offer(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        3 * TOKEN_A,
+    );
+    assert!(result.is_err(), "Taking more than what's left of the offer should fail");
+
+    // Bob takes the rest, which closes the offer. Its rounded up price is capped
+    // at what's left to pay, so Alice gets exactly what she asked for.
+    let events = execute_take_offer_for_events(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        2 * TOKEN_A,
+    );
+
+    assert_eq!(events.len(), 1, "Each fill should emit one OfferFilled event");
+    let event = &events[0];
+    assert_eq!(event.token_a_amount, 2 * TOKEN_A);
+    assert_eq!(event.token_b_amount, 1_333_333_333);
+    assert_eq!(event.token_a_remaining_amount, 0);
+    assert_eq!(event.token_b_remaining_amount, 0);
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.bob_token_account_a,
+        3 * TOKEN_A,
+        "Bob should have received all 3 token A",
+    );
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_b,
+        2 * TOKEN_B,
+        "Alice should have received exactly 2 token B",
+    );
+    check_account_is_closed(
+        &test_environment.litesvm,
+        &offer_account,
+        "Offer account should be closed once fully filled",
+    );
+    check_account_is_closed(
+        &test_environment.litesvm,
+        &vault,
+        "Vault should be closed once fully filled",
+    );
+}
+
+#[test]
+fn test_partially_filled_offer_can_be_refunded() {
+    let mut test_environment = setup_escrow_test();
+
+    // Alice creates an offer: 3 token A for 2 token B
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        3 * TOKEN_A,
+        2 * TOKEN_B,
+        None,
+    ).unwrap();
+
+    // Bob takes 1 token A
+    let bob = test_environment.bob.insecure_clone();
+    let bob_token_account_a = test_environment.bob_token_account_a;
+    let bob_token_account_b = test_environment.bob_token_account_b;
+    let alice_token_account_b = test_environment.alice_token_account_b;
+    execute_take_offer(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        1 * TOKEN_A,
+    ).unwrap();
+
+    // Alice refunds the 2 token A left
+    execute_refund_offer(
+        &mut test_environment,
+        &alice,
+        alice_token_account_a,
+        offer_account,
+        vault,
+    ).unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_a,
+        9 * TOKEN_A,
+        "Alice should have the 2 unfilled token A back",
+    );
+    check_account_is_closed(
+        &test_environment.litesvm,
+        &offer_account,
+        "Offer account should be closed after refund",
+    );
+}
+
+#[test]
+fn test_fills_are_priced_rounded_up() {
+    let mut test_environment = setup_escrow_test();
+
+    // Alice creates an offer: 1000 units of token A for 1 unit of token B
+    let offer_id = next_offer_id(&test_environment, &test_environment.alice.pubkey());
+    let alice = test_environment.alice.insecure_clone();
+    let alice_token_account_a = test_environment.alice_token_account_a;
+    let (offer_account, vault) = execute_make_offer(
+        &mut test_environment,
+        offer_id,
+        &alice,
+        alice_token_account_a,
+        1000,
+        1,
+        None,
+    ).unwrap();
+
+    // Bob takes 1 unit of token A, which costs a thousandth of a unit of token B
+    // rounded up to a whole unit
+    let bob = test_environment.bob.insecure_clone();
+    let bob_token_account_a = test_environment.bob_token_account_a;
+    let bob_token_account_b = test_environment.bob_token_account_b;
+    let alice_token_account_b = test_environment.alice_token_account_b;
+    execute_take_offer(
+        &mut test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        1,
+    ).unwrap();
+
+    assert_token_balance(
+        &test_environment.litesvm,
+        &test_environment.alice_token_account_b,
+        1,
+        "Alice should have been paid the whole unit of token B",
+    );
+
+    // Nothing is left to pay, so Bob can't take the rest of the offer for free
+    let take_offer_instruction = take_offer_instruction(
+        &test_environment,
+        &bob,
+        &alice,
+        bob_token_account_a,
+        bob_token_account_b,
+        alice_token_account_b,
+        offer_account,
+        vault,
+        1,
+    );
+    let result = send_transaction_for_logs(
+        &mut test_environment.litesvm,
+        &[take_offer_instruction],
+        &[&bob],
+        &bob.pubkey(),
+    );
+    assert_anchor_error(
+        result,
+        ErrorCode::FillTooSmall.into(),
+        "A fill that pays nothing should fail",
+    );
+    assert_token_balance(
+        &test_environment.litesvm,
+        &vault,
+        999,
+        "The vault should still have the rest of the offer",
+    );
+}
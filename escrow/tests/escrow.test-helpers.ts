@@ -19,6 +19,16 @@ export const getRandomBigInt = () => {
   return BigInt(Math.floor(Math.random() * 1_000_000_000_000_000_000));
 };
 
+// Offer IDs are handed out in order by each maker's offer counter
+export async function getNextOfferId(connection: Connection, maker: Address) {
+  const offerCounterPDAAndBump = await connection.getPDAAndBump(programClient.ESCROW_PROGRAM_ADDRESS, [
+    "offer_counter",
+    maker,
+  ]);
+  const offerCounter = await programClient.fetchMaybeOfferCounter(connection.rpc, offerCounterPDAAndBump.pda);
+  return offerCounter.exists ? offerCounter.data.nextId : 0n;
+}
+
 // Helper function to create a test offer
 export async function createTestOffer(params: {
   connection: Connection;
@@ -29,6 +39,7 @@ export async function createTestOffer(params: {
   tokenAOfferedAmount: bigint;
   tokenBWantedAmount: bigint;
   offerId?: bigint;
+  expiresAt?: bigint | null;
 }) {
   const {
     connection,
@@ -38,10 +49,15 @@ export async function createTestOffer(params: {
     makerTokenAccountA,
     tokenAOfferedAmount,
     tokenBWantedAmount,
-    offerId = getRandomBigInt(),
+    offerId = await getNextOfferId(connection, maker.address),
+    expiresAt = null,
   } = params;
 
-  const offerPDAAndBump = await connection.getPDAAndBump(programClient.ESCROW_PROGRAM_ADDRESS, ["offer", offerId]);
+  const offerPDAAndBump = await connection.getPDAAndBump(programClient.ESCROW_PROGRAM_ADDRESS, [
+    "offer",
+    maker.address,
+    offerId,
+  ]);
   const offer = offerPDAAndBump.pda;
   const vault = await connection.getTokenAccountAddress(offer, tokenMintA, true);
 
@@ -55,6 +71,7 @@ export async function createTestOffer(params: {
     id: offerId,
     tokenAOfferedAmount,
     tokenBWantedAmount,
+    expiresAt,
     tokenProgram: TOKEN_EXTENSIONS_PROGRAM,
   });
 
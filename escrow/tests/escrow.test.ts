@@ -4,7 +4,7 @@ import * as programClient from "../dist/js-client";
 import { getOfferDecoder, OFFER_DISCRIMINATOR } from "../dist/js-client";
 import { connect, Connection, TOKEN_EXTENSIONS_PROGRAM, ErrorWithTransaction } from "solana-kite";
 import { type KeyPairSigner, type Address } from "@solana/kit";
-import { createTestOffer, getRandomBigInt, ONE_SOL } from "./escrow.test-helpers";
+import { createTestOffer, getNextOfferId, ONE_SOL } from "./escrow.test-helpers";
 
 const INSUFFICIENT_FUNDS_ERROR = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb.TransferChecked: insufficient funds";
 const REFUND_OFFER_ERROR =
@@ -104,8 +104,8 @@ describe("Escrow", () => {
     });
 
     test("fails when trying to reuse an existing offer ID", async () => {
-      // First, create an offer with Alice using a specific offer ID
-      const offerId = getRandomBigInt();
+      // First, create an offer with Alice using her next offer ID
+      const offerId = await getNextOfferId(connection, alice.address);
       await createTestOffer({
         connection,
         maker: alice,
@@ -117,20 +117,17 @@ describe("Escrow", () => {
         offerId,
       });
 
-      // Now try to create another offer with Bob using the same offer ID
-
-      // Create bobTokenAccountA if it doesn't exist
-      bobTokenAccountA = await connection.getTokenAccountAddress(bob.address, tokenMintA, true);
-
+      // Now try to create another offer with Alice using the same offer ID
+      // (offers are namespaced by maker, so only Alice can collide with her own IDs)
       let testOffer: { offer: Address; vault: Address; offerId: bigint; signature: string };
       try {
         testOffer = await createTestOffer({
           connection,
-          maker: bob,
+          maker: alice,
           tokenMintA,
           tokenMintB,
-          makerTokenAccountA: bobTokenAccountA,
-          tokenAOfferedAmount: bobInitialTokenAAmount,
+          makerTokenAccountA: aliceTokenAccountA,
+          tokenAOfferedAmount,
           tokenBWantedAmount,
           offerId, // Reusing the same offer ID
         });
@@ -299,6 +296,7 @@ describe("Escrow", () => {
         makerTokenAccountB: aliceTokenAccountB,
         offer: testOffer,
         vault: testVault,
+        tokenAAmount: tokenAOfferedAmount,
         tokenProgram: TOKEN_EXTENSIONS_PROGRAM,
       });
 
@@ -349,6 +347,7 @@ describe("Escrow", () => {
         makerTokenAccountB: aliceTokenAccountB,
         offer,
         vault,
+        tokenAAmount: tokenAOfferedAmount,
         tokenProgram: TOKEN_EXTENSIONS_PROGRAM,
       });
 
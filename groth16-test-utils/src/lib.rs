@@ -0,0 +1,174 @@
+//! Groth16 fixtures shared by the tests of the verifier crates and programs.
+//!
+//! Points come out in the alt_bn128 syscall encoding the verifiers expect:
+//! big-endian coordinates, G2 coordinates as `c1 || c0`.
+
+use ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable},
+};
+use ark_serialize::CanonicalSerialize;
+use ark_snark::SNARK;
+use rand::thread_rng;
+
+// Proves knowledge of `x` with `x * x = y`, `y` public
+#[derive(Clone)]
+pub struct SquareCircuit {
+    pub x: Option<Fr>,
+}
+
+impl ConstraintSynthesizer<Fr> for SquareCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        SquaresCircuit { xs: vec![self.x] }.generate_constraints(cs)
+    }
+}
+
+// Proves knowledge of `x_i` with `x_i * x_i = y_i` for every public `y_i`
+#[derive(Clone)]
+pub struct SquaresCircuit {
+    pub xs: Vec<Option<Fr>>,
+}
+
+impl ConstraintSynthesizer<Fr> for SquaresCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for x_value in self.xs {
+            let y_value = x_value.map(|x| x * x);
+            let y = cs.new_input_variable(|| y_value.ok_or(SynthesisError::AssignmentMissing))?;
+            let x = cs.new_witness_variable(|| x_value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce_constraint(lc!() + x, lc!() + x, lc!() + y)?;
+        }
+        Ok(())
+    }
+}
+
+// Copies a witness into every public input, so any field elements can be
+// proven. It stands in for circuits whose statement the test doesn't care
+// about: the verifying key still binds each input.
+#[derive(Clone)]
+pub struct InputsCircuit {
+    pub inputs: Vec<Option<Fr>>,
+}
+
+impl ConstraintSynthesizer<Fr> for InputsCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for value in self.inputs {
+            let input = cs.new_input_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+            let witness = cs.new_witness_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce_constraint(lc!() + witness, lc!() + Variable::One, lc!() + input)?;
+        }
+        Ok(())
+    }
+}
+
+// arkworks serializes coordinates little-endian, the syscalls want big-endian.
+// Reversing each 64-byte G2 coordinate also swaps c0 and c1 into syscall order.
+pub fn g1_bytes(point: &G1Affine) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    point.serialize_uncompressed(&mut bytes[..]).unwrap();
+    bytes[..32].reverse();
+    bytes[32..].reverse();
+    bytes
+}
+
+pub fn g2_bytes(point: &G2Affine) -> [u8; 128] {
+    let mut bytes = [0u8; 128];
+    point.serialize_uncompressed(&mut bytes[..]).unwrap();
+    bytes[..64].reverse();
+    bytes[64..].reverse();
+    bytes
+}
+
+pub fn fr_bytes(value: Fr) -> [u8; 32] {
+    value.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+/// The field element a 32-byte big-endian input stands for, reduced mod `r`
+pub fn fr_from_bytes(bytes: &[u8; 32]) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes)
+}
+
+/// A verifying key in the syscall encoding, `ic` holding `vk_ic`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyingKeyBytes {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: Vec<[u8; 64]>,
+}
+
+impl VerifyingKeyBytes {
+    pub fn new(vk: &VerifyingKey<Bn254>) -> Self {
+        VerifyingKeyBytes {
+            alpha_g1: g1_bytes(&vk.alpha_g1),
+            beta_g2: g2_bytes(&vk.beta_g2),
+            gamma_g2: g2_bytes(&vk.gamma_g2),
+            delta_g2: g2_bytes(&vk.delta_g2),
+            ic: vk.gamma_abc_g1.iter().map(g1_bytes).collect(),
+        }
+    }
+}
+
+/// A proof in the syscall encoding, with `a` already negated as the verifiers expect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofBytes {
+    pub a: [u8; 64],
+    pub b: [u8; 128],
+    pub c: [u8; 64],
+}
+
+impl ProofBytes {
+    pub fn new(proof: &Proof<Bn254>) -> Self {
+        ProofBytes {
+            a: g1_bytes(&-proof.a),
+            b: g2_bytes(&proof.b),
+            c: g1_bytes(&proof.c),
+        }
+    }
+
+    /// `a || b || c`, the 256-byte layout of a proof passed as one array
+    pub fn to_bytes(&self) -> [u8; 256] {
+        let mut bytes = [0u8; 256];
+        bytes[..64].copy_from_slice(&self.a);
+        bytes[64..192].copy_from_slice(&self.b);
+        bytes[192..].copy_from_slice(&self.c);
+        bytes
+    }
+}
+
+/// `IC_0 + sum x_i IC_i`, computed off-chain
+pub fn prepared_inputs(vk: &VerifyingKey<Bn254>, inputs: &[Fr]) -> [u8; 64] {
+    let mut acc: G1Projective = vk.gamma_abc_g1[0].into_group();
+    for (input, ic) in inputs.iter().zip(vk.gamma_abc_g1.iter().skip(1)) {
+        acc += ic.mul_bigint(input.into_bigint());
+    }
+    g1_bytes(&acc.into_affine())
+}
+
+/// Proving and verifying keys for an `InputsCircuit` with `nr_public_inputs` inputs
+pub struct InputsKeys {
+    pub pk: ProvingKey<Bn254>,
+    pub vk: VerifyingKey<Bn254>,
+}
+
+impl InputsKeys {
+    pub fn setup(nr_public_inputs: usize) -> Self {
+        let circuit = InputsCircuit { inputs: vec![None; nr_public_inputs] };
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(circuit, &mut thread_rng()).unwrap();
+        InputsKeys { pk, vk }
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKeyBytes {
+        VerifyingKeyBytes::new(&self.vk)
+    }
+
+    /// A valid proof for the 32-byte big-endian `inputs`
+    pub fn prove(&self, inputs: &[[u8; 32]]) -> ProofBytes {
+        let circuit = InputsCircuit { inputs: inputs.iter().map(|input| Some(fr_from_bytes(input))).collect() };
+        ProofBytes::new(&Groth16::<Bn254>::prove(&self.pk, circuit, &mut thread_rng()).unwrap())
+    }
+}
@@ -0,0 +1,133 @@
+//! BN254 group operations on syscall-encoded points.
+//!
+//! Every function has the same signature under both backends, so callers
+//! never need to know which one is compiled in.
+
+use crate::Groth16Error;
+
+pub const G1_LEN: usize = 64;
+pub const G2_LEN: usize = 128;
+pub const SCALAR_LEN: usize = 32;
+/// One `(G1, G2)` pair of a pairing input
+pub const PAIRING_ELEMENT_LEN: usize = G1_LEN + G2_LEN;
+
+/// The BN254 scalar field modulus `r`, big-endian
+pub const FR_MODULUS_BE: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// True if the big-endian `scalar` is a canonical scalar field element
+pub fn is_less_than_field_size_be(scalar: &[u8; 32]) -> bool {
+    scalar < &FR_MODULUS_BE
+}
+
+#[cfg(not(feature = "arkworks"))]
+mod backend {
+    use alloc::vec::Vec;
+    use solana_bn254::prelude::{alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing};
+
+    use super::*;
+
+    pub fn g1_add(a: &[u8; G1_LEN], b: &[u8; G1_LEN]) -> Result<[u8; G1_LEN], Groth16Error> {
+        alt_bn128_addition(&[&a[..], &b[..]].concat())
+            .map_err(|_| Groth16Error::PreparingInputsG1AdditionFailed)?[..]
+            .try_into()
+            .map_err(|_| Groth16Error::PreparingInputsG1AdditionFailed)
+    }
+
+    pub fn g1_mul(point: &[u8; G1_LEN], scalar: &[u8; SCALAR_LEN]) -> Result<[u8; G1_LEN], Groth16Error> {
+        alt_bn128_multiplication(&[&point[..], &scalar[..]].concat())
+            .map_err(|_| Groth16Error::PreparingInputsG1MulFailed)?[..]
+            .try_into()
+            .map_err(|_| Groth16Error::PreparingInputsG1MulFailed)
+    }
+
+    pub fn pairing_check(input: &[u8]) -> Result<bool, Groth16Error> {
+        let result: Vec<u8> =
+            alt_bn128_pairing(input).map_err(|_| Groth16Error::PairingVerificationError)?;
+        Ok(result.last() == Some(&1) && result[..result.len() - 1].iter().all(|b| *b == 0))
+    }
+}
+
+#[cfg(feature = "arkworks")]
+mod backend {
+    use alloc::vec::Vec;
+    use ark_bn254::{Bn254, Fq, Fq2, G1Affine, G2Affine};
+    use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup};
+    use ark_ff::{BigInteger, BigInteger256, One, PrimeField};
+
+    use super::*;
+
+    fn bigint_from_be(bytes: &[u8]) -> BigInteger256 {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let end = bytes.len() - 8 * i;
+            *limb = u64::from_be_bytes(bytes[end - 8..end].try_into().unwrap());
+        }
+        BigInteger256::new(limbs)
+    }
+
+    fn fq_from_be(bytes: &[u8]) -> Option<Fq> {
+        Fq::from_bigint(bigint_from_be(bytes))
+    }
+
+    fn fq_to_be(value: &Fq, out: &mut [u8]) {
+        out.copy_from_slice(&value.into_bigint().to_bytes_be());
+    }
+
+    fn g1_from_bytes(bytes: &[u8]) -> Option<G1Affine> {
+        if bytes.iter().all(|b| *b == 0) {
+            return Some(G1Affine::zero());
+        }
+        let point = G1Affine::new_unchecked(fq_from_be(&bytes[..32])?, fq_from_be(&bytes[32..64])?);
+        point.is_on_curve().then_some(point)
+    }
+
+    fn g1_to_bytes(point: &G1Affine) -> [u8; G1_LEN] {
+        let mut out = [0u8; G1_LEN];
+        if let Some((x, y)) = point.xy() {
+            fq_to_be(x, &mut out[..32]);
+            fq_to_be(y, &mut out[32..]);
+        }
+        out
+    }
+
+    fn g2_from_bytes(bytes: &[u8]) -> Option<G2Affine> {
+        if bytes.iter().all(|b| *b == 0) {
+            return Some(G2Affine::zero());
+        }
+        let x = Fq2::new(fq_from_be(&bytes[32..64])?, fq_from_be(&bytes[..32])?);
+        let y = Fq2::new(fq_from_be(&bytes[96..128])?, fq_from_be(&bytes[64..96])?);
+        let point = G2Affine::new_unchecked(x, y);
+        (point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve()).then_some(point)
+    }
+
+    pub fn g1_add(a: &[u8; G1_LEN], b: &[u8; G1_LEN]) -> Result<[u8; G1_LEN], Groth16Error> {
+        let a = g1_from_bytes(a).ok_or(Groth16Error::PreparingInputsG1AdditionFailed)?;
+        let b = g1_from_bytes(b).ok_or(Groth16Error::PreparingInputsG1AdditionFailed)?;
+        Ok(g1_to_bytes(&(a + b).into_affine()))
+    }
+
+    pub fn g1_mul(point: &[u8; G1_LEN], scalar: &[u8; SCALAR_LEN]) -> Result<[u8; G1_LEN], Groth16Error> {
+        let point = g1_from_bytes(point).ok_or(Groth16Error::PreparingInputsG1MulFailed)?;
+        Ok(g1_to_bytes(&point.mul_bigint(bigint_from_be(scalar)).into_affine()))
+    }
+
+    pub fn pairing_check(input: &[u8]) -> Result<bool, Groth16Error> {
+        if input.len() % PAIRING_ELEMENT_LEN != 0 {
+            return Err(Groth16Error::PairingVerificationError);
+        }
+
+        let mut g1_points = Vec::with_capacity(input.len() / PAIRING_ELEMENT_LEN);
+        let mut g2_points = Vec::with_capacity(input.len() / PAIRING_ELEMENT_LEN);
+        for element in input.chunks(PAIRING_ELEMENT_LEN) {
+            g1_points.push(g1_from_bytes(&element[..G1_LEN]).ok_or(Groth16Error::PairingVerificationError)?);
+            g2_points.push(g2_from_bytes(&element[G1_LEN..]).ok_or(Groth16Error::PairingVerificationError)?);
+        }
+
+        Ok(Bn254::multi_pairing(g1_points, g2_points).0.is_one())
+    }
+}
+
+pub use backend::{g1_add, g1_mul, pairing_check};
@@ -0,0 +1,153 @@
+use core::fmt;
+
+/// Errors returned by proof-verify as `ProgramError::Custom(code)`.
+///
+/// The discriminants are part of the program's interface: never reorder or
+/// reuse them, only append new variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Groth16Error {
+    IncompatibleVerifyingKeyWithNrPublicInputs = 0,
+    ProofVerificationFailed = 1,
+    PairingVerificationError = 2,
+    PreparingInputsG1AdditionFailed = 3,
+    PreparingInputsG1MulFailed = 4,
+    InvalidG1Length = 5,
+    InvalidG2Length = 6,
+    InvalidPublicInputsLength = 7,
+    DecompressingG1Failed = 8,
+    DecompressingG2Failed = 9,
+    PublicInputGreaterThenFieldSize = 10,
+    AccountToCheckMissing = 11,
+    CircuitMismatch = 12,
+    PublicInputsMismatch = 13,
+    InvalidTokenAccount = 14,
+    TokenMintMismatch = 15,
+    TokenOwnerMismatch = 16,
+    InsufficientTokenBalance = 17,
+    InvalidThresholdInput = 18,
+    EmptyBatch = 19,
+    BatchTooLarge = 20,
+    UnsupportedInstructionVersion = 21,
+    InputHashFailed = 22,
+    UnknownMerkleRoot = 23,
+    MerkleTreeFull = 24,
+    RootAuthorityMismatch = 25,
+}
+
+impl Groth16Error {
+    pub const ALL: [Groth16Error; 26] = [
+        Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs,
+        Groth16Error::ProofVerificationFailed,
+        Groth16Error::PairingVerificationError,
+        Groth16Error::PreparingInputsG1AdditionFailed,
+        Groth16Error::PreparingInputsG1MulFailed,
+        Groth16Error::InvalidG1Length,
+        Groth16Error::InvalidG2Length,
+        Groth16Error::InvalidPublicInputsLength,
+        Groth16Error::DecompressingG1Failed,
+        Groth16Error::DecompressingG2Failed,
+        Groth16Error::PublicInputGreaterThenFieldSize,
+        Groth16Error::AccountToCheckMissing,
+        Groth16Error::CircuitMismatch,
+        Groth16Error::PublicInputsMismatch,
+        Groth16Error::InvalidTokenAccount,
+        Groth16Error::TokenMintMismatch,
+        Groth16Error::TokenOwnerMismatch,
+        Groth16Error::InsufficientTokenBalance,
+        Groth16Error::InvalidThresholdInput,
+        Groth16Error::EmptyBatch,
+        Groth16Error::BatchTooLarge,
+        Groth16Error::UnsupportedInstructionVersion,
+        Groth16Error::InputHashFailed,
+        Groth16Error::UnknownMerkleRoot,
+        Groth16Error::MerkleTreeFull,
+        Groth16Error::RootAuthorityMismatch,
+    ];
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|e| e.code() == code)
+    }
+}
+
+impl fmt::Display for Groth16Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs => "Incompatible Verifying Key with number of public inputs",
+            Groth16Error::ProofVerificationFailed => "ProofVerificationFailed",
+            Groth16Error::PairingVerificationError => "PairingVerificationError",
+            Groth16Error::PreparingInputsG1AdditionFailed => "PreparingInputsG1AdditionFailed",
+            Groth16Error::PreparingInputsG1MulFailed => "PreparingInputsG1MulFailed",
+            Groth16Error::InvalidG1Length => "InvalidG1Length",
+            Groth16Error::InvalidG2Length => "InvalidG2Length",
+            Groth16Error::InvalidPublicInputsLength => "InvalidPublicInputsLength",
+            Groth16Error::DecompressingG1Failed => "DecompressingG1Failed",
+            Groth16Error::DecompressingG2Failed => "DecompressingG2Failed",
+            Groth16Error::PublicInputGreaterThenFieldSize => "PublicInputGreaterThenFieldSize",
+            Groth16Error::AccountToCheckMissing => "AccountToCheckMissing",
+            Groth16Error::CircuitMismatch => "CircuitMismatch",
+            Groth16Error::PublicInputsMismatch => "PublicInputsMismatch",
+            Groth16Error::InvalidTokenAccount => "InvalidTokenAccount",
+            Groth16Error::TokenMintMismatch => "TokenMintMismatch",
+            Groth16Error::TokenOwnerMismatch => "TokenOwnerMismatch",
+            Groth16Error::InsufficientTokenBalance => "InsufficientTokenBalance",
+            Groth16Error::InvalidThresholdInput => "InvalidThresholdInput",
+            Groth16Error::EmptyBatch => "EmptyBatch",
+            Groth16Error::BatchTooLarge => "BatchTooLarge",
+            Groth16Error::UnsupportedInstructionVersion => "UnsupportedInstructionVersion",
+            Groth16Error::InputHashFailed => "InputHashFailed",
+            Groth16Error::UnknownMerkleRoot => "UnknownMerkleRoot",
+            Groth16Error::MerkleTreeFull => "MerkleTreeFull",
+            Groth16Error::RootAuthorityMismatch => "RootAuthorityMismatch",
+        };
+        f.write_str(message)
+    }
+}
+
+impl core::error::Error for Groth16Error {}
+
+#[cfg(feature = "solana")]
+mod solana {
+    use solana_program::{instruction::InstructionError, program_error::ProgramError};
+    use solana_transaction_error::TransactionError;
+
+    use super::Groth16Error;
+
+    impl Groth16Error {
+        /// Maps a `ProgramError` returned by the processor back to its variant
+        pub fn from_program_error(err: &ProgramError) -> Option<Self> {
+            match err {
+                ProgramError::Custom(code) => Self::from_code(*code),
+                _ => None,
+            }
+        }
+
+        /// Client-side decoding of an instruction error from a failed transaction
+        pub fn from_instruction_error(err: &InstructionError) -> Option<Self> {
+            match err {
+                InstructionError::Custom(code) => Self::from_code(*code),
+                _ => None,
+            }
+        }
+
+        /// Client-side decoding of a failed transaction. Returns the index of the
+        /// failing instruction along with the decoded error.
+        pub fn from_transaction_error(err: &TransactionError) -> Option<(u8, Self)> {
+            match err {
+                TransactionError::InstructionError(index, err) => {
+                    Self::from_instruction_error(err).map(|e| (*index, e))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    impl From<Groth16Error> for ProgramError {
+        fn from(e: Groth16Error) -> Self {
+            ProgramError::Custom(e.code())
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Groth16 verification on BN254 shared by the proof-verify program and its
+//! clients.
+//!
+//! Points use the alt_bn128 syscall encoding: big-endian coordinates, G2
+//! coordinates as `c1 || c0`, and the point at infinity as all zeros. The
+//! group operations come from the alt_bn128 syscalls (`syscalls`, the
+//! default) or from arkworks (`arkworks`), so the same code runs on-chain and
+//! off-chain.
+
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "syscalls", feature = "arkworks")))]
+compile_error!("enable either the `syscalls` or the `arkworks` feature");
+
+pub mod bn254;
+pub mod error;
+pub mod verifier;
+
+pub use error::*;
+pub use verifier::*;
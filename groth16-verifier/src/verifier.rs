@@ -0,0 +1,97 @@
+use alloc::{boxed::Box, vec::Vec};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::bn254::{g1_add, g1_mul, is_less_than_field_size_be, pairing_check, PAIRING_ELEMENT_LEN};
+use crate::Groth16Error;
+
+#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct Groth16VerifyingKeyPrepared {
+    pub vk_alpha_g1: [u8; 64],
+    pub vk_beta_g2: [u8; 128],
+    pub vk_gamma_g2: [u8; 128],
+    pub vk_delta_g2: [u8; 128],
+}
+
+/// A proof with its public inputs already prepared against `vk_ic`.
+/// `proof_a` is negated, so the check is
+/// `e(-A, B) e(L, gamma) e(C, delta) e(alpha, beta) = 1`.
+#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct Groth16VerifierPrepared {
+    pub proof_a: [u8; 64],
+    pub proof_b: [u8; 128],
+    pub proof_c: [u8; 64],
+    pub prepared_public_inputs: [u8; 64],
+    pub verifying_key: Box<Groth16VerifyingKeyPrepared>,
+}
+
+impl Groth16VerifierPrepared {
+    pub fn new(
+        proof_a: &[u8],
+        proof_b: &[u8],
+        proof_c: &[u8],
+        prepared_public_inputs: &[u8],
+        verifying_key: Box<Groth16VerifyingKeyPrepared>,
+    ) -> Result<Self, Groth16Error> {
+        Ok(Groth16VerifierPrepared {
+            proof_a: proof_a.try_into().map_err(|_| Groth16Error::InvalidG1Length)?,
+            proof_b: proof_b.try_into().map_err(|_| Groth16Error::InvalidG2Length)?,
+            proof_c: proof_c.try_into().map_err(|_| Groth16Error::InvalidG1Length)?,
+            prepared_public_inputs: prepared_public_inputs
+                .try_into()
+                .map_err(|_| Groth16Error::InvalidPublicInputsLength)?,
+            verifying_key,
+        })
+    }
+
+    /// The four `(G1, G2)` pairs whose product must be one
+    pub fn pairing_input(&self) -> Vec<u8> {
+        let mut input = Vec::with_capacity(4 * PAIRING_ELEMENT_LEN);
+        input.extend_from_slice(&self.proof_a);
+        input.extend_from_slice(&self.proof_b);
+        input.extend_from_slice(&self.prepared_public_inputs);
+        input.extend_from_slice(&self.verifying_key.vk_gamma_g2);
+        input.extend_from_slice(&self.proof_c);
+        input.extend_from_slice(&self.verifying_key.vk_delta_g2);
+        input.extend_from_slice(&self.verifying_key.vk_alpha_g1);
+        input.extend_from_slice(&self.verifying_key.vk_beta_g2);
+        input
+    }
+
+    /// Returns `Ok(false)` for a well-formed proof that doesn't verify, and an
+    /// error if the points can't be decoded.
+    pub fn verify(&self) -> Result<bool, Groth16Error> {
+        pairing_check(&self.pairing_input())
+    }
+}
+
+/// Computes `vk_ic[0] + sum(input_i * vk_ic[i + 1])`. Inputs are 32-byte
+/// big-endian field elements and must be smaller than the field modulus.
+pub fn prepare_public_inputs(
+    vk_ic: &[[u8; 64]],
+    public_inputs: &[[u8; 32]],
+) -> Result<[u8; 64], Groth16Error> {
+    if !public_inputs.iter().all(is_less_than_field_size_be) {
+        return Err(Groth16Error::PublicInputGreaterThenFieldSize);
+    }
+    prepare_public_inputs_unchecked(vk_ic, public_inputs)
+}
+
+/// `prepare_public_inputs` without the field size check. An input of `r` or
+/// more acts as its value mod `r`, so two different byte strings can prepare
+/// to the same point.
+pub fn prepare_public_inputs_unchecked(
+    vk_ic: &[[u8; 64]],
+    public_inputs: &[[u8; 32]],
+) -> Result<[u8; 64], Groth16Error> {
+    if public_inputs.len() + 1 != vk_ic.len() {
+        return Err(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs);
+    }
+
+    let mut prepared_public_inputs = vk_ic[0];
+    for (input, ic) in public_inputs.iter().zip(vk_ic.iter().skip(1)) {
+        let term = g1_mul(ic, input)?;
+        prepared_public_inputs = g1_add(&term, &prepared_public_inputs)?;
+    }
+
+    Ok(prepared_public_inputs)
+}
@@ -0,0 +1,142 @@
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use ark_snark::SNARK;
+use ark_std::rand::thread_rng;
+use groth16_test_utils::{fr_bytes, g1_bytes, g2_bytes, SquareCircuit};
+use groth16_verifier::bn254::{g1_add, g1_mul, pairing_check, FR_MODULUS_BE};
+use groth16_verifier::*;
+
+fn prepared_key(vk: &VerifyingKey<Bn254>) -> Groth16VerifyingKeyPrepared {
+    Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: g1_bytes(&vk.alpha_g1),
+        vk_beta_g2: g2_bytes(&vk.beta_g2),
+        vk_gamma_g2: g2_bytes(&vk.gamma_g2),
+        vk_delta_g2: g2_bytes(&vk.delta_g2),
+    }
+}
+
+fn square_proof(x: u64) -> (VerifyingKey<Bn254>, Proof<Bn254>, [u8; 32]) {
+    let rng = &mut thread_rng();
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(SquareCircuit { x: None }, rng).unwrap();
+    let x = Fr::from(x);
+    let proof = Groth16::<Bn254>::prove(&pk, SquareCircuit { x: Some(x) }, rng).unwrap();
+    (vk, proof, fr_bytes(x * x))
+}
+
+fn verifier_for(vk: &VerifyingKey<Bn254>, proof: &Proof<Bn254>, input: [u8; 32]) -> Groth16VerifierPrepared {
+    let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_bytes).collect();
+    let prepared = prepare_public_inputs(&vk_ic, &[input]).unwrap();
+    Groth16VerifierPrepared::new(
+        &g1_bytes(&(-proof.a)),
+        &g2_bytes(&proof.b),
+        &g1_bytes(&proof.c),
+        &prepared,
+        Box::new(prepared_key(vk)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_verify_accepts_valid_proof() {
+    let (vk, proof, input) = square_proof(7);
+    assert!(verifier_for(&vk, &proof, input).verify().unwrap());
+}
+
+#[test]
+fn test_verify_rejects_wrong_public_input() {
+    let (vk, proof, _) = square_proof(7);
+    assert!(!verifier_for(&vk, &proof, fr_bytes(Fr::from(50u64))).verify().unwrap());
+}
+
+#[test]
+fn test_verify_errors_on_points_off_the_curve() {
+    let (vk, proof, input) = square_proof(7);
+    let mut verifier = verifier_for(&vk, &proof, input);
+    verifier.proof_a = [1u8; 64];
+    assert_eq!(verifier.verify(), Err(Groth16Error::PairingVerificationError));
+}
+
+#[test]
+fn test_prepare_public_inputs_matches_arkworks() {
+    let (vk, _, _) = square_proof(3);
+    let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_bytes).collect();
+    let input = Fr::from(9u64);
+
+    let expected = (vk.gamma_abc_g1[0] + vk.gamma_abc_g1[1] * input).into_affine();
+    assert_eq!(prepare_public_inputs(&vk_ic, &[fr_bytes(input)]).unwrap(), g1_bytes(&expected));
+}
+
+#[test]
+fn test_prepare_public_inputs_checks_count_and_field_size() {
+    let generator = g1_bytes(&G1Affine::generator());
+
+    assert_eq!(
+        prepare_public_inputs(&[generator], &[[0u8; 32]]),
+        Err(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs)
+    );
+    assert_eq!(
+        prepare_public_inputs(&[generator, generator], &[FR_MODULUS_BE]),
+        Err(Groth16Error::PublicInputGreaterThenFieldSize)
+    );
+    // Unchecked, r acts as zero and leaves vk_ic[0]
+    assert_eq!(
+        prepare_public_inputs_unchecked(&[generator, generator], &[FR_MODULUS_BE]),
+        Ok(generator)
+    );
+}
+
+#[test]
+fn test_group_operations() {
+    let generator = G1Affine::generator();
+    let g = g1_bytes(&generator);
+    let mut two = [0u8; 32];
+    two[31] = 2;
+
+    let doubled = g1_bytes(&(generator + generator).into_affine());
+    assert_eq!(g1_add(&g, &g).unwrap(), doubled);
+    assert_eq!(g1_mul(&g, &two).unwrap(), doubled);
+    assert_eq!(g1_add(&g, &[0u8; 64]).unwrap(), g);
+
+    // e(-P, Q) e(P, Q) = 1
+    let q = g2_bytes(&G2Affine::generator());
+    let input = [&g1_bytes(&(-generator))[..], &q[..], &g[..], &q[..]].concat();
+    assert!(pairing_check(&input).unwrap());
+    let input = [&g[..], &q[..], &g[..], &q[..]].concat();
+    assert!(!pairing_check(&input).unwrap());
+}
+
+#[test]
+fn test_verifier_new_checks_lengths() {
+    let vk = Box::new(Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: [0u8; 64],
+        vk_beta_g2: [0u8; 128],
+        vk_gamma_g2: [0u8; 128],
+        vk_delta_g2: [0u8; 128],
+    });
+
+    assert_eq!(
+        Groth16VerifierPrepared::new(&[0u8; 63], &[0u8; 128], &[0u8; 64], &[0u8; 64], vk.clone()),
+        Err(Groth16Error::InvalidG1Length)
+    );
+    assert_eq!(
+        Groth16VerifierPrepared::new(&[0u8; 64], &[0u8; 127], &[0u8; 64], &[0u8; 64], vk.clone()),
+        Err(Groth16Error::InvalidG2Length)
+    );
+    assert_eq!(
+        Groth16VerifierPrepared::new(&[0u8; 64], &[0u8; 128], &[0u8; 64], &[0u8; 32], vk.clone()),
+        Err(Groth16Error::InvalidPublicInputsLength)
+    );
+
+    let verifier = Groth16VerifierPrepared::new(&[0u8; 64], &[0u8; 128], &[0u8; 64], &[0u8; 64], vk).unwrap();
+    assert_eq!(borsh::to_vec(&verifier).unwrap().len(), 64 + 128 + 64 + 64 + 64 + 3 * 128);
+    assert_eq!(verifier.pairing_input().len(), 4 * bn254::PAIRING_ELEMENT_LEN);
+}
+
+#[test]
+fn test_error_display_and_codes() {
+    assert_eq!(Groth16Error::InvalidG1Length.to_string(), "InvalidG1Length");
+    for (i, e) in Groth16Error::ALL.iter().enumerate() {
+        assert_eq!(Groth16Error::from_code(i as u32), Some(*e));
+    }
+}
@@ -0,0 +1,481 @@
+//! Compute-unit profile of the proof-verify program.
+//!
+//! Loads the built `solana_zk_example.so` into `solana-program-test`, so the
+//! alt_bn128 syscalls are metered as they would be on a validator, and prints
+//! the CU consumed per instruction and per public input count. Each case has a
+//! budget that fails the test if verification gets more expensive.
+//!
+//! The tests need the SBF build, and skip with a message when it is missing:
+//!
+//! ```text
+//! cargo build-sbf --manifest-path proof-verify/Cargo.toml
+//! cargo test -p integration-tests --test compute_units -- --nocapture
+//! ```
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, ProvingKey, VerifyingKey};
+use ark_snark::SNARK;
+use ark_std::rand::thread_rng;
+use groth16_test_utils::{fr_bytes, g1_bytes, g2_bytes, prepared_inputs, InputsKeys, SquaresCircuit};
+use solana_program_test::ProgramTest;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signer,
+    transaction::Transaction,
+};
+use solana_zk_example::{
+    circuit_id_with_ic, find_root_registry_address, find_verification_state_address,
+    find_verifying_key_address, hash_public_inputs, member_context, BatchProof,
+    Groth16VerifierPrepared, Groth16VerifyingKeyPrepared, InputHashScheme, ProgramInstruction,
+    RegisteredVerifyingKey, RootRegistry, VerificationState, MAX_HASHED_INPUTS, ROOT_HISTORY_SIZE,
+};
+
+/// Where `cargo build-sbf` puts the program
+const SBF_OUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy");
+
+/// Whole-transaction limit, so an over-budget case still runs and reports its cost
+const MAX_COMPUTE_UNITS: u64 = 1_400_000;
+
+/// `VerifyProof` budgets, by public input count. The program prepares the
+/// raw inputs on-chain to check the prepared input, so like `VerifyBatch` each
+/// input adds a scalar multiplication. `VerifyProof` carries the verifying key
+/// and the raw inputs, so past a few inputs the instruction no longer fits in
+/// a transaction.
+const VERIFY_PROOF_MAX_UNITS: [(usize, u64); 2] = [(1, 165_000), (2, 175_000)];
+
+/// `VerifyBatch` budgets for one proof, by public input count. Each input adds
+/// a G1 scalar multiplication and addition on top of the four pairings.
+const VERIFY_BATCH_MAX_UNITS: [(usize, u64); 4] = [
+    (1, 160_000),
+    (2, 170_000),
+    (8, 220_000),
+    (16, 290_000),
+];
+
+/// `VerifyBatch` budgets for several single-input proofs, by batch size
+const VERIFY_BATCH_SIZE_MAX_UNITS: [(usize, u64); 3] = [(2, 200_000), (3, 240_000), (4, 280_000)];
+
+/// `VerifyProofWithBalance` budget for one input: `VerifyProof` plus a
+/// lamport balance read
+const VERIFY_PROOF_WITH_BALANCE_MAX_UNITS: u64 = 170_000;
+
+/// `VerifyHashedInputs` budgets for `MAX_HASHED_INPUTS` logical inputs, by
+/// scheme. The key has one input, so the cost past one-input verification is
+/// the hash: one SHA-256 syscall, or two chained Poseidon syscalls.
+const VERIFY_HASHED_INPUTS_MAX_UNITS: [(InputHashScheme, u64); 2] =
+    [(InputHashScheme::Sha256, 170_000), (InputHashScheme::Poseidon, 190_000)];
+
+/// `VerifyMembership` budget: two-input verification plus the root history lookup
+const VERIFY_MEMBERSHIP_MAX_UNITS: u64 = 180_000;
+
+struct Circuit {
+    pk: ProvingKey<Bn254>,
+    vk: VerifyingKey<Bn254>,
+    verifying_key: Groth16VerifyingKeyPrepared,
+    vk_ic: Vec<[u8; 64]>,
+}
+
+impl Circuit {
+    fn setup(nr_public_inputs: usize) -> Self {
+        let circuit = SquaresCircuit { xs: vec![None; nr_public_inputs] };
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(circuit, &mut thread_rng()).unwrap();
+        let verifying_key = Groth16VerifyingKeyPrepared {
+            vk_alpha_g1: g1_bytes(&vk.alpha_g1),
+            vk_beta_g2: g2_bytes(&vk.beta_g2),
+            vk_gamma_g2: g2_bytes(&vk.gamma_g2),
+            vk_delta_g2: g2_bytes(&vk.delta_g2),
+        };
+        let vk_ic = vk.gamma_abc_g1.iter().map(g1_bytes).collect();
+        Circuit { pk, vk, verifying_key, vk_ic }
+    }
+
+    fn nr_public_inputs(&self) -> usize {
+        self.vk_ic.len() - 1
+    }
+
+    /// A valid proof with `x_i = seed + i`
+    fn prove(&self, seed: u64) -> (BatchProof, Vec<Fr>) {
+        let xs: Vec<Fr> = (0..self.nr_public_inputs() as u64).map(|i| Fr::from(seed + i)).collect();
+        let circuit = SquaresCircuit { xs: xs.iter().copied().map(Some).collect() };
+        let proof = Groth16::<Bn254>::prove(&self.pk, circuit, &mut thread_rng()).unwrap();
+        let inputs: Vec<Fr> = xs.iter().map(|x| *x * *x).collect();
+        let batch_proof = BatchProof {
+            proof_a: g1_bytes(&-proof.a),
+            proof_b: g2_bytes(&proof.b),
+            proof_c: g1_bytes(&proof.c),
+            public_inputs: inputs.iter().copied().map(fr_bytes).collect(),
+        };
+        (batch_proof, inputs)
+    }
+
+    /// `IC_0 + sum x_i IC_i`, computed off-chain for `VerifyProof`
+    fn prepared_inputs(&self, inputs: &[Fr]) -> [u8; 64] {
+        prepared_inputs(&self.vk, inputs)
+    }
+}
+
+fn program_account<T: borsh::BorshSerialize>(program_id: &Pubkey, value: &T, space: usize) -> Account {
+    let mut data = borsh::to_vec(value).unwrap();
+    data.resize(space, 0);
+    Account {
+        lamports: Rent::default().minimum_balance(space),
+        data,
+        owner: *program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Whether the SBF build is there to load. Without it the tests print how to
+/// build it and pass, so the rest of the workspace can run without the SBF
+/// toolchain.
+fn program_built() -> bool {
+    let out_dir = std::env::var("SBF_OUT_DIR").unwrap_or_else(|_| SBF_OUT_DIR.to_string());
+    let path = std::path::Path::new(&out_dir).join("solana_zk_example.so");
+    if path.exists() {
+        std::env::set_var("SBF_OUT_DIR", out_dir);
+        return true;
+    }
+    println!(
+        "skipping: {} not found, run `cargo build-sbf --manifest-path proof-verify/Cargo.toml` first",
+        path.display()
+    );
+    false
+}
+
+/// A bank running the BPF build, with a registered key and a verification
+/// state already in place. Pre-populating the accounts keeps large keys out
+/// of the transaction: a 16-input key doesn't fit in a `RegisterVerifyingKey`.
+struct Bank {
+    program_test: ProgramTest,
+    program_id: Pubkey,
+    authority: Pubkey,
+    key_pda: Pubkey,
+    state_pda: Pubkey,
+}
+
+impl Bank {
+    fn new(verifying_key: &Groth16VerifyingKeyPrepared, vk_ic: &[[u8; 64]], state_circuit_id: [u8; 32]) -> Self {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("solana_zk_example", program_id, None);
+        program_test.prefer_bpf(true);
+        program_test.set_compute_max_units(MAX_COMPUTE_UNITS);
+
+        let key_circuit_id = circuit_id_with_ic(verifying_key, vk_ic);
+        let (key_pda, key_bump) = find_verifying_key_address(&program_id, &authority, &key_circuit_id);
+        let registered = RegisteredVerifyingKey {
+            is_initialized: true,
+            authority,
+            circuit_id: key_circuit_id,
+            bump: key_bump,
+            verifying_key: verifying_key.clone(),
+            vk_ic: vk_ic.to_vec(),
+        };
+        let key_space = RegisteredVerifyingKey::space(vk_ic.len() - 1);
+        program_test.add_account(key_pda, program_account(&program_id, &registered, key_space));
+
+        let (state_pda, state_bump) = find_verification_state_address(&program_id, &authority, &state_circuit_id);
+        let state = VerificationState::new(authority, state_circuit_id, state_bump);
+        program_test.add_account(state_pda, program_account(&program_id, &state, VerificationState::LEN));
+
+        Bank {
+            program_test,
+            program_id,
+            authority,
+            key_pda,
+            state_pda,
+        }
+    }
+
+    /// Runs `instruction(verifier)` and returns the compute units it consumed
+    async fn measure(self, instruction: impl FnOnce(&Pubkey) -> Instruction) -> u64 {
+        let (banks_client, payer, recent_blockhash) = self.program_test.start().await;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction(&payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction_with_metadata(transaction).await.unwrap();
+        let metadata = result.metadata.unwrap();
+        assert!(result.result.is_ok(), "{:?}\n{:#?}", result.result, metadata.log_messages);
+        metadata.compute_units_consumed
+    }
+}
+
+/// Runs `instruction(program_id, key, state, verifier)` against the BPF build
+/// with `circuit`'s key and a state for `state_circuit_id` in place, and
+/// returns the compute units it consumed
+async fn measure(
+    circuit: &Circuit,
+    state_circuit_id: [u8; 32],
+    instruction: impl FnOnce(&Pubkey, &Pubkey, &Pubkey, &Pubkey) -> Instruction,
+) -> u64 {
+    let bank = Bank::new(&circuit.verifying_key, &circuit.vk_ic, state_circuit_id);
+    let (program_id, key, state) = (bank.program_id, bank.key_pda, bank.state_pda);
+    bank.measure(|verifier| instruction(&program_id, &key, &state, verifier)).await
+}
+
+async fn measure_verify_proof(circuit: &Circuit) -> u64 {
+    let (proof, inputs) = circuit.prove(3);
+    let verifier = Groth16VerifierPrepared {
+        proof_a: proof.proof_a,
+        proof_b: proof.proof_b,
+        proof_c: proof.proof_c,
+        prepared_public_inputs: circuit.prepared_inputs(&inputs),
+        verifying_key: Box::new(circuit.verifying_key.clone()),
+    };
+    let data = ProgramInstruction::VerifyProof {
+        proof_data: verifier,
+        public_inputs: proof.public_inputs,
+    }
+    .pack();
+
+    let key_circuit_id = circuit_id_with_ic(&circuit.verifying_key, &circuit.vk_ic);
+    measure(circuit, key_circuit_id, |program_id, key, state, verifier| {
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(*key, false),
+                AccountMeta::new(*state, false),
+                AccountMeta::new_readonly(*verifier, true),
+            ],
+        )
+    })
+    .await
+}
+
+async fn measure_verify_batch(circuit: &Circuit, batch_size: u64) -> u64 {
+    let proofs: Vec<BatchProof> = (0..batch_size).map(|i| circuit.prove(3 + 100 * i).0).collect();
+    let data = ProgramInstruction::VerifyBatch { proofs }.pack();
+
+    let key_circuit_id = circuit_id_with_ic(&circuit.verifying_key, &circuit.vk_ic);
+    measure(circuit, key_circuit_id, |program_id, key, state, verifier| {
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(*key, false),
+                AccountMeta::new(*state, false),
+                AccountMeta::new_readonly(*verifier, true),
+            ],
+        )
+    })
+    .await
+}
+
+async fn measure_verify_proof_with_balance(circuit: &Circuit) -> u64 {
+    let (proof, inputs) = circuit.prove(3);
+    let proof_data = Groth16VerifierPrepared {
+        proof_a: proof.proof_a,
+        proof_b: proof.proof_b,
+        proof_c: proof.proof_c,
+        prepared_public_inputs: circuit.prepared_inputs(&inputs),
+        verifying_key: Box::new(circuit.verifying_key.clone()),
+    };
+
+    let key_circuit_id = circuit_id_with_ic(&circuit.verifying_key, &circuit.vk_ic);
+    measure(circuit, key_circuit_id, |program_id, key, state, verifier| {
+        // The verifier's own lamports are the balance checked
+        let data = ProgramInstruction::VerifyProofWithBalance {
+            proof_data,
+            public_inputs: proof.public_inputs,
+            required_balance: 1,
+            account_to_check: *verifier,
+        }
+        .pack();
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(*key, false),
+                AccountMeta::new(*state, false),
+                AccountMeta::new_readonly(*verifier, true),
+            ],
+        )
+    })
+    .await
+}
+
+/// A bank with `keys`' verifying key registered and a state for it
+fn inputs_bank(keys: &InputsKeys) -> Bank {
+    let vk = keys.verifying_key();
+    let verifying_key = Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamma_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+    };
+    let key_circuit_id = circuit_id_with_ic(&verifying_key, &vk.ic);
+    Bank::new(&verifying_key, &vk.ic, key_circuit_id)
+}
+
+async fn measure_verify_hashed_inputs(keys: &InputsKeys, scheme: InputHashScheme) -> u64 {
+    let logical_inputs: Vec<[u8; 32]> =
+        (1..=MAX_HASHED_INPUTS as u64).map(|i| fr_bytes(Fr::from(i * 1_000_003))).collect();
+    let proof = keys.prove(&[hash_public_inputs(scheme, &logical_inputs).unwrap()]);
+    let data = ProgramInstruction::VerifyHashedInputs {
+        proof: BatchProof {
+            proof_a: proof.a,
+            proof_b: proof.b,
+            proof_c: proof.c,
+            public_inputs: logical_inputs,
+        },
+        scheme,
+    }
+    .pack();
+
+    let bank = inputs_bank(keys);
+    let (program_id, key, state) = (bank.program_id, bank.key_pda, bank.state_pda);
+    bank.measure(|verifier| {
+        Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(key, false),
+                AccountMeta::new(state, false),
+                AccountMeta::new_readonly(*verifier, true),
+            ],
+        )
+    })
+    .await
+}
+
+async fn measure_verify_membership(keys: &InputsKeys) -> u64 {
+    let mut bank = inputs_bank(keys);
+
+    // A registry of the state's authority, with the root proven against its
+    // most recent entry so the history lookup scans every slot
+    let root = fr_bytes(Fr::from(7u64));
+    let registry_id = [1u8; 32];
+    let (registry_pda, bump) = find_root_registry_address(&bank.program_id, &bank.authority, &registry_id);
+    let mut registry = RootRegistry::new(bank.authority, registry_id, bump, 20, fr_bytes(Fr::from(1u64)));
+    for i in 2..ROOT_HISTORY_SIZE as u64 {
+        registry.history.push(fr_bytes(Fr::from(i)));
+    }
+    registry.history.push(root);
+    bank.program_test
+        .add_account(registry_pda, program_account(&bank.program_id, &registry, RootRegistry::LEN));
+
+    let (program_id, key, state) = (bank.program_id, bank.key_pda, bank.state_pda);
+    bank.measure(|verifier| {
+        let public_inputs = vec![root, member_context(verifier)];
+        let proof = keys.prove(&public_inputs);
+        let data = ProgramInstruction::VerifyMembership {
+            proof: BatchProof {
+                proof_a: proof.a,
+                proof_b: proof.b,
+                proof_c: proof.c,
+                public_inputs,
+            },
+        }
+        .pack();
+        Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(key, false),
+                AccountMeta::new_readonly(registry_pda, false),
+                AccountMeta::new(state, false),
+                AccountMeta::new_readonly(*verifier, true),
+            ],
+        )
+    })
+    .await
+}
+
+fn check_budget(label: &str, units: u64, budget: u64) -> bool {
+    println!("{:<40} {:>9} CU  (budget {:>9})", label, units, budget);
+    units <= budget
+}
+
+#[tokio::test]
+async fn test_verification_compute_units_by_public_inputs() {
+    if !program_built() {
+        return;
+    }
+    let mut within_budget = true;
+    for (nr_public_inputs, budget) in VERIFY_BATCH_MAX_UNITS {
+        let circuit = Circuit::setup(nr_public_inputs);
+
+        if let Some((_, proof_budget)) = VERIFY_PROOF_MAX_UNITS.iter().find(|(n, _)| *n == nr_public_inputs) {
+            let units = measure_verify_proof(&circuit).await;
+            within_budget &= check_budget(
+                &format!("VerifyProof, {} inputs", nr_public_inputs),
+                units,
+                *proof_budget,
+            );
+        }
+
+        let units = measure_verify_batch(&circuit, 1).await;
+        within_budget &= check_budget(
+            &format!("VerifyBatch, 1 proof, {} inputs", nr_public_inputs),
+            units,
+            budget,
+        );
+    }
+    assert!(within_budget, "verification exceeded its compute budget, see the table above");
+}
+
+#[tokio::test]
+async fn test_verify_batch_compute_units_by_batch_size() {
+    if !program_built() {
+        return;
+    }
+    let circuit = Circuit::setup(1);
+
+    let mut within_budget = true;
+    for (batch_size, budget) in VERIFY_BATCH_SIZE_MAX_UNITS {
+        let units = measure_verify_batch(&circuit, batch_size as u64).await;
+        within_budget &= check_budget(&format!("VerifyBatch, {} proofs, 1 input", batch_size), units, budget);
+    }
+    assert!(within_budget, "batch verification exceeded its compute budget, see the table above");
+}
+
+#[tokio::test]
+async fn test_verify_proof_with_balance_compute_units() {
+    if !program_built() {
+        return;
+    }
+    let units = measure_verify_proof_with_balance(&Circuit::setup(1)).await;
+    assert!(
+        check_budget("VerifyProofWithBalance, 1 input", units, VERIFY_PROOF_WITH_BALANCE_MAX_UNITS),
+        "balance verification exceeded its compute budget"
+    );
+}
+
+#[tokio::test]
+async fn test_verify_hashed_inputs_compute_units() {
+    if !program_built() {
+        return;
+    }
+    let keys = InputsKeys::setup(1);
+
+    let mut within_budget = true;
+    for (scheme, budget) in VERIFY_HASHED_INPUTS_MAX_UNITS {
+        let units = measure_verify_hashed_inputs(&keys, scheme).await;
+        within_budget &= check_budget(
+            &format!("VerifyHashedInputs, {:?}, {} inputs", scheme, MAX_HASHED_INPUTS),
+            units,
+            budget,
+        );
+    }
+    assert!(within_budget, "hashed input verification exceeded its compute budget, see the table above");
+}
+
+#[tokio::test]
+async fn test_verify_membership_compute_units() {
+    if !program_built() {
+        return;
+    }
+    let units = measure_verify_membership(&InputsKeys::setup(2)).await;
+    assert!(
+        check_budget("VerifyMembership", units, VERIFY_MEMBERSHIP_MAX_UNITS),
+        "membership verification exceeded its compute budget"
+    );
+}
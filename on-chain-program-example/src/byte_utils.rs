@@ -1,5 +1,5 @@
 use ark_ff::PrimeField;
-use ark_serialize::{SerializationError};
+use ark_serialize::{CanonicalSerialize, SerializationError};
 
 // Helper function to convert a field element to bytes
 pub fn field_to_bytes<F: PrimeField>(field: F) -> [u8; 32] {
@@ -45,28 +45,20 @@ pub fn convert_endianness<const INPUT_SIZE: usize, const OUTPUT_SIZE: usize>(
     Ok(output)
 }
 
-// Stub implementations for alt_bn128 functions (client-side only)
-// These would normally be provided by Solana's runtime
-
-pub fn alt_bn128_pairing(_input: &[u8]) -> Result<[u8; 32], u32> {
-    // This is a stub implementation
-    // In a real implementation, this would call the actual pairing function
-    // For now, return a successful result with the expected output format
-    let mut result = [0u8; 32];
-    result[31] = 1; // Set the last byte to 1 to indicate success
-    Ok(result)
+// arkworks serializes coordinates little-endian, the alt_bn128 syscalls want big-endian
+pub fn g1_to_be_bytes(point: &ark_bn254::G1Affine) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    point.serialize_uncompressed(&mut bytes[..]).unwrap();
+    bytes[..32].reverse();
+    bytes[32..].reverse();
+    bytes
 }
 
-pub fn alt_bn128_multiplication(input: &[u8]) -> Result<Vec<u8>, u32> {
-    // This is a stub implementation
-    // In a real implementation, this would perform elliptic curve multiplication
-    // For now, return the input as-is
-    Ok(input.to_vec())
+// Same for G2, which gives the `c1 || c0` order the syscalls expect
+pub fn g2_to_be_bytes(point: &ark_bn254::G2Affine) -> [u8; 128] {
+    let mut bytes = [0u8; 128];
+    point.serialize_uncompressed(&mut bytes[..]).unwrap();
+    bytes[..64].reverse();
+    bytes[64..].reverse();
+    bytes
 }
-
-pub fn alt_bn128_addition(input: &[u8]) -> Result<Vec<u8>, u32> {
-    // This is a stub implementation
-    // In a real implementation, this would perform elliptic curve addition
-    // For now, return the input as-is
-    Ok(input.to_vec())
-}
\ No newline at end of file
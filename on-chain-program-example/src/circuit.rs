@@ -1,4 +1,9 @@
 use crate::byte_utils::field_to_bytes;
+use crate::hashed_inputs::PublicInputsCircuit;
+use crate::merkle::{member_context, member_leaf, merkle_root_gadget, MerklePath, MerkleTree};
+use crate::poseidon::poseidon_gadget;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use solana_program::pubkey::Pubkey;
 use ark_bn254::Fr;
 use ark_relations::lc;
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
@@ -16,6 +21,10 @@ pub enum CircuitError {
     MissingAssignment,
     #[error("Invalid value range")]
     InvalidRange,
+    #[error("Failed to hash public inputs")]
+    InputHashFailed,
+    #[error("Leaf is not in the Merkle tree")]
+    NotInTree,
 }
 
 #[derive(Clone)]
@@ -76,6 +85,29 @@ impl ConstraintSynthesizer<Fr> for ExampleCircuit {
         let x_var = cs.new_input_variable(|| Ok(x))?;
         let y_var = cs.new_input_variable(|| Ok(y))?;
 
+        self.generate_constraints_with_inputs(cs, &[x_var, y_var])
+    }
+}
+
+/// X and Y can also be hashed into a single public input, see `HashedInputsCircuit`
+impl PublicInputsCircuit for ExampleCircuit {
+    fn nr_public_inputs(&self) -> usize {
+        2
+    }
+
+    fn public_values(&self) -> Option<Vec<Fr>> {
+        Some(vec![self.prover_value?, self.verifier_value?])
+    }
+
+    fn generate_constraints_with_inputs(
+        self,
+        cs: ConstraintSystemRef<Fr>,
+        inputs: &[Variable],
+    ) -> Result<(), SynthesisError> {
+        let x = self.prover_value.ok_or(SynthesisError::AssignmentMissing)?;
+        let y = self.verifier_value.ok_or(SynthesisError::AssignmentMissing)?;
+        let (x_var, y_var) = (inputs[0], inputs[1]);
+
         // Compute D = X - Y by introducing neg_y = -Y first
         let neg_one = Fr::from(-1i32);
         let neg_y_var = cs.new_witness_variable(|| Ok(neg_one * y))?;
@@ -422,3 +454,149 @@ mod token_circuit_tests {
         assert!(result, "Proof verification should succeed");
     }
 }
+
+// ============================================================================
+// MerkleMembershipCircuit - Anonymous membership in a Poseidon Merkle tree
+// ============================================================================
+
+/// Merkle Membership Circuit proves that the holder of a secret is a member
+/// of a set, for example KYC'd takers, without revealing which member
+///
+/// Public Inputs: root, context (in that order, as proof-verify's
+/// `VerifyMembership` expects them)
+/// Witness Structure: Private(secret, leaf index bits, siblings)
+///
+/// Implementation Details:
+/// - The leaf is `member_leaf(secret)`, Poseidon over the secret
+/// - Each level hashes `[node, sibling]` or `[sibling, node]`, picked by the
+///   index bit, with `poseidon_gadget`, up to `depth` levels
+/// - `context` is `member_context(member)`. It is squared so it appears in a
+///   constraint; an unconstrained public input could be swapped in a proof
+#[derive(Clone)]
+pub struct MerkleMembershipCircuit {
+    pub depth: usize,
+    pub secret: Option<Fr>,
+    pub path: Option<MerklePath>,
+    pub root: Option<Fr>,
+    pub context: Option<Fr>,
+}
+
+impl MerkleMembershipCircuit {
+    /// Membership of `member_leaf(secret)` at `index` in `tree`, bound to `member`
+    pub fn new(tree: &MerkleTree, index: u64, secret: Fr, member: &Pubkey) -> Result<Self, CircuitError> {
+        let path = tree.path(index)?;
+        if path.compute_root(member_leaf(secret)) != tree.root() {
+            return Err(CircuitError::NotInTree);
+        }
+
+        Ok(MerkleMembershipCircuit {
+            depth: tree.depth(),
+            secret: Some(secret),
+            path: Some(path),
+            root: Some(tree.root()),
+            context: Some(member_context(member)),
+        })
+    }
+
+    pub fn public_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        match (self.root, self.context) {
+            (Some(root), Some(context)) => Ok(vec![field_to_bytes(root), field_to_bytes(context)]),
+            _ => Err(CircuitError::MissingAssignment),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for MerkleMembershipCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate the public inputs
+        let root = FpVar::new_input(cs.clone(), || self.root.ok_or(SynthesisError::AssignmentMissing))?;
+        let context = FpVar::new_input(cs.clone(), || self.context.ok_or(SynthesisError::AssignmentMissing))?;
+        context.square()?;
+
+        // Allocate the secret and hash it into the leaf, then walk up the tree
+        let secret = FpVar::new_witness(cs.clone(), || self.secret.ok_or(SynthesisError::AssignmentMissing))?;
+        let leaf = poseidon_gadget(&[secret])?;
+        let node = merkle_root_gadget(cs, leaf, self.path.as_ref(), self.depth)?;
+
+        node.enforce_equal(&root)
+    }
+}
+
+#[cfg(test)]
+mod membership_circuit_tests {
+    use super::*;
+    use crate::byte_utils::{g1_to_be_bytes, g2_to_be_bytes};
+    use crate::hashed_inputs::fr_to_be_bytes;
+    use crate::prove::{generate_proof_package, setup};
+    use ark_relations::r1cs::ConstraintSystem;
+    use groth16_verifier::{prepare_public_inputs, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
+
+    const DEPTH: usize = 4;
+
+    fn allowlist() -> (MerkleTree, Vec<Fr>) {
+        let secrets: Vec<Fr> = (1..=6u64).map(|i| Fr::from(i * 7919)).collect();
+        let leaves: Vec<Fr> = secrets.iter().copied().map(member_leaf).collect();
+        (MerkleTree::from_leaves(DEPTH, &leaves).unwrap(), secrets)
+    }
+
+    #[test]
+    fn test_membership_constraints() {
+        let (tree, secrets) = allowlist();
+        let member = Pubkey::new_unique();
+
+        for (index, secret) in secrets.iter().enumerate() {
+            let circuit = MerkleMembershipCircuit::new(&tree, index as u64, *secret, &member).unwrap();
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert!(cs.is_satisfied().unwrap());
+        }
+
+        // Someone else's secret doesn't open a leaf
+        assert!(MerkleMembershipCircuit::new(&tree, 0, secrets[1], &member).is_err());
+        let mut circuit = MerkleMembershipCircuit::new(&tree, 0, secrets[0], &member).unwrap();
+        circuit.secret = Some(secrets[1]);
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_membership_proof_verifies_like_on_chain() {
+        let (tree, secrets) = allowlist();
+        let member = Pubkey::new_unique();
+        let circuit = MerkleMembershipCircuit::new(&tree, 3, secrets[3], &member).unwrap();
+
+        let (pk, vk) = setup(false, circuit.clone());
+        let public_inputs = circuit.public_inputs().unwrap();
+        let (_, _, package) = generate_proof_package(&pk, &vk, circuit, &public_inputs).unwrap();
+
+        // What VerifyMembership does: the registry's root and the verifier's
+        // context, as big-endian inputs against the registered vk_ic
+        let on_chain_inputs = |member: &Pubkey| {
+            vec![tree.root_bytes(), solana_zk_example::member_context(member)]
+        };
+        assert_eq!(on_chain_inputs(&member)[1], fr_to_be_bytes(member_context(&member)));
+
+        let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_to_be_bytes).collect();
+        let verifier = Groth16VerifierPrepared {
+            proof_a: g1_to_be_bytes(&-package.proof.a),
+            proof_b: g2_to_be_bytes(&package.proof.b),
+            proof_c: g1_to_be_bytes(&package.proof.c),
+            prepared_public_inputs: prepare_public_inputs(&vk_ic, &on_chain_inputs(&member)).unwrap(),
+            verifying_key: Box::new(Groth16VerifyingKeyPrepared {
+                vk_alpha_g1: g1_to_be_bytes(&vk.alpha_g1),
+                vk_beta_g2: g2_to_be_bytes(&vk.beta_g2),
+                vk_gamma_g2: g2_to_be_bytes(&vk.gamma_g2),
+                vk_delta_g2: g2_to_be_bytes(&vk.delta_g2),
+            }),
+        };
+        assert_eq!(verifier.verify(), Ok(true));
+
+        // Replayed by another verifier, the context no longer matches
+        let replayed = Groth16VerifierPrepared {
+            prepared_public_inputs: prepare_public_inputs(&vk_ic, &on_chain_inputs(&Pubkey::new_unique())).unwrap(),
+            ..verifier
+        };
+        assert_eq!(replayed.verify(), Ok(false));
+    }
+}
@@ -0,0 +1,401 @@
+use crate::byte_utils::field_to_bytes;
+use crate::circuit::CircuitError;
+use crate::hashed_inputs::fr_to_be_bytes;
+use crate::poseidon::{poseidon_gadget, poseidon_hash};
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+
+/// Opening of an escrow order commitment: the fields of the escrow's
+/// `OrderDetails` and the nonce, a 32-byte big-endian field element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderOpening {
+    pub token_amount: u64,
+    pub min_receive_amount: u64,
+    pub preferred_venue: u8,
+    pub max_slippage: u16,
+    pub nonce: [u8; 32],
+}
+
+impl OrderOpening {
+    /// The Poseidon inputs, in the order of `OrderDetails::poseidon_inputs`
+    pub fn field_elements(&self) -> Result<[Fr; 5], CircuitError> {
+        let nonce = Fr::from_be_bytes_mod_order(&self.nonce);
+        if fr_to_be_bytes(nonce) != self.nonce {
+            return Err(CircuitError::InvalidRange);
+        }
+
+        Ok([
+            Fr::from(self.token_amount),
+            Fr::from(self.min_receive_amount),
+            Fr::from(self.preferred_venue as u64),
+            Fr::from(self.max_slippage as u64),
+            nonce,
+        ])
+    }
+
+    pub fn commitment(&self) -> Result<Fr, CircuitError> {
+        poseidon_hash(&self.field_elements()?).map_err(|_| CircuitError::InputHashFailed)
+    }
+
+    /// The commitment as the escrow stores it in `commitment_hash` for
+    /// `CommitmentScheme::Poseidon`
+    pub fn commitment_hash(&self) -> Result<[u8; 32], CircuitError> {
+        Ok(fr_to_be_bytes(self.commitment()?))
+    }
+}
+
+/// The order fields allocated as witnesses, each range-checked to its width
+/// in `OrderDetails`, so a satisfying opening always decodes to a valid order
+pub struct OrderVars {
+    pub token_amount: FpVar<Fr>,
+    pub min_receive_amount: FpVar<Fr>,
+    pub preferred_venue: FpVar<Fr>,
+    pub max_slippage: FpVar<Fr>,
+    pub nonce: FpVar<Fr>,
+}
+
+impl OrderVars {
+    pub fn new_witness(cs: ConstraintSystemRef<Fr>, opening: Option<&OrderOpening>) -> Result<Self, SynthesisError> {
+        let nonce = opening
+            .map(|opening| opening.field_elements().map(|fields| fields[4]))
+            .transpose()
+            .map_err(|_| SynthesisError::Unsatisfiable)?;
+
+        Ok(OrderVars {
+            token_amount: uint_witness(cs.clone(), opening.map(|o| o.token_amount), 64)?,
+            min_receive_amount: uint_witness(cs.clone(), opening.map(|o| o.min_receive_amount), 64)?,
+            preferred_venue: uint_witness(cs.clone(), opening.map(|o| o.preferred_venue as u64), 8)?,
+            max_slippage: uint_witness(cs.clone(), opening.map(|o| o.max_slippage as u64), 16)?,
+            nonce: FpVar::new_witness(cs, || nonce.ok_or(SynthesisError::AssignmentMissing))?,
+        })
+    }
+
+    /// Poseidon commitment to the order, as `OrderOpening::commitment`
+    pub fn commitment(&self) -> Result<FpVar<Fr>, SynthesisError> {
+        poseidon_gadget(&[
+            self.token_amount.clone(),
+            self.min_receive_amount.clone(),
+            self.preferred_venue.clone(),
+            self.max_slippage.clone(),
+            self.nonce.clone(),
+        ])
+    }
+}
+
+/// Allocates `value` as `bits` boolean witnesses and returns their sum, so the
+/// result is below `2^bits`
+pub fn uint_witness(cs: ConstraintSystemRef<Fr>, value: Option<u64>, bits: usize) -> Result<FpVar<Fr>, SynthesisError> {
+    let bits = (0..bits)
+        .map(|i| {
+            Boolean::new_witness(cs.clone(), || {
+                value.map(|v| (v >> i) & 1 == 1).ok_or(SynthesisError::AssignmentMissing)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+/// CommitmentOpeningCircuit proves knowledge of an `OrderOpening` whose
+/// Poseidon hash is the public commitment.
+///
+/// Public input: the commitment, as stored by the escrow's
+/// `initialize_enhanced` with `CommitmentScheme::Poseidon`.
+/// Witness: the order fields and the nonce.
+#[derive(Clone)]
+pub struct CommitmentOpeningCircuit {
+    pub opening: Option<OrderOpening>,
+    pub commitment: Option<Fr>,
+}
+
+impl CommitmentOpeningCircuit {
+    pub fn new(opening: OrderOpening) -> Result<Self, CircuitError> {
+        let commitment = opening.commitment()?;
+        Ok(CommitmentOpeningCircuit {
+            opening: Some(opening),
+            commitment: Some(commitment),
+        })
+    }
+
+    pub fn public_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        match self.commitment {
+            Some(commitment) => Ok(vec![field_to_bytes(commitment)]),
+            None => Err(CircuitError::MissingAssignment),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for CommitmentOpeningCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate the public commitment
+        let commitment = FpVar::new_input(cs.clone(), || self.commitment.ok_or(SynthesisError::AssignmentMissing))?;
+
+        // Allocate the opening and constrain its hash
+        let order = OrderVars::new_witness(cs, self.opening.as_ref())?;
+        order.commitment()?.enforce_equal(&commitment)
+    }
+}
+
+/// OrderPropertiesCircuit proves that the order behind a Poseidon commitment
+/// covers the taker amount, accepts it as payment and caps its slippage,
+/// without opening it.
+///
+/// Public inputs, in the order of the escrow's `order_proof_public_inputs`:
+/// the commitment, `taker_amount`, `slippage_cap` and `deposit_amount`, what
+/// the escrow's vault holds.
+/// Witness: the order fields and the nonce.
+/// Constraints: the opening hashes to the commitment,
+/// `token_amount >= taker_amount`, `min_receive_amount <= taker_amount`,
+/// `max_slippage <= slippage_cap` and `token_amount == deposit_amount`.
+#[derive(Clone)]
+pub struct OrderPropertiesCircuit {
+    pub opening: Option<OrderOpening>,
+    pub commitment: Option<Fr>,
+    pub taker_amount: Option<u64>,
+    pub slippage_cap: Option<u16>,
+    pub deposit_amount: Option<u64>,
+}
+
+impl OrderPropertiesCircuit {
+    /// Properties of `opening`, whose whole `token_amount` was deposited
+    pub fn new(opening: OrderOpening, taker_amount: u64, slippage_cap: u16) -> Result<Self, CircuitError> {
+        let commitment = opening.commitment()?;
+        Ok(OrderPropertiesCircuit {
+            deposit_amount: Some(opening.token_amount),
+            opening: Some(opening),
+            commitment: Some(commitment),
+            taker_amount: Some(taker_amount),
+            slippage_cap: Some(slippage_cap),
+        })
+    }
+
+    pub fn public_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        match (self.commitment, self.taker_amount, self.slippage_cap, self.deposit_amount) {
+            (Some(commitment), Some(taker_amount), Some(slippage_cap), Some(deposit_amount)) => Ok(vec![
+                field_to_bytes(commitment),
+                field_to_bytes(Fr::from(taker_amount)),
+                field_to_bytes(Fr::from(slippage_cap as u64)),
+                field_to_bytes(Fr::from(deposit_amount)),
+            ]),
+            _ => Err(CircuitError::MissingAssignment),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for OrderPropertiesCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate the public inputs
+        let commitment = FpVar::new_input(cs.clone(), || self.commitment.ok_or(SynthesisError::AssignmentMissing))?;
+        let taker_amount = FpVar::new_input(cs.clone(), || {
+            self.taker_amount.map(Fr::from).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let slippage_cap = FpVar::new_input(cs.clone(), || {
+            self.slippage_cap.map(|cap| Fr::from(cap as u64)).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let deposit_amount = FpVar::new_input(cs.clone(), || {
+            self.deposit_amount.map(Fr::from).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        // Allocate the opening and constrain its hash
+        let order = OrderVars::new_witness(cs.clone(), self.opening.as_ref())?;
+        order.commitment()?.enforce_equal(&commitment)?;
+
+        // The vault holds the whole order
+        order.token_amount.enforce_equal(&deposit_amount)?;
+
+        // token_amount - taker_amount fits in 64 bits, so it isn't negative.
+        // An unsatisfiable order wraps, and the difference doesn't match.
+        let amount_margin = uint_witness(
+            cs.clone(),
+            self.opening
+                .as_ref()
+                .zip(self.taker_amount)
+                .map(|(opening, taker_amount)| opening.token_amount.wrapping_sub(taker_amount)),
+            64,
+        )?;
+        amount_margin.enforce_equal(&(&order.token_amount - &taker_amount))?;
+
+        // Same for taker_amount - min_receive_amount, so the hidden minimum
+        // never asks for more than the taker pays
+        let receive_margin = uint_witness(
+            cs.clone(),
+            self.opening
+                .as_ref()
+                .zip(self.taker_amount)
+                .map(|(opening, taker_amount)| taker_amount.wrapping_sub(opening.min_receive_amount)),
+            64,
+        )?;
+        receive_margin.enforce_equal(&(&taker_amount - &order.min_receive_amount))?;
+
+        // Same for slippage_cap - max_slippage in 16 bits
+        let slippage_margin = uint_witness(
+            cs,
+            self.opening
+                .as_ref()
+                .zip(self.slippage_cap)
+                .map(|(opening, cap)| cap.wrapping_sub(opening.max_slippage) as u64),
+            16,
+        )?;
+        slippage_margin.enforce_equal(&(&slippage_cap - &order.max_slippage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::byte_utils::{g1_to_be_bytes, g2_to_be_bytes};
+    use crate::prove::{generate_proof_package, setup};
+    use crate::verify::verify_proof_package;
+    use ark_relations::r1cs::ConstraintSystem;
+    use groth16_verifier::{prepare_public_inputs, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
+    use solana_zk_example::{hash_public_inputs, InputHashScheme};
+
+    fn sample_opening() -> OrderOpening {
+        let mut nonce = [0x5a; 32];
+        nonce[0] = 0;
+        OrderOpening {
+            token_amount: 2_000_000_000,
+            min_receive_amount: 1_000_000_000,
+            preferred_venue: 3,
+            max_slippage: 50,
+            nonce,
+        }
+    }
+
+    #[test]
+    fn test_commitment_matches_poseidon_syscall() {
+        let opening = sample_opening();
+        let inputs: Vec<[u8; 32]> = opening.field_elements().unwrap().iter().copied().map(fr_to_be_bytes).collect();
+
+        // Five inputs fit in one Poseidon call, like the escrow's reveal
+        let expected = hash_public_inputs(InputHashScheme::Poseidon, &inputs).unwrap();
+        assert_eq!(opening.commitment_hash().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_non_canonical_nonce_is_rejected() {
+        let mut opening = sample_opening();
+        opening.nonce = [0xff; 32];
+        assert!(opening.field_elements().is_err());
+        assert!(CommitmentOpeningCircuit::new(opening).is_err());
+    }
+
+    #[test]
+    fn test_commitment_opening_constraints() {
+        let circuit = CommitmentOpeningCircuit::new(sample_opening()).unwrap();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // A different order doesn't open the same commitment
+        let mut other = sample_opening();
+        other.max_slippage += 1;
+        let wrong = CommitmentOpeningCircuit {
+            opening: Some(other),
+            commitment: circuit.commitment,
+        };
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        wrong.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_commitment_opening_proof() {
+        let circuit = CommitmentOpeningCircuit::new(sample_opening()).unwrap();
+        let public_inputs = circuit.public_inputs().unwrap();
+
+        let (pk, vk) = setup(false, circuit.clone());
+        let (_, _, proof_package) = generate_proof_package(&pk, &vk, circuit, &public_inputs).unwrap();
+        assert!(verify_proof_package(&proof_package).unwrap());
+    }
+
+    fn is_satisfied(circuit: OrderPropertiesCircuit) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_order_properties_constraints() {
+        let opening = sample_opening();
+
+        // Bounds are inclusive
+        assert!(is_satisfied(OrderPropertiesCircuit::new(opening.clone(), 1_500_000_000, 100).unwrap()));
+        assert!(is_satisfied(
+            OrderPropertiesCircuit::new(opening.clone(), opening.token_amount, opening.max_slippage).unwrap()
+        ));
+
+        assert!(is_satisfied(
+            OrderPropertiesCircuit::new(opening.clone(), opening.min_receive_amount, 100).unwrap()
+        ));
+
+        // Too large a taker amount, one below the order's minimum, or too tight
+        // a slippage cap
+        assert!(!is_satisfied(
+            OrderPropertiesCircuit::new(opening.clone(), opening.token_amount + 1, 100).unwrap()
+        ));
+        assert!(!is_satisfied(
+            OrderPropertiesCircuit::new(opening.clone(), opening.min_receive_amount - 1, 100).unwrap()
+        ));
+        assert!(!is_satisfied(
+            OrderPropertiesCircuit::new(opening.clone(), 1_500_000_000, opening.max_slippage - 1).unwrap()
+        ));
+
+        // The properties hold, but for a different commitment
+        let mut circuit = OrderPropertiesCircuit::new(opening.clone(), 1_500_000_000, 100).unwrap();
+        circuit.commitment = circuit.commitment.map(|c| c + Fr::from(1u64));
+        assert!(!is_satisfied(circuit));
+
+        // Or with less in the vault than the order
+        let mut circuit = OrderPropertiesCircuit::new(opening.clone(), 1_500_000_000, 100).unwrap();
+        circuit.deposit_amount = Some(opening.token_amount - 1);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn test_order_properties_proof_verifies_like_escrow() {
+        let opening = sample_opening();
+        let (taker_amount, slippage_cap) = (1_500_000_000u64, 100u16);
+        let circuit = OrderPropertiesCircuit::new(opening.clone(), taker_amount, slippage_cap).unwrap();
+
+        let (pk, vk) = setup(false, circuit.clone());
+        assert_eq!(vk.gamma_abc_g1.len(), 5, "matches the escrow's OrderVerifyingKey::vk_ic");
+        let public_inputs = circuit.public_inputs().unwrap();
+        let (_, _, package) = generate_proof_package(&pk, &vk, circuit, &public_inputs).unwrap();
+
+        // What prove_order_properties does: the commitment from storage, then
+        // the escrow's taker amount, the claimed cap and the escrow's deposit,
+        // as big-endian inputs
+        let escrow_inputs = |taker_amount: u64, slippage_cap: u16| {
+            let mut taker_amount_input = [0u8; 32];
+            taker_amount_input[24..].copy_from_slice(&taker_amount.to_be_bytes());
+            let mut slippage_cap_input = [0u8; 32];
+            slippage_cap_input[30..].copy_from_slice(&slippage_cap.to_be_bytes());
+            let mut deposit_amount_input = [0u8; 32];
+            deposit_amount_input[24..].copy_from_slice(&opening.token_amount.to_be_bytes());
+            [opening.commitment_hash().unwrap(), taker_amount_input, slippage_cap_input, deposit_amount_input]
+        };
+        let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_to_be_bytes).collect();
+        let verifier = Groth16VerifierPrepared {
+            proof_a: g1_to_be_bytes(&-package.proof.a),
+            proof_b: g2_to_be_bytes(&package.proof.b),
+            proof_c: g1_to_be_bytes(&package.proof.c),
+            prepared_public_inputs: prepare_public_inputs(&vk_ic, &escrow_inputs(taker_amount, slippage_cap)).unwrap(),
+            verifying_key: Box::new(Groth16VerifyingKeyPrepared {
+                vk_alpha_g1: g1_to_be_bytes(&vk.alpha_g1),
+                vk_beta_g2: g2_to_be_bytes(&vk.beta_g2),
+                vk_gamma_g2: g2_to_be_bytes(&vk.gamma_g2),
+                vk_delta_g2: g2_to_be_bytes(&vk.delta_g2),
+            }),
+        };
+        assert_eq!(verifier.verify(), Ok(true));
+
+        // The proof doesn't carry over to another escrow's taker amount
+        let other = Groth16VerifierPrepared {
+            prepared_public_inputs: prepare_public_inputs(&vk_ic, &escrow_inputs(taker_amount - 1, slippage_cap)).unwrap(),
+            ..verifier
+        };
+        assert_eq!(other.verify(), Ok(false));
+    }
+}
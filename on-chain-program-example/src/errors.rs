@@ -1,27 +1,3 @@
-use thiserror::Error;
-
-#[derive(Debug, Error, Clone, PartialEq, Eq)]
-pub enum Groth16Error {
-    #[error("Incompatible Verifying Key with number of public inputs")]
-    IncompatibleVerifyingKeyWithNrPublicInputs,
-    #[error("ProofVerificationFailed")]
-    ProofVerificationFailed,
-    #[error("PairingVerificationError")]
-    PairingVerificationError,
-    #[error("PreparingInputsG1AdditionFailed")]
-    PreparingInputsG1AdditionFailed,
-    #[error("PreparingInputsG1MulFailed")]
-    PreparingInputsG1MulFailed,
-    #[error("InvalidG1Length")]
-    InvalidG1Length,
-    #[error("InvalidG2Length")]
-    InvalidG2Length,
-    #[error("InvalidPublicInputsLength")]
-    InvalidPublicInputsLength,
-    #[error("DecompressingG1Failed")]
-    DecompressingG1Failed,
-    #[error("DecompressingG2Failed")]
-    DecompressingG2Failed,
-    #[error("PublicInputGreaterThenFieldSize")]
-    PublicInputGreaterThenFieldSize,
-}
\ No newline at end of file
+// The program's error enum, so codes decoded from failed transactions and
+// errors from the local verifier are the same type
+pub use groth16_verifier::Groth16Error;
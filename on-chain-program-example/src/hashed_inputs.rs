@@ -0,0 +1,233 @@
+use crate::byte_utils::field_to_bytes;
+use crate::circuit::CircuitError;
+use crate::poseidon::{poseidon_gadget, POSEIDON_MAX_INPUTS};
+use ark_bn254::Fr;
+use ark_crypto_primitives::crh::sha256::constraints::Sha256Gadget;
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{
+    fields::fp::{AllocatedFp, FpVar},
+    prelude::*,
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use solana_zk_example::hash_public_inputs;
+
+pub use solana_zk_example::InputHashScheme;
+
+/// A circuit whose public inputs are allocated by the caller, so they can be
+/// either instance variables or witnesses committed to by a hash
+pub trait PublicInputsCircuit: Clone {
+    fn nr_public_inputs(&self) -> usize;
+
+    /// The logical public input values, `None` if the circuit is unassigned
+    fn public_values(&self) -> Option<Vec<Fr>>;
+
+    /// Enforces the circuit's constraints over the already allocated `inputs`
+    fn generate_constraints_with_inputs(
+        self,
+        cs: ConstraintSystemRef<Fr>,
+        inputs: &[Variable],
+    ) -> Result<(), SynthesisError>;
+}
+
+/// Wraps a circuit so that its logical public inputs become witnesses and the
+/// only public input is their hash under `scheme`.
+///
+/// The verifying key then has a single IC term, and proof-verify's
+/// `VerifyHashedInputs` recomputes the hash from the full input list, so
+/// verification costs one G1 scalar multiplication however many inputs the
+/// circuit has. Prove it with `generate_proof_package` and `public_inputs()`
+/// as usual, and send `logical_inputs()` on-chain.
+#[derive(Clone)]
+pub struct HashedInputsCircuit<C> {
+    pub inner: C,
+    pub scheme: InputHashScheme,
+}
+
+impl<C: PublicInputsCircuit> HashedInputsCircuit<C> {
+    pub fn new(inner: C, scheme: InputHashScheme) -> Self {
+        HashedInputsCircuit { inner, scheme }
+    }
+
+    /// The logical inputs as 32-byte big-endian field elements, the format
+    /// `VerifyHashedInputs` takes
+    pub fn logical_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        let values = self.inner.public_values().ok_or(CircuitError::MissingAssignment)?;
+        Ok(values.into_iter().map(fr_to_be_bytes).collect())
+    }
+
+    pub fn input_hash(&self) -> Result<Fr, CircuitError> {
+        hash_inputs(self.scheme, &self.logical_inputs()?)
+    }
+
+    /// The circuit's single public input, encoded like the other circuits'
+    /// `public_inputs()`
+    pub fn public_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        Ok(vec![field_to_bytes(self.input_hash()?)])
+    }
+}
+
+impl<C: PublicInputsCircuit> ConstraintSynthesizer<Fr> for HashedInputsCircuit<C> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let values = self.inner.public_values();
+        let input_hash = self.input_hash().ok();
+
+        // Allocate the single public input
+        let input_hash_var = FpVar::new_input(cs.clone(), || input_hash.ok_or(SynthesisError::AssignmentMissing))?;
+
+        // Allocate the logical inputs as witnesses
+        let mut input_variables = Vec::with_capacity(self.inner.nr_public_inputs());
+        let mut input_vars = Vec::with_capacity(self.inner.nr_public_inputs());
+        for i in 0..self.inner.nr_public_inputs() {
+            let value = values.as_ref().map(|values| values[i]);
+            let input = AllocatedFp::new_witness(cs.clone(), || value.ok_or(SynthesisError::AssignmentMissing))?;
+            input_variables.push(input.variable);
+            input_vars.push(FpVar::Var(input));
+        }
+
+        // Constrain public input = hash(logical inputs)
+        let computed_hash = match self.scheme {
+            InputHashScheme::Sha256 => sha256_input_hash_gadget(&input_vars)?,
+            InputHashScheme::Poseidon => poseidon_input_hash_gadget(&input_vars)?,
+        };
+        computed_hash.enforce_equal(&input_hash_var)?;
+
+        self.inner.generate_constraints_with_inputs(cs, &input_variables)
+    }
+}
+
+pub fn fr_to_be_bytes(value: Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&value.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// Native `InputHashScheme` hash, computed by the same code as on-chain
+pub fn hash_inputs(scheme: InputHashScheme, inputs: &[[u8; 32]]) -> Result<Fr, CircuitError> {
+    let hash = hash_public_inputs(scheme, inputs).map_err(|_| CircuitError::InputHashFailed)?;
+    Ok(Fr::from_be_bytes_mod_order(&hash))
+}
+
+/// `InputHashScheme::Sha256` in-circuit: SHA-256 over the big-endian inputs,
+/// keeping the low 253 bits of the digest. Like on-chain, the top three bits
+/// are dropped rather than the digest reduced mod `r`.
+pub fn sha256_input_hash_gadget(inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    let mut bytes: Vec<UInt8<Fr>> = Vec::with_capacity(32 * inputs.len());
+    for input in inputs {
+        // to_bytes is canonical and little-endian
+        let mut input_bytes = input.to_bytes()?;
+        input_bytes.reverse();
+        bytes.extend(input_bytes);
+    }
+    let digest = Sha256Gadget::digest(&bytes)?;
+
+    let mut bits: Vec<Boolean<Fr>> = Vec::with_capacity(256);
+    for byte in digest.0.iter().rev() {
+        bits.extend(byte.to_bits_le()?);
+    }
+    bits.truncate(253);
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+/// `InputHashScheme::Poseidon` in-circuit, chained the same way as on-chain
+pub fn poseidon_input_hash_gadget(inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    let (first, rest) = inputs.split_at(inputs.len().min(POSEIDON_MAX_INPUTS));
+    let mut acc = poseidon_gadget(first)?;
+    for chunk in rest.chunks(POSEIDON_MAX_INPUTS - 1) {
+        let mut parts = vec![acc];
+        parts.extend_from_slice(chunk);
+        acc = poseidon_gadget(&parts)?;
+    }
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::byte_utils::{g1_to_be_bytes, g2_to_be_bytes};
+    use crate::circuit::ExampleCircuit;
+    use crate::prove::{generate_proof_package, setup};
+    use ark_relations::r1cs::ConstraintSystem;
+    use groth16_verifier::{prepare_public_inputs, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
+
+    fn input_vars(cs: &ConstraintSystemRef<Fr>, inputs: &[Fr]) -> Vec<FpVar<Fr>> {
+        inputs
+            .iter()
+            .map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_input_hash_gadgets_match_on_chain_hash() {
+        // 13 inputs also covers the Poseidon chaining
+        let inputs: Vec<Fr> = (1..=13u64).map(|i| Fr::from(i * 1_000_003)).collect();
+        let bytes: Vec<[u8; 32]> = inputs.iter().copied().map(fr_to_be_bytes).collect();
+
+        for scheme in [InputHashScheme::Sha256, InputHashScheme::Poseidon] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let vars = input_vars(&cs, &inputs);
+            let hash = match scheme {
+                InputHashScheme::Sha256 => sha256_input_hash_gadget(&vars).unwrap(),
+                InputHashScheme::Poseidon => poseidon_input_hash_gadget(&vars).unwrap(),
+            };
+
+            assert_eq!(hash.value().unwrap(), hash_inputs(scheme, &bytes).unwrap());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hashed_inputs_circuit_rejects_wrong_hash() {
+        let circuit = HashedInputsCircuit::new(ExampleCircuit::new(100, 50).unwrap(), InputHashScheme::Poseidon);
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(cs.num_instance_variables(), 2);
+
+        // Tamper with the public input after synthesis
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.borrow_mut().unwrap().instance_assignment[1] += Fr::from(1u64);
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_hashed_inputs_proof_verifies_like_on_chain() {
+        for scheme in [InputHashScheme::Sha256, InputHashScheme::Poseidon] {
+            let circuit = HashedInputsCircuit::new(ExampleCircuit::new(1000, 500).unwrap(), scheme);
+            let (pk, vk) = setup(false, circuit.clone());
+            assert_eq!(vk.gamma_abc_g1.len(), 2, "one IC term past the constant one");
+
+            let public_inputs = circuit.public_inputs().unwrap();
+            let (_, _, package) = generate_proof_package(&pk, &vk, circuit.clone(), &public_inputs).unwrap();
+
+            // What VerifyHashedInputs does: hash the logical inputs, then
+            // prepare them against the registered vk_ic
+            let logical_inputs = circuit.logical_inputs().unwrap();
+            let input_hash = hash_public_inputs(scheme, &logical_inputs).unwrap();
+            let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_to_be_bytes).collect();
+            let verifier = Groth16VerifierPrepared {
+                proof_a: g1_to_be_bytes(&-package.proof.a),
+                proof_b: g2_to_be_bytes(&package.proof.b),
+                proof_c: g1_to_be_bytes(&package.proof.c),
+                prepared_public_inputs: prepare_public_inputs(&vk_ic, &[input_hash]).unwrap(),
+                verifying_key: Box::new(Groth16VerifyingKeyPrepared {
+                    vk_alpha_g1: g1_to_be_bytes(&vk.alpha_g1),
+                    vk_beta_g2: g2_to_be_bytes(&vk.beta_g2),
+                    vk_gamma_g2: g2_to_be_bytes(&vk.gamma_g2),
+                    vk_delta_g2: g2_to_be_bytes(&vk.delta_g2),
+                }),
+            };
+            assert_eq!(verifier.verify(), Ok(true));
+
+            // A different logical input list hashes to a different input
+            let mut other_inputs = logical_inputs.clone();
+            other_inputs[1][31] ^= 1;
+            let other_hash = hash_public_inputs(scheme, &other_inputs).unwrap();
+            let other = Groth16VerifierPrepared {
+                prepared_public_inputs: prepare_public_inputs(&vk_ic, &[other_hash]).unwrap(),
+                ..verifier
+            };
+            assert_eq!(other.verify(), Ok(false));
+        }
+    }
+}
@@ -3,4 +3,9 @@ pub mod prove;
 pub mod verify;
 pub mod byte_utils;
 pub mod verify_lite;
-pub mod circuit;
\ No newline at end of file
+pub mod circuit;
+pub mod poseidon;
+pub mod hashed_inputs;
+pub mod commitment;
+pub mod merkle;
+pub mod shielded;
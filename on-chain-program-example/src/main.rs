@@ -11,7 +11,6 @@ mod test {
     use ark_serialize::{CanonicalSerialize, Compress};
     use ark_snark::SNARK;
     use ark_std::UniformRand;
-    use borsh::{to_vec, BorshDeserialize, BorshSerialize};
     use log::{info, LevelFilter};
     use rand::thread_rng;
     use solana_client::nonblocking::rpc_client::RpcClient;
@@ -22,11 +21,14 @@ mod test {
     use solana_sdk::sysvar::slot_history::ProgramError;
     use solana_sdk::sysvar::slot_history::AccountInfo;
     use solana_sdk::program::invoke;
-    use solana_zk_client_example::byte_utils::convert_endianness;
+    use solana_zk_client_example::byte_utils::{convert_endianness, g1_to_be_bytes};
     use solana_zk_client_example::circuit::ExampleCircuit;
     use solana_zk_client_example::prove::{generate_proof_package, setup};
     use solana_zk_client_example::verify::verify_proof_package;
     use solana_zk_client_example::verify_lite::{build_verifier, convert_ark_public_input, convert_arkworks_verifying_key_to_solana_verifying_key_prepared, prepare_inputs, Groth16VerifierPrepared};
+    use solana_zk_example::{
+        circuit_id_with_ic, find_verification_state_address, find_verifying_key_address, ProgramInstruction,
+    };
     use std::ops::{Mul, Neg};
     use std::str::FromStr;
     use std::convert::TryInto;
@@ -49,16 +51,6 @@ mod test {
         let _ = env_logger::builder().filter_level(LevelFilter::Info).is_test(true).try_init();
     }
 
-    #[derive(BorshSerialize, BorshDeserialize)]
-    pub enum ProgramInstruction {
-        VerifyProof(Groth16VerifierPrepared),
-        VerifyProofWithBalance {
-            proof_data: Groth16VerifierPrepared,
-            required_balance: u64,
-            account_to_check: Pubkey,
-        },
-    }
-
     async fn request_airdrop(
         client: &RpcClient,
         pubkey: &Pubkey,
@@ -142,19 +134,54 @@ async fn test_verify_off_chain() -> Result<(), Box<dyn std::error::Error>> {
 
         let verifier_prepared = build_verifier(proof_package);
 
+        // The key and verification state PDAs are keyed by the payer and the
+        // circuit's verifying key, including its IC points
+        let verifying_key_prepared = convert_arkworks_verifying_key_to_solana_verifying_key_prepared(&verifying_key);
+        let vk_ic: Vec<[u8; 64]> = verifying_key.gamma_abc_g1.iter().map(g1_to_be_bytes).collect();
+        let circuit_id = circuit_id_with_ic(&verifying_key_prepared, &vk_ic);
+        let (key_pda, _) = find_verifying_key_address(&program_id, &payer.pubkey(), &circuit_id);
+        let (state_pda, _) = find_verification_state_address(&program_id, &payer.pubkey(), &circuit_id);
+
+        let register_instruction = Instruction::new_with_bytes(
+            program_id,
+            &ProgramInstruction::RegisterVerifyingKey { verifying_key: *verifying_key_prepared, vk_ic }.pack(),
+            vec![
+                AccountMeta::new(key_pda, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+        );
+        let init_instruction = Instruction::new_with_bytes(
+            program_id,
+            &ProgramInstruction::InitVerificationState { circuit_id }.pack(),
+            vec![
+                AccountMeta::new(state_pda, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+        );
+
         // Serialize and encode the proof package
-        let instruction_data = to_vec(&ProgramInstruction::VerifyProof(verifier_prepared))?;
+        let instruction_data = ProgramInstruction::VerifyProof {
+            proof_data: verifier_prepared,
+            public_inputs: public_inputs.clone(),
+        }
+        .pack();
         
         let instruction = Instruction::new_with_bytes(
             program_id,
             &instruction_data,
-            vec![AccountMeta::new(payer.pubkey(), true)],
+            vec![
+                AccountMeta::new_readonly(key_pda, false),
+                AccountMeta::new(state_pda, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
         );
 
         // Create and send the transaction
         let recent_blockhash = client.get_latest_blockhash().await?;
         let transaction = Transaction::new_signed_with_payer(
-            &[instruction],
+            &[register_instruction, init_instruction, instruction],
             Some(&payer.pubkey()),
             &[&payer],
             recent_blockhash,
@@ -253,16 +280,16 @@ async fn test_verify_off_chain() -> Result<(), Box<dyn std::error::Error>> {
         info!("Proof B: {:?}", proof_b);
         info!("Proof C: {:?}", proof_c);
 
-        let mut verifier: Groth16VerifierPrepared = Groth16VerifierPrepared::new(
-            proof_a,
-           proof_b,
-            proof_c,
-            prepared_public_input,
+        let verifier: Groth16VerifierPrepared = Groth16VerifierPrepared::new(
+            &proof_a,
+            &proof_b,
+            &proof_c,
+            &prepared_public_input,
             groth16_vk_prepared,
         )
         .unwrap();
 
-        match verifier.verify(&[]) {
+        match verifier.verify() {
              Ok(true) => {
                 info!("Proof verification succeeded");
                 Ok(())
@@ -0,0 +1,339 @@
+use crate::circuit::CircuitError;
+use crate::hashed_inputs::fr_to_be_bytes;
+use crate::poseidon::{poseidon_gadget, poseidon_hash};
+use ark_bn254::Fr;
+use ark_ff::{PrimeField, Zero};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use solana_program::pubkey::Pubkey;
+
+pub use solana_zk_example::{MAX_MERKLE_DEPTH, ROOT_HISTORY_SIZE};
+
+/// Parent of two nodes: Poseidon over `[left, right]`, the same as the
+/// Poseidon syscall with two big-endian inputs
+pub fn hash_pair(left: Fr, right: Fr) -> Fr {
+    poseidon_hash(&[left, right]).expect("Poseidon over two inputs")
+}
+
+/// `zero_hashes(depth)[h]` is the root of an empty subtree of height `h`.
+/// Empty leaves are zero.
+pub fn zero_hashes(depth: usize) -> Vec<Fr> {
+    let mut zeros = vec![Fr::zero()];
+    for h in 0..depth {
+        zeros.push(hash_pair(zeros[h], zeros[h]));
+    }
+    zeros
+}
+
+/// Leaf of a member holding `secret`: Poseidon over `[secret]`. The tree
+/// only publishes leaves, and proving membership needs the secret.
+pub fn member_leaf(secret: Fr) -> Fr {
+    poseidon_hash(&[secret]).expect("Poseidon over one input")
+}
+
+/// `member_context` from proof-verify as a field element
+pub fn member_context(member: &Pubkey) -> Fr {
+    Fr::from_be_bytes_mod_order(&solana_zk_example::member_context(member))
+}
+
+/// Authentication path of the leaf at `index`, siblings from the leaf level up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerklePath {
+    pub index: u64,
+    pub siblings: Vec<Fr>,
+}
+
+impl MerklePath {
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    pub fn compute_root(&self, leaf: Fr) -> Fr {
+        self.siblings.iter().enumerate().fold(leaf, |node, (h, sibling)| {
+            if (self.index >> h) & 1 == 1 {
+                hash_pair(*sibling, node)
+            } else {
+                hash_pair(node, *sibling)
+            }
+        })
+    }
+}
+
+/// In-circuit `MerklePath::compute_root`: allocates the index bits and the
+/// siblings of `path` as witnesses and hashes `leaf` up `depth` levels
+pub fn merkle_root_gadget(
+    cs: ConstraintSystemRef<Fr>,
+    leaf: FpVar<Fr>,
+    path: Option<&MerklePath>,
+    depth: usize,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    if path.is_some_and(|path| path.depth() != depth) {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    let mut node = leaf;
+    for h in 0..depth {
+        let is_right = Boolean::new_witness(cs.clone(), || {
+            path.map(|path| (path.index >> h) & 1 == 1)
+                .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let sibling = FpVar::new_witness(cs.clone(), || {
+            path.map(|path| path.siblings[h]).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        // Order each pair by the index bit
+        let left = is_right.select(&sibling, &node)?;
+        let right = is_right.select(&node, &sibling)?;
+        node = poseidon_gadget(&[left, right])?;
+    }
+    Ok(node)
+}
+
+/// Append-only Poseidon Merkle tree of fixed depth, for building the trees
+/// whose roots go into a proof-verify `RootRegistry` and generating the
+/// paths `MerkleMembershipCircuit` proves against.
+///
+/// Only the filled part of each level is stored, so a deep tree with few
+/// leaves stays small.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    depth: usize,
+    // layers[h] holds the nodes of height h, leaves first
+    layers: Vec<Vec<Fr>>,
+    zeros: Vec<Fr>,
+}
+
+impl MerkleTree {
+    pub fn new(depth: usize) -> Result<Self, CircuitError> {
+        if depth == 0 || depth > MAX_MERKLE_DEPTH as usize {
+            return Err(CircuitError::InvalidRange);
+        }
+        Ok(MerkleTree {
+            depth,
+            layers: vec![Vec::new(); depth + 1],
+            zeros: zero_hashes(depth),
+        })
+    }
+
+    pub fn from_leaves(depth: usize, leaves: &[Fr]) -> Result<Self, CircuitError> {
+        let mut tree = Self::new(depth)?;
+        for leaf in leaves {
+            tree.insert(*leaf)?;
+        }
+        Ok(tree)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn capacity(&self) -> u64 {
+        1u64 << self.depth
+    }
+
+    pub fn len(&self) -> u64 {
+        self.layers[0].len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers[0].is_empty()
+    }
+
+    pub fn leaf(&self, index: u64) -> Option<Fr> {
+        self.layers[0].get(index as usize).copied()
+    }
+
+    /// Appends `leaf` and returns its index
+    pub fn insert(&mut self, leaf: Fr) -> Result<u64, CircuitError> {
+        let index = self.len();
+        if index >= self.capacity() {
+            return Err(CircuitError::InvalidRange);
+        }
+
+        self.layers[0].push(leaf);
+        let mut position = index as usize;
+        for h in 0..self.depth {
+            let parent = position / 2;
+            let node = hash_pair(self.node(h, 2 * parent), self.node(h, 2 * parent + 1));
+            if parent == self.layers[h + 1].len() {
+                self.layers[h + 1].push(node);
+            } else {
+                self.layers[h + 1][parent] = node;
+            }
+            position = parent;
+        }
+        Ok(index)
+    }
+
+    pub fn root(&self) -> Fr {
+        self.node(self.depth, 0)
+    }
+
+    /// The root as 32 big-endian bytes, the format `InitRootRegistry` and
+    /// `UpdateRoot` take
+    pub fn root_bytes(&self) -> [u8; 32] {
+        fr_to_be_bytes(self.root())
+    }
+
+    pub fn path(&self, index: u64) -> Result<MerklePath, CircuitError> {
+        if index >= self.len() {
+            return Err(CircuitError::InvalidRange);
+        }
+        let siblings = (0..self.depth)
+            .map(|h| self.node(h, ((index >> h) ^ 1) as usize))
+            .collect();
+        Ok(MerklePath { index, siblings })
+    }
+
+    fn node(&self, height: usize, position: usize) -> Fr {
+        self.layers[height].get(position).copied().unwrap_or(self.zeros[height])
+    }
+}
+
+/// Client mirror of proof-verify's `IncrementalMerkleTree` account. Feed it
+/// the same leaves, in order, for example from `LeafAppended` events, and it
+/// has the same root and root history, plus the paths the account doesn't
+/// keep.
+#[derive(Clone, Debug)]
+pub struct IncrementalTreeMirror {
+    tree: MerkleTree,
+    // Ring buffer laid out like the account's `RootHistory`
+    roots: Vec<Fr>,
+    current_root_index: usize,
+}
+
+impl IncrementalTreeMirror {
+    pub fn new(depth: usize) -> Result<Self, CircuitError> {
+        let tree = MerkleTree::new(depth)?;
+        let mut roots = vec![Fr::zero(); ROOT_HISTORY_SIZE];
+        roots[0] = tree.root();
+        Ok(IncrementalTreeMirror {
+            tree,
+            roots,
+            current_root_index: 0,
+        })
+    }
+
+    pub fn from_leaves(depth: usize, leaves: &[Fr]) -> Result<Self, CircuitError> {
+        let mut mirror = Self::new(depth)?;
+        for leaf in leaves {
+            mirror.append(*leaf)?;
+        }
+        Ok(mirror)
+    }
+
+    /// Appends `leaf` like `AppendLeaf` and returns its index
+    pub fn append(&mut self, leaf: Fr) -> Result<u64, CircuitError> {
+        let index = self.tree.insert(leaf)?;
+        self.current_root_index = (self.current_root_index + 1) % ROOT_HISTORY_SIZE;
+        self.roots[self.current_root_index] = self.tree.root();
+        Ok(index)
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Whether `VerifyMembership` would still accept a proof against `root`
+    pub fn is_known_root(&self, root: Fr) -> bool {
+        !root.is_zero() && self.roots.contains(&root)
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_zk_example::{hash_public_inputs, InputHashScheme};
+
+    fn leaves(n: u64) -> Vec<Fr> {
+        (1..=n).map(|i| member_leaf(Fr::from(i))).collect()
+    }
+
+    #[test]
+    fn test_hash_pair_matches_poseidon_syscall() {
+        let (left, right) = (Fr::from(3u64), Fr::from(4u64));
+        let expected = hash_public_inputs(InputHashScheme::Poseidon, &[fr_to_be_bytes(left), fr_to_be_bytes(right)]);
+        assert_eq!(fr_to_be_bytes(hash_pair(left, right)), expected.unwrap());
+    }
+
+    #[test]
+    fn test_tree_root_matches_full_recomputation() {
+        let depth = 3;
+        let leaves = leaves(5);
+        let tree = MerkleTree::from_leaves(depth, &leaves).unwrap();
+
+        let mut level: Vec<Fr> = leaves.clone();
+        level.resize(1 << depth, Fr::zero());
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| hash_pair(pair[0], pair[1])).collect();
+        }
+        assert_eq!(tree.root(), level[0]);
+        assert_eq!(MerkleTree::new(depth).unwrap().root(), zero_hashes(depth)[depth]);
+    }
+
+    #[test]
+    fn test_paths_open_to_the_root() {
+        let tree = MerkleTree::from_leaves(4, &leaves(11)).unwrap();
+        for index in 0..tree.len() {
+            let path = tree.path(index).unwrap();
+            assert_eq!(path.depth(), 4);
+            assert_eq!(path.compute_root(tree.leaf(index).unwrap()), tree.root());
+            assert_ne!(path.compute_root(Fr::from(999u64)), tree.root());
+        }
+        assert!(tree.path(tree.len()).is_err());
+    }
+
+    #[test]
+    fn test_tree_bounds() {
+        assert!(MerkleTree::new(0).is_err());
+        assert!(MerkleTree::new(MAX_MERKLE_DEPTH as usize + 1).is_err());
+
+        let mut tree = MerkleTree::from_leaves(2, &leaves(4)).unwrap();
+        assert_eq!(tree.len(), tree.capacity());
+        assert!(tree.insert(Fr::from(5u64)).is_err());
+    }
+
+    #[test]
+    fn test_mirror_reproduces_on_chain_roots() {
+        let depth = 5u8;
+        let mut account = solana_zk_example::IncrementalMerkleTree::new(Pubkey::new_unique(), [0u8; 32], 255, depth).unwrap();
+        let mut mirror = IncrementalTreeMirror::new(depth as usize).unwrap();
+        assert_eq!(fr_to_be_bytes(mirror.root()), account.root());
+
+        for leaf in leaves(20) {
+            let index = account.append(fr_to_be_bytes(leaf)).unwrap();
+            assert_eq!(mirror.append(leaf).unwrap(), index);
+            assert_eq!(fr_to_be_bytes(mirror.root()), account.root());
+        }
+
+        // Same ring buffer, slot for slot
+        let roots: Vec<[u8; 32]> = mirror.roots.iter().copied().map(fr_to_be_bytes).collect();
+        assert_eq!(roots, account.history.roots.to_vec());
+        assert_eq!(mirror.current_root_index, account.history.current_index as usize);
+    }
+
+    #[test]
+    fn test_mirror_forgets_old_roots_like_the_account() {
+        let mut mirror = IncrementalTreeMirror::new(6).unwrap();
+        let first_root = mirror.root();
+        let leaves = leaves(ROOT_HISTORY_SIZE as u64);
+
+        for leaf in &leaves[..ROOT_HISTORY_SIZE - 1] {
+            mirror.append(*leaf).unwrap();
+        }
+        assert!(mirror.is_known_root(first_root));
+
+        mirror.append(leaves[ROOT_HISTORY_SIZE - 1]).unwrap();
+        assert!(!mirror.is_known_root(first_root));
+        assert!(mirror.is_known_root(mirror.root()));
+        assert!(!mirror.is_known_root(Fr::zero()));
+
+        // The mirror's paths open its leaves against the on-chain root
+        let path = mirror.tree().path(3).unwrap();
+        assert_eq!(path.compute_root(leaves[3]), mirror.root());
+    }
+}
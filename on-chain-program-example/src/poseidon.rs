@@ -0,0 +1,115 @@
+use ark_bn254::Fr;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::SynthesisError;
+use light_poseidon::{parameters::bn254_x5::get_poseidon_parameters, Poseidon, PoseidonHasher};
+
+pub use solana_zk_example::POSEIDON_MAX_INPUTS;
+
+/// Native circom-compatible Poseidon over BN254, the same function as the
+/// Poseidon syscall and `poseidon_gadget`
+pub fn poseidon_hash(inputs: &[Fr]) -> Result<Fr, SynthesisError> {
+    let mut hasher = Poseidon::<Fr>::new_circom(inputs.len()).map_err(|_| SynthesisError::Unsatisfiable)?;
+    hasher.hash(inputs).map_err(|_| SynthesisError::Unsatisfiable)
+}
+
+/// In-circuit Poseidon over 1 to `POSEIDON_MAX_INPUTS` inputs.
+///
+/// Follows light-poseidon: the state is `[0, inputs...]`, and each round adds
+/// the round constants, applies `x^5` to every element (full rounds) or to the
+/// first one (partial rounds), then multiplies by the MDS matrix. Each S-box
+/// costs three constraints; the rest is linear.
+pub fn poseidon_gadget(inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    if inputs.is_empty() || inputs.len() > POSEIDON_MAX_INPUTS {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    let width = inputs.len() + 1;
+    let params = get_poseidon_parameters::<Fr>(width as u8).map_err(|_| SynthesisError::Unsatisfiable)?;
+
+    let mut state: Vec<FpVar<Fr>> = Vec::with_capacity(width);
+    state.push(FpVar::zero());
+    state.extend(inputs.iter().cloned());
+
+    let half_full_rounds = params.full_rounds / 2;
+    let all_rounds = params.full_rounds + params.partial_rounds;
+    for round in 0..all_rounds {
+        for (i, element) in state.iter_mut().enumerate() {
+            *element += params.ark[round * width + i];
+        }
+
+        let is_full_round = round < half_full_rounds || round >= half_full_rounds + params.partial_rounds;
+        if is_full_round {
+            for element in state.iter_mut() {
+                *element = sbox(element)?;
+            }
+        } else {
+            state[0] = sbox(&state[0])?;
+        }
+
+        state = params
+            .mds
+            .iter()
+            .map(|row| {
+                state
+                    .iter()
+                    .zip(row.iter())
+                    .fold(FpVar::zero(), |acc, (element, m)| acc + element * *m)
+            })
+            .collect();
+    }
+
+    Ok(state.swap_remove(0))
+}
+
+// x^5, the S-box for BN254
+fn sbox(x: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+    let x2 = x.square()?;
+    let x4 = x2.square()?;
+    Ok(x4 * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_poseidon_gadget_matches_native() {
+        for nr_inputs in [1, 2, 5, POSEIDON_MAX_INPUTS] {
+            let inputs: Vec<Fr> = (1..=nr_inputs as u64).map(Fr::from).collect();
+
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let input_vars: Vec<FpVar<Fr>> = inputs
+                .iter()
+                .map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)).unwrap())
+                .collect();
+            let hash = poseidon_gadget(&input_vars).unwrap();
+
+            assert_eq!(hash.value().unwrap(), poseidon_hash(&inputs).unwrap());
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_poseidon_matches_on_chain_hash() {
+        use ark_ff::{BigInteger, PrimeField};
+        use solana_zk_example::{hash_public_inputs, InputHashScheme};
+
+        let inputs = [Fr::from(7u64), Fr::from(11u64)];
+        let bytes: Vec<[u8; 32]> = inputs
+            .iter()
+            .map(|x| x.into_bigint().to_bytes_be().try_into().unwrap())
+            .collect();
+        let expected = hash_public_inputs(InputHashScheme::Poseidon, &bytes).unwrap();
+
+        let hash = poseidon_hash(&inputs).unwrap();
+        assert_eq!(hash.into_bigint().to_bytes_be(), expected.to_vec());
+    }
+
+    #[test]
+    fn test_poseidon_gadget_rejects_bad_arity() {
+        assert!(poseidon_gadget(&[]).is_err());
+        let too_many = vec![FpVar::zero(); POSEIDON_MAX_INPUTS + 1];
+        assert!(poseidon_gadget(&too_many).is_err());
+    }
+}
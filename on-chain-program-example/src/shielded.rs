@@ -0,0 +1,163 @@
+use crate::byte_utils::field_to_bytes;
+use crate::circuit::CircuitError;
+use crate::hashed_inputs::fr_to_be_bytes;
+use crate::merkle::{member_context, merkle_root_gadget, MerklePath, MerkleTree};
+use crate::poseidon::{poseidon_gadget, poseidon_hash};
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use rand::Rng;
+use solana_program::pubkey::Pubkey;
+
+/// A shielded pool note. The depositor keeps it secret: whoever holds it can
+/// withdraw the deposit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub nullifier_secret: Fr,
+    pub randomness: Fr,
+}
+
+impl Note {
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        Note {
+            nullifier_secret: Fr::rand(rng),
+            randomness: Fr::rand(rng),
+        }
+    }
+
+    /// The leaf appended to the pool's tree: Poseidon over
+    /// `[nullifier_secret, randomness]`
+    pub fn commitment(&self) -> Fr {
+        poseidon_hash(&[self.nullifier_secret, self.randomness]).expect("Poseidon over two inputs")
+    }
+
+    /// The commitment as `Deposit` takes it, 32 big-endian bytes
+    pub fn commitment_bytes(&self) -> [u8; 32] {
+        fr_to_be_bytes(self.commitment())
+    }
+
+    /// Revealed on withdrawal: Poseidon over `[nullifier_secret]`. It doesn't
+    /// link back to the commitment, but a note only has one.
+    pub fn nullifier_hash(&self) -> Fr {
+        poseidon_hash(&[self.nullifier_secret]).expect("Poseidon over one input")
+    }
+
+    pub fn nullifier_hash_bytes(&self) -> [u8; 32] {
+        fr_to_be_bytes(self.nullifier_hash())
+    }
+}
+
+/// WithdrawCircuit proves that the prover holds a note whose commitment is in
+/// the pool's tree, and reveals its nullifier hash so it can't be spent twice.
+///
+/// Public inputs, in the order of the shielded pool's `Withdraw`: the root,
+/// the nullifier hash and the recipient, `member_context` of the recipient
+/// token account.
+/// Witness: the note and its Merkle path.
+/// Constraints: the commitment opens to the root through the path, and the
+/// nullifier hash is the note's. The recipient is squared so a relayer can't
+/// swap it in the proof.
+#[derive(Clone)]
+pub struct WithdrawCircuit {
+    pub depth: usize,
+    pub note: Option<Note>,
+    pub path: Option<MerklePath>,
+    pub root: Option<Fr>,
+    pub nullifier_hash: Option<Fr>,
+    pub recipient: Option<Fr>,
+}
+
+impl WithdrawCircuit {
+    /// Withdrawal of `note`, the leaf at `index` of `tree`, to `recipient`
+    pub fn new(tree: &MerkleTree, index: u64, note: Note, recipient: &Pubkey) -> Result<Self, CircuitError> {
+        let path = tree.path(index)?;
+        if path.compute_root(note.commitment()) != tree.root() {
+            return Err(CircuitError::NotInTree);
+        }
+
+        Ok(WithdrawCircuit {
+            depth: tree.depth(),
+            note: Some(note),
+            path: Some(path),
+            root: Some(tree.root()),
+            nullifier_hash: Some(note.nullifier_hash()),
+            recipient: Some(member_context(recipient)),
+        })
+    }
+
+    pub fn public_inputs(&self) -> Result<Vec<[u8; 32]>, CircuitError> {
+        match (self.root, self.nullifier_hash, self.recipient) {
+            (Some(root), Some(nullifier_hash), Some(recipient)) => Ok(vec![
+                field_to_bytes(root),
+                field_to_bytes(nullifier_hash),
+                field_to_bytes(recipient),
+            ]),
+            _ => Err(CircuitError::MissingAssignment),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for WithdrawCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        // Allocate the public inputs
+        let root = FpVar::new_input(cs.clone(), || self.root.ok_or(SynthesisError::AssignmentMissing))?;
+        let nullifier_hash =
+            FpVar::new_input(cs.clone(), || self.nullifier_hash.ok_or(SynthesisError::AssignmentMissing))?;
+        let recipient = FpVar::new_input(cs.clone(), || self.recipient.ok_or(SynthesisError::AssignmentMissing))?;
+        recipient.square()?;
+
+        // Allocate the note
+        let nullifier_secret = FpVar::new_witness(cs.clone(), || {
+            self.note.map(|note| note.nullifier_secret).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let randomness = FpVar::new_witness(cs.clone(), || {
+            self.note.map(|note| note.randomness).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        // The nullifier hash belongs to the note
+        poseidon_gadget(&[nullifier_secret.clone()])?.enforce_equal(&nullifier_hash)?;
+
+        // The note's commitment is in the tree
+        let commitment = poseidon_gadget(&[nullifier_secret, randomness])?;
+        merkle_root_gadget(cs, commitment, self.path.as_ref(), self.depth)?.enforce_equal(&root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_withdraw_constraints() {
+        let rng = &mut thread_rng();
+        let notes: Vec<Note> = (0..5).map(|_| Note::random(rng)).collect();
+        let commitments: Vec<Fr> = notes.iter().map(Note::commitment).collect();
+        let tree = MerkleTree::from_leaves(6, &commitments).unwrap();
+        let recipient = Pubkey::new_unique();
+
+        let circuit = WithdrawCircuit::new(&tree, 2, notes[2], &recipient).unwrap();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // Another note's nullifier hash
+        let mut wrong = circuit.clone();
+        wrong.nullifier_hash = Some(notes[3].nullifier_hash());
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        wrong.generate_constraints(cs.clone()).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        // A note that was never deposited
+        assert!(WithdrawCircuit::new(&tree, 2, Note::random(rng), &recipient).is_err());
+    }
+
+    #[test]
+    fn test_note_hashes_are_distinct() {
+        let note = Note::random(&mut thread_rng());
+        assert_ne!(note.commitment(), note.nullifier_hash());
+        assert_eq!(note.commitment_bytes(), fr_to_be_bytes(note.commitment()));
+    }
+}
@@ -1,8 +1,11 @@
-use ark_bn254::{Bn254, G1Projective};
-use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use ark_bn254::{Bn254, Fr, G1Projective};
+use ark_groth16::{prepare_verifying_key, Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
 use thiserror::Error;
 use crate::prove::ProofPackage;
+use ark_ec::pairing::Pairing;
 use ark_ec::CurveGroup; 
+use ark_ff::{Field, PrimeField, Zero};
+use rand::{thread_rng, Rng};
 
 #[derive(Error, Debug)]
 pub enum VerificationError {
@@ -16,6 +19,8 @@ pub enum VerificationError {
     InvalidVerifyingKey,
     #[error("Input count mismatch")]
     InputCountMismatch,
+    #[error("Empty batch")]
+    EmptyBatch,
 }
 
 fn validate_verifying_key(vk: &VerifyingKey<Bn254>) -> Result<(), VerificationError> {
@@ -82,6 +87,132 @@ pub fn verify_proof_package(proof_package: &ProofPackage) -> Result<bool, Verifi
     .map_err(|_| VerificationError::VerificationFailed)
 }
 
+/// Checks many proofs for one circuit with a single randomized multi-pairing.
+///
+/// Mirrors `VerifyBatch` in proof-verify: proof `i` is scaled by a random
+/// 128-bit `r_i` and the Groth16 equations are multiplied together, so
+/// `prod e(r_i A_i, B_i) * e(sum r_i L_i, -gamma) * e(sum r_i C_i, -delta)`
+/// must equal `e(alpha, beta)^(sum r_i)`. Off-chain the coefficients come
+/// from the thread RNG rather than a hash of the batch.
+///
+/// If the combined check fails, `find_invalid` bisects the batch to find
+/// which proofs are bad, with O(k log n) multi-pairings for k bad proofs.
+pub struct BatchVerifier<'a> {
+    pvk: &'a PreparedVerifyingKey<Bn254>,
+    entries: Vec<(Proof<Bn254>, G1Projective)>,
+}
+
+impl<'a> BatchVerifier<'a> {
+    pub fn new(pvk: &'a PreparedVerifyingKey<Bn254>) -> Self {
+        BatchVerifier { pvk, entries: Vec::new() }
+    }
+
+    /// Adds a proof with its raw public inputs, prepared against the batch key
+    pub fn add(&mut self, proof: Proof<Bn254>, public_inputs: &[Fr]) -> Result<(), VerificationError> {
+        let prepared_inputs = Groth16::<Bn254>::prepare_inputs(self.pvk, public_inputs)
+            .map_err(|_| VerificationError::InputCountMismatch)?;
+        self.add_prepared(proof, prepared_inputs)
+    }
+
+    /// Adds a proof whose public inputs are already prepared, as in `ProofPackage`
+    pub fn add_prepared(&mut self, proof: Proof<Bn254>, prepared_inputs: G1Projective) -> Result<(), VerificationError> {
+        if !is_valid_proof(&proof) {
+            return Err(VerificationError::InvalidProof);
+        }
+        if !is_valid_point(&prepared_inputs) {
+            return Err(VerificationError::InvalidPublicInput);
+        }
+        self.entries.push((proof, prepared_inputs));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns true if every proof in the batch is valid
+    pub fn verify(&self) -> Result<bool, VerificationError> {
+        if self.entries.is_empty() {
+            return Err(VerificationError::EmptyBatch);
+        }
+        self.check(&self.entries)
+    }
+
+    /// Returns the indices of the invalid proofs, in order. Empty if the
+    /// whole batch verifies.
+    pub fn find_invalid(&self) -> Result<Vec<usize>, VerificationError> {
+        if self.entries.is_empty() {
+            return Err(VerificationError::EmptyBatch);
+        }
+        let mut invalid = Vec::new();
+        self.bisect(0, &self.entries, &mut invalid)?;
+        Ok(invalid)
+    }
+
+    fn bisect(
+        &self,
+        offset: usize,
+        entries: &[(Proof<Bn254>, G1Projective)],
+        invalid: &mut Vec<usize>,
+    ) -> Result<(), VerificationError> {
+        if self.check(entries)? {
+            return Ok(());
+        }
+        if entries.len() == 1 {
+            invalid.push(offset);
+            return Ok(());
+        }
+        let mid = entries.len() / 2;
+        self.bisect(offset, &entries[..mid], invalid)?;
+        self.bisect(offset + mid, &entries[mid..], invalid)
+    }
+
+    fn check(&self, entries: &[(Proof<Bn254>, G1Projective)]) -> Result<bool, VerificationError> {
+        let rng = &mut thread_rng();
+        let mut g1_terms: Vec<<Bn254 as Pairing>::G1Prepared> = Vec::with_capacity(entries.len() + 2);
+        let mut g2_terms: Vec<<Bn254 as Pairing>::G2Prepared> = Vec::with_capacity(entries.len() + 2);
+        let mut sum_inputs = G1Projective::zero();
+        let mut sum_c = G1Projective::zero();
+        let mut sum_r = Fr::zero();
+
+        for (proof, prepared_inputs) in entries {
+            let r = Fr::from(rng.gen::<u128>());
+            g1_terms.push((proof.a * r).into_affine().into());
+            g2_terms.push(proof.b.into());
+            sum_inputs += *prepared_inputs * r;
+            sum_c += proof.c * r;
+            sum_r += r;
+        }
+
+        g1_terms.push(sum_inputs.into_affine().into());
+        g2_terms.push(self.pvk.gamma_g2_neg_pc.clone());
+        g1_terms.push(sum_c.into_affine().into());
+        g2_terms.push(self.pvk.delta_g2_neg_pc.clone());
+
+        let qap = Bn254::multi_miller_loop(g1_terms, g2_terms);
+        let test = Bn254::final_exponentiation(qap).ok_or(VerificationError::VerificationFailed)?;
+
+        Ok(test.0 == self.pvk.alpha_g1_beta_g2.pow(sum_r.into_bigint()))
+    }
+}
+
+/// Verifies many proofs for one circuit with a single multi-pairing.
+/// See `BatchVerifier`.
+pub fn verify_batch(
+    pvk: &PreparedVerifyingKey<Bn254>,
+    batch: &[(Proof<Bn254>, Vec<Fr>)],
+) -> Result<bool, VerificationError> {
+    let mut verifier = BatchVerifier::new(pvk);
+    for (proof, public_inputs) in batch {
+        verifier.add(proof.clone(), public_inputs)?;
+    }
+    verifier.verify()
+}
+
 fn is_valid_point(point: &G1Projective) -> bool {
     let affine = point.into_affine();
     affine.is_on_curve() && affine.is_in_correct_subgroup_assuming_on_curve()
@@ -149,6 +280,94 @@ mod tests {
         assert!(!is_valid_proof(&proof));
     }
 
+    fn generate_token_batch(size: u64) -> (PreparedVerifyingKey<Bn254>, Vec<(Proof<Bn254>, Vec<Fr>)>) {
+        use crate::circuit::TokenVerificationCircuit;
+        use ark_snark::SNARK;
+
+        let mut rng = thread_rng();
+        let setup_circuit = TokenVerificationCircuit::new(2000, 1500).unwrap();
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(setup_circuit, &mut rng).unwrap();
+
+        let batch = (0..size)
+            .map(|i| {
+                let tokens_asked = 1000 + i;
+                let circuit = TokenVerificationCircuit::new(2000, tokens_asked).unwrap();
+                let proof = Groth16::<Bn254>::prove(&pk, circuit, &mut rng).unwrap();
+                (proof, vec![Fr::from(tokens_asked)])
+            })
+            .collect();
+
+        (prepare_verifying_key(&vk), batch)
+    }
+
+    #[test]
+    fn test_verify_batch_accepts_valid_proofs() {
+        let (pvk, batch) = generate_token_batch(3);
+        assert!(verify_batch(&pvk, &batch).unwrap());
+    }
+
+    #[test]
+    fn test_verify_batch_rejects_wrong_public_input() {
+        let (pvk, mut batch) = generate_token_batch(3);
+        batch[1].1 = vec![Fr::from(999u64)];
+        assert!(!verify_batch(&pvk, &batch).unwrap());
+    }
+
+    #[test]
+    fn test_verify_batch_rejects_empty_and_mismatched_input_count() {
+        let (pvk, mut batch) = generate_token_batch(1);
+        assert!(matches!(verify_batch(&pvk, &[]), Err(VerificationError::EmptyBatch)));
+
+        batch[0].1.push(Fr::from(1u64));
+        assert!(matches!(
+            verify_batch(&pvk, &batch),
+            Err(VerificationError::InputCountMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_batch_verifier_finds_invalid_proofs() {
+        let (pvk, mut batch) = generate_token_batch(5);
+        batch[1].1 = vec![Fr::from(999u64)];
+        batch[4].1 = vec![Fr::from(1u64)];
+
+        let mut verifier = BatchVerifier::new(&pvk);
+        for (proof, inputs) in &batch {
+            verifier.add(proof.clone(), inputs).unwrap();
+        }
+
+        assert_eq!(verifier.len(), 5);
+        assert!(!verifier.verify().unwrap());
+        assert_eq!(verifier.find_invalid().unwrap(), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_batch_verifier_accepts_prepared_inputs() {
+        let (pvk, batch) = generate_token_batch(2);
+
+        let mut verifier = BatchVerifier::new(&pvk);
+        for (proof, inputs) in &batch {
+            let prepared = Groth16::<Bn254>::prepare_inputs(&pvk, inputs).unwrap();
+            verifier.add_prepared(proof.clone(), prepared).unwrap();
+        }
+
+        assert!(verifier.verify().unwrap());
+        assert!(verifier.find_invalid().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_verifier_rejects_invalid_proof_points() {
+        let (pvk, _) = generate_token_batch(1);
+        let mut verifier = BatchVerifier::new(&pvk);
+
+        assert!(matches!(
+            verifier.add(generate_invalid_proof(), &[Fr::from(1000u64)]),
+            Err(VerificationError::InvalidProof)
+        ));
+        assert!(verifier.is_empty());
+        assert!(matches!(verifier.find_invalid(), Err(VerificationError::EmptyBatch)));
+    }
+
     #[test]
     fn test_verify_with_invalid_proof() {
         let mut rng = thread_rng();
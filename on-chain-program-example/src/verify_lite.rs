@@ -7,27 +7,12 @@ use ark_groth16::{Proof, VerifyingKey};
 use ark_relations::r1cs::SynthesisError;
 use ark_serialize::{CanonicalSerialize, Compress};
 use borsh::{BorshDeserialize, BorshSerialize};
-use num_bigint::BigUint;
-use solana_program::{pubkey::Pubkey, program::invoke, instruction::Instruction};
-use solana_program::account_info::AccountInfo;
-use solana_program::program_error::ProgramError;
+use groth16_verifier::bn254::pairing_check;
+use groth16_verifier::{prepare_public_inputs, prepare_public_inputs_unchecked};
 use std::ops::{AddAssign, Neg};
 use log::info;
 use crate::prove::ProofPackage;
 
-pub const ALT_BN128_PAIRING: Pubkey = Pubkey::new_from_array([
-    2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-]);
-
-fn alt_bn128_pairing_call(data: &[u8], accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-    let ix = Instruction {
-        program_id: ALT_BN128_PAIRING,
-        accounts: vec![],
-        data: data.to_vec(),
-    };
-    invoke(&ix, accounts)
-}
-
 #[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub struct Groth16VerifyingKey {
     pub number_public_inputs: usize,
@@ -48,71 +33,9 @@ pub struct Groth16Verifier<'a, const NR_INPUTS: usize> {
     verifying_key: Box<Groth16VerifyingKey>,
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct Groth16VerifyingKeyPrepared {
-    pub vk_alpha_g1: [u8; 64],
-    pub vk_beta_g2: [u8; 128],
-    pub vk_gamma_g2: [u8; 128],
-    pub vk_delta_g2: [u8; 128],
-}
-
-#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct Groth16VerifierPrepared {
-    proof_a: [u8; 64],
-    proof_b: [u8; 128],
-    proof_c: [u8; 64],
-    prepared_public_inputs: [u8; 64],
-    verifying_key: Box<Groth16VerifyingKeyPrepared>,
-}
-
-impl Groth16VerifierPrepared {
-    pub fn new(
-        proof_a: [u8; 64],
-        proof_b: [u8; 128],
-        proof_c: [u8; 64],
-        prepared_public_inputs: [u8; 64],
-        verifying_key: Box<Groth16VerifyingKeyPrepared>,
-    ) -> Result<Groth16VerifierPrepared, Groth16Error> {
-        if proof_a.len() != 64 {
-            return Err(Groth16Error::InvalidG1Length);
-        }
-
-        if proof_b.len() != 128 {
-            return Err(Groth16Error::InvalidG2Length);
-        }
-
-        if proof_c.len() != 64 {
-            return Err(Groth16Error::InvalidG1Length);
-        }
-
-        Ok(Groth16VerifierPrepared {
-            proof_a,
-            proof_b,
-            proof_c,
-            prepared_public_inputs,
-            verifying_key,
-        })
-    }
-
-    pub fn verify(&mut self, accounts: &[AccountInfo]) -> Result<bool, Groth16Error> {
-        let pairing_input = [
-            self.proof_a.as_slice(),
-            self.proof_b.as_slice(),
-            self.prepared_public_inputs.as_slice(),
-            self.verifying_key.vk_gamma_g2.as_slice(),
-            self.proof_c.as_slice(),
-            self.verifying_key.vk_delta_g2.as_slice(),
-            self.verifying_key.vk_alpha_g1.as_slice(),
-            self.verifying_key.vk_beta_g2.as_slice(),
-        ]
-        .concat();
-
-        alt_bn128_pairing_call(pairing_input.as_slice(), accounts)
-        .map_err(|_| Groth16Error::ProofVerificationFailed)?;
-
-        Ok(true)
-    }
-}
+// Shared with the program, so the client can't drift from the on-chain
+// Borsh layout or verification logic
+pub use groth16_verifier::{Groth16VerifierPrepared, Groth16VerifyingKeyPrepared};
 
 impl<const NR_INPUTS: usize> Groth16Verifier<'_, NR_INPUTS> {
     pub fn new<'a>(
@@ -149,42 +72,28 @@ impl<const NR_INPUTS: usize> Groth16Verifier<'_, NR_INPUTS> {
     }
 
     pub fn prepare_inputs<const CHECK: bool>(&mut self) -> Result<(), Groth16Error> {
-        let mut prepared_public_inputs = self.verifying_key.vk_ic[0];
-
-        for (i, input) in self.public_inputs.iter().enumerate() {
-            if CHECK && !is_less_than_bn254_field_size_be(input) {
-                return Err(Groth16Error::PublicInputGreaterThenFieldSize);
-            }
-            let x = [&self.verifying_key.vk_ic[i + 1][..], &input[..]].concat();
-            let mul_res = crate::byte_utils::alt_bn128_multiplication(&x).map_err(|error| {
-                info!("{:?}", error);
-                Groth16Error::PreparingInputsG1MulFailed
-            })?;
-            prepared_public_inputs =
-                crate::byte_utils::alt_bn128_addition(&[&mul_res[..], &prepared_public_inputs[..]].concat())
-                    .map_err(|_| Groth16Error::PreparingInputsG1AdditionFailed)?[..]
-                    .try_into()
-                    .map_err(|_| Groth16Error::PreparingInputsG1AdditionFailed)?;
-        }
-
-        self.prepared_public_inputs = prepared_public_inputs;
+        self.prepared_public_inputs = if CHECK {
+            prepare_public_inputs(&self.verifying_key.vk_ic, self.public_inputs)?
+        } else {
+            prepare_public_inputs_unchecked(&self.verifying_key.vk_ic, self.public_inputs)?
+        };
 
         Ok(())
     }
 
     /// Verifies the proof, and checks that public inputs are smaller than
     /// field size.
-    pub fn prepare_and_verify(&mut self, accounts: &[AccountInfo]) -> Result<bool, Groth16Error> {
-        self.prepare_and_verify_common::<true>(accounts)
+    pub fn prepare_and_verify(&mut self) -> Result<bool, Groth16Error> {
+        self.prepare_and_verify_common::<true>()
     }
 
     /// Verifies the proof, and does not check that public inputs are smaller
     /// than field size.
-    pub fn prepare_and_verify_unchecked(&mut self, accounts: &[AccountInfo]) -> Result<bool, Groth16Error> {
-        self.prepare_and_verify_common::<false>(accounts)
+    pub fn prepare_and_verify_unchecked(&mut self) -> Result<bool, Groth16Error> {
+        self.prepare_and_verify_common::<false>()
     }
 
-    fn prepare_and_verify_common<const CHECK: bool>(&mut self, accounts: &[AccountInfo]) -> Result<bool, Groth16Error> {
+    fn prepare_and_verify_common<const CHECK: bool>(&mut self) -> Result<bool, Groth16Error> {
         self.prepare_inputs::<CHECK>()?;
 
         let pairing_input = [
@@ -199,11 +108,7 @@ impl<const NR_INPUTS: usize> Groth16Verifier<'_, NR_INPUTS> {
         ]
         .concat();
 
-        alt_bn128_pairing_call(pairing_input.as_slice(), accounts)
-        .map_err(|_| Groth16Error::ProofVerificationFailed)?;
-
-
-        Ok(true)
+        pairing_check(&pairing_input)
     }
 }
 
@@ -257,10 +162,10 @@ pub fn build_verifier(proof_package: ProofPackage) -> Groth16VerifierPrepared {
     info!("Proof C: {:?}", proof_c);
 
     let verifier: Groth16VerifierPrepared = Groth16VerifierPrepared::new(
-        proof_a,
-        proof_b,
-        proof_c,
-        prepared_public_input,
+        &proof_a,
+        &proof_b,
+        &proof_c,
+        &prepared_public_input,
         Box::new(groth_vk_prepared),
     )
         .unwrap();
@@ -268,8 +173,7 @@ pub fn build_verifier(proof_package: ProofPackage) -> Groth16VerifierPrepared {
 }
 
 pub fn is_less_than_bn254_field_size_be(bytes: &[u8; 32]) -> bool {
-    let bigint = BigUint::from_bytes_be(bytes);
-    bigint < ark_bn254::Fr::MODULUS.into()
+    groth16_verifier::bn254::is_less_than_field_size_be(bytes)
 }
 
 pub fn convert_arkworks_verifying_key_to_solana_verifying_key(
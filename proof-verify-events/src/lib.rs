@@ -0,0 +1,159 @@
+//! Events emitted by the proof-verify program and a decoder for them.
+//!
+//! The program logs each event with `sol_log_data(&[EVENT_TAG, borsh(event)])`,
+//! which shows up in transaction logs as
+//! `Program data: <base64 tag> <base64 event>`. `decode_logs` walks a
+//! transaction's log messages and returns the events logged by one program.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+use thiserror::Error;
+
+/// First field of every event log, so other `Program data:` lines are skipped
+pub const EVENT_TAG: &[u8] = b"proof-verify:event";
+
+const DATA_PREFIX: &str = "Program data: ";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProofEvent {
+    /// A proof (or a whole batch) verified and was recorded in a verification state
+    ProofVerified {
+        circuit_id: [u8; 32],
+        public_inputs_digest: [u8; 32],
+        verifier: Pubkey,
+        slot: u64,
+    },
+    /// A proof failed to verify. `reason` is the `Groth16Error` code.
+    ProofRejected { reason: u32 },
+    /// A leaf was appended to an incremental Merkle tree. Replaying these in
+    /// order rebuilds the tree off-chain.
+    LeafAppended {
+        tree: Pubkey,
+        index: u64,
+        leaf: [u8; 32],
+        root: [u8; 32],
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EventDecodeError {
+    #[error("Invalid base64 in program data")]
+    InvalidBase64,
+    #[error("Malformed event data")]
+    MalformedEvent,
+}
+
+/// Parses one log line. Returns `Ok(None)` for lines that aren't proof-verify
+/// events, including `Program data:` lines logged with a different tag.
+pub fn parse_log_line(line: &str) -> Result<Option<ProofEvent>, EventDecodeError> {
+    let Some(data) = line.strip_prefix(DATA_PREFIX) else {
+        return Ok(None);
+    };
+
+    let mut fields = data.split_whitespace();
+    let tag = match fields.next() {
+        Some(tag) => STANDARD.decode(tag).map_err(|_| EventDecodeError::InvalidBase64)?,
+        None => return Ok(None),
+    };
+    if tag != EVENT_TAG {
+        return Ok(None);
+    }
+
+    let payload = fields.next().ok_or(EventDecodeError::MalformedEvent)?;
+    let payload = STANDARD.decode(payload).map_err(|_| EventDecodeError::InvalidBase64)?;
+    ProofEvent::try_from_slice(&payload)
+        .map(Some)
+        .map_err(|_| EventDecodeError::MalformedEvent)
+}
+
+/// Returns the events logged by `program_id`, in order. Program invocations
+/// are tracked so that events from other programs, or from a copy of
+/// proof-verify deployed at another address, are ignored.
+pub fn decode_logs<S: AsRef<str>>(
+    program_id: &Pubkey,
+    logs: &[S],
+) -> Result<Vec<ProofEvent>, EventDecodeError> {
+    let program_id = program_id.to_string();
+    let mut invocations: Vec<String> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs.iter().map(|line| line.as_ref()) {
+        if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split_whitespace();
+            if let (Some(id), Some(action)) = (words.next(), words.next()) {
+                if action == "invoke" {
+                    invocations.push(id.to_string());
+                    continue;
+                }
+                if action == "success" || action == "failed:" {
+                    invocations.pop();
+                    continue;
+                }
+            }
+        }
+
+        if invocations.last() != Some(&program_id) {
+            continue;
+        }
+        if let Some(event) = parse_log_line(line)? {
+            events.push(event);
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_line(event: &ProofEvent) -> String {
+        format!(
+            "{}{} {}",
+            DATA_PREFIX,
+            STANDARD.encode(EVENT_TAG),
+            STANDARD.encode(borsh::to_vec(event).unwrap())
+        )
+    }
+
+    #[test]
+    fn test_parse_log_line_roundtrip() {
+        let event = ProofEvent::ProofVerified {
+            circuit_id: [1u8; 32],
+            public_inputs_digest: [2u8; 32],
+            verifier: Pubkey::new_unique(),
+            slot: 42,
+        };
+        assert_eq!(parse_log_line(&data_line(&event)), Ok(Some(event)));
+        assert_eq!(parse_log_line("Program log: Proof is valid!"), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_log_line_skips_other_tags() {
+        let line = format!("{}{} {}", DATA_PREFIX, STANDARD.encode(b"other"), STANDARD.encode([1u8]));
+        assert_eq!(parse_log_line(&line), Ok(None));
+
+        let truncated = format!("{}{}", DATA_PREFIX, STANDARD.encode(EVENT_TAG));
+        assert_eq!(parse_log_line(&truncated), Err(EventDecodeError::MalformedEvent));
+    }
+
+    #[test]
+    fn test_decode_logs_only_returns_events_from_program() {
+        let program_id = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let rejected = ProofEvent::ProofRejected { reason: 1 };
+
+        let logs = vec![
+            format!("Program {} invoke [1]", other),
+            data_line(&rejected),
+            format!("Program {} invoke [2]", program_id),
+            data_line(&rejected),
+            format!("Program {} failed: custom program error: 0x1", program_id),
+            data_line(&ProofEvent::ProofRejected { reason: 2 }),
+            format!("Program {} success", other),
+        ];
+
+        assert_eq!(decode_logs(&program_id, &logs), Ok(vec![rejected]));
+    }
+}
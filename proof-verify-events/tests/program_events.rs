@@ -0,0 +1,264 @@
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::Groth16;
+use ark_snark::SNARK;
+use ark_std::rand::thread_rng;
+use borsh::BorshDeserialize;
+use groth16_test_utils::{fr_bytes, g1_bytes, g2_bytes, SquareCircuit};
+use proof_verify_events::{decode_logs, ProofEvent};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use solana_zk_example::{
+    circuit_id_with_ic, find_merkle_tree_address, find_verification_state_address,
+    find_verifying_key_address, process_instruction, public_inputs_digest, BatchProof,
+    Groth16Error, Groth16VerifyingKeyPrepared, IncrementalMerkleTree, ProgramInstruction,
+};
+
+struct Fixture {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    program_id: Pubkey,
+    key_pda: Pubkey,
+    state_pda: Pubkey,
+    circuit_id: [u8; 32],
+    proofs: Vec<BatchProof>,
+}
+
+async fn setup(batch_size: u64) -> Fixture {
+    let rng = &mut thread_rng();
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(SquareCircuit { x: None }, rng).unwrap();
+
+    let proofs: Vec<BatchProof> = (0..batch_size)
+        .map(|i| {
+            let x = Fr::from(3 + i);
+            let proof = Groth16::<Bn254>::prove(&pk, SquareCircuit { x: Some(x) }, rng).unwrap();
+            BatchProof {
+                proof_a: g1_bytes(&-proof.a),
+                proof_b: g2_bytes(&proof.b),
+                proof_c: g1_bytes(&proof.c),
+                public_inputs: vec![fr_bytes(x * x)],
+            }
+        })
+        .collect();
+
+    let verifying_key = Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: g1_bytes(&vk.alpha_g1),
+        vk_beta_g2: g2_bytes(&vk.beta_g2),
+        vk_gamma_g2: g2_bytes(&vk.gamma_g2),
+        vk_delta_g2: g2_bytes(&vk.delta_g2),
+    };
+    let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_bytes).collect();
+    let circuit_id = circuit_id_with_ic(&verifying_key, &vk_ic);
+
+    let program_id = Pubkey::new_unique();
+    let program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let (key_pda, _) = find_verifying_key_address(&program_id, &payer.pubkey(), &circuit_id);
+    let (state_pda, _) = find_verification_state_address(&program_id, &payer.pubkey(), &circuit_id);
+
+    let register = Instruction::new_with_bytes(
+        program_id,
+        &ProgramInstruction::RegisterVerifyingKey { verifying_key, vk_ic }.pack(),
+        vec![
+            AccountMeta::new(key_pda, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    let init = Instruction::new_with_bytes(
+        program_id,
+        &ProgramInstruction::InitVerificationState { circuit_id }.pack(),
+        vec![
+            AccountMeta::new(state_pda, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[register, init],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    Fixture {
+        banks_client,
+        payer,
+        recent_blockhash,
+        program_id,
+        key_pda,
+        state_pda,
+        circuit_id,
+        proofs,
+    }
+}
+
+async fn verify_batch_logs(fixture: &mut Fixture, proofs: Vec<BatchProof>) -> (bool, Vec<String>) {
+    let instruction = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyBatch { proofs }.pack(),
+        vec![
+            AccountMeta::new_readonly(fixture.key_pda, false),
+            AccountMeta::new(fixture.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+        ],
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&fixture.payer.pubkey()),
+        &[&fixture.payer],
+        fixture.recent_blockhash,
+    );
+    let result = fixture
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    (result.result.is_ok(), result.metadata.unwrap().log_messages)
+}
+
+#[tokio::test]
+async fn test_verified_batch_emits_proof_verified() {
+    let mut fixture = setup(2).await;
+    let proofs = fixture.proofs.clone();
+    let (succeeded, logs) = verify_batch_logs(&mut fixture, proofs.clone()).await;
+    assert!(succeeded, "{:#?}", logs);
+
+    let inputs: Vec<[u8; 32]> = proofs.iter().flat_map(|p| p.public_inputs.clone()).collect();
+    let events = decode_logs(&fixture.program_id, &logs).unwrap();
+    assert_eq!(events.len(), 1);
+    match &events[0] {
+        ProofEvent::ProofVerified {
+            circuit_id,
+            public_inputs_digest: digest,
+            verifier,
+            ..
+        } => {
+            assert_eq!(*circuit_id, fixture.circuit_id);
+            assert_eq!(*digest, public_inputs_digest(&inputs));
+            assert_eq!(*verifier, fixture.payer.pubkey());
+        }
+        event => panic!("expected ProofVerified, got {:?}", event),
+    }
+}
+
+#[tokio::test]
+async fn test_rejected_batch_emits_proof_rejected() {
+    let mut fixture = setup(2).await;
+
+    let mut proofs = fixture.proofs.clone();
+    proofs[1].public_inputs = proofs[0].public_inputs.clone();
+    let (succeeded, logs) = verify_batch_logs(&mut fixture, proofs).await;
+    assert!(!succeeded);
+    assert_eq!(
+        decode_logs(&fixture.program_id, &logs).unwrap(),
+        vec![ProofEvent::ProofRejected {
+            reason: Groth16Error::ProofVerificationFailed.code()
+        }]
+    );
+
+    let (succeeded, logs) = verify_batch_logs(&mut fixture, vec![]).await;
+    assert!(!succeeded);
+    assert_eq!(
+        decode_logs(&fixture.program_id, &logs).unwrap(),
+        vec![ProofEvent::ProofRejected {
+            reason: Groth16Error::EmptyBatch.code()
+        }]
+    );
+
+    // Events from a different program id are not attributed to proof-verify
+    assert!(decode_logs(&Pubkey::new_unique(), &logs).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_append_leaf_emits_leaf_appended() {
+    let program_id = Pubkey::new_unique();
+    let program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // A separate authority, as when another program's PDA owns the tree
+    let authority = Keypair::new();
+    let tree_id = [9u8; 32];
+    let (tree_pda, _) = find_merkle_tree_address(&program_id, &authority.pubkey(), &tree_id);
+
+    let init = Instruction::new_with_bytes(
+        program_id,
+        &ProgramInstruction::InitMerkleTree { tree_id, depth: 8 }.pack(),
+        vec![
+            AccountMeta::new(tree_pda, false),
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    let leaves = [fr_bytes(Fr::from(11u64)), fr_bytes(Fr::from(12u64))];
+    let appends: Vec<Instruction> = leaves
+        .iter()
+        .map(|leaf| {
+            Instruction::new_with_bytes(
+                program_id,
+                &ProgramInstruction::AppendLeaf { leaf: *leaf }.pack(),
+                vec![
+                    AccountMeta::new(tree_pda, false),
+                    AccountMeta::new_readonly(authority.pubkey(), true),
+                ],
+            )
+        })
+        .collect();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[&[init][..], &appends[..]].concat(),
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    let result = banks_client.process_transaction_with_metadata(transaction).await.unwrap();
+    assert!(result.result.is_ok(), "{:?}", result.result);
+    let logs = result.metadata.unwrap().log_messages;
+
+    // The events alone rebuild the tree's leaves and roots
+    let account = banks_client.get_account(tree_pda).await.unwrap().unwrap();
+    let tree = IncrementalMerkleTree::deserialize(&mut &account.data[..]).unwrap();
+    let mut expected = IncrementalMerkleTree::new(authority.pubkey(), tree_id, tree.bump, 8).unwrap();
+    let events: Vec<ProofEvent> = leaves
+        .iter()
+        .map(|leaf| {
+            let index = expected.append(*leaf).unwrap();
+            ProofEvent::LeafAppended {
+                tree: tree_pda,
+                index,
+                leaf: *leaf,
+                root: expected.root(),
+            }
+        })
+        .collect();
+    assert_eq!(decode_logs(&program_id, &logs).unwrap(), events);
+    assert_eq!(tree, expected);
+
+    // Only the authority appends
+    let append = Instruction::new_with_bytes(
+        program_id,
+        &ProgramInstruction::AppendLeaf { leaf: leaves[0] }.pack(),
+        vec![
+            AccountMeta::new(tree_pda, false),
+            AccountMeta::new_readonly(payer.pubkey(), true),
+        ],
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[append],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+}
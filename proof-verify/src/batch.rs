@@ -0,0 +1,127 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use groth16_verifier::bn254::{g1_add, g1_mul, pairing_check};
+use solana_program::hash::hashv;
+
+use crate::{prepare_public_inputs, Groth16Error, Groth16VerifyingKeyPrepared};
+
+/// Upper bound on proofs per `VerifyBatch`, well above what fits in one transaction
+pub const MAX_BATCH_SIZE: usize = 16;
+
+// Random coefficients are 128 bits, stored as 32-byte big-endian scalars
+const RANDOMNESS_BYTES: usize = 16;
+
+/// One proof in a `VerifyBatch` instruction. `proof_a` is negated, as in
+/// `Groth16VerifierPrepared`, and the public inputs are 32-byte big-endian
+/// field elements that are prepared on-chain against the registered key.
+#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct BatchProof {
+    pub proof_a: [u8; 64],
+    pub proof_b: [u8; 128],
+    pub proof_c: [u8; 64],
+    pub public_inputs: Vec<[u8; 32]>,
+}
+
+/// Derives one 128-bit coefficient per proof from a hash of the whole batch.
+/// Every proof and input feeds the seed, so a prover can't pick proofs that
+/// cancel out under coefficients they already know.
+pub fn batch_randomness(circuit_id: &[u8; 32], proofs: &[BatchProof]) -> Vec<[u8; 32]> {
+    let mut seed_parts: Vec<&[u8]> = vec![b"proof-verify:batch", circuit_id];
+    for proof in proofs {
+        seed_parts.push(&proof.proof_a);
+        seed_parts.push(&proof.proof_b);
+        seed_parts.push(&proof.proof_c);
+        seed_parts.extend(proof.public_inputs.iter().map(|input| input.as_slice()));
+    }
+    let seed = hashv(&seed_parts).to_bytes();
+
+    (0..proofs.len() as u32)
+        .map(|i| {
+            let digest = hashv(&[&seed, &i.to_le_bytes()]).to_bytes();
+            let mut scalar = [0u8; 32];
+            scalar[32 - RANDOMNESS_BYTES..].copy_from_slice(&digest[..RANDOMNESS_BYTES]);
+            // A zero coefficient would drop the proof from the check
+            if scalar.iter().all(|b| *b == 0) {
+                scalar[31] = 1;
+            }
+            scalar
+        })
+        .collect()
+}
+
+/// Adds two 32-byte big-endian integers. Used to sum 128-bit coefficients,
+/// which can't come near the field modulus for `MAX_BATCH_SIZE` proofs.
+pub fn add_scalars_be(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut carry = 0u16;
+    for i in (0..32).rev() {
+        let sum = a[i] as u16 + b[i] as u16 + carry;
+        out[i] = sum as u8;
+        carry = sum >> 8;
+    }
+    out
+}
+
+/// Verifies all `proofs` with a single multi-pairing.
+///
+/// Each proof satisfies `e(-A, B) e(L, gamma) e(C, delta) e(alpha, beta) = 1`.
+/// Scaling proof `i` by `r_i` and multiplying the equations together gives
+///
+/// `prod e(-r_i A_i, B_i) * e(sum r_i L_i, gamma) * e(sum r_i C_i, delta) * e((sum r_i) alpha, beta) = 1`
+///
+/// which costs N + 3 pairings instead of 4N.
+pub fn verify_batch(
+    verifying_key: &Groth16VerifyingKeyPrepared,
+    vk_ic: &[[u8; 64]],
+    circuit_id: &[u8; 32],
+    proofs: &[BatchProof],
+) -> Result<(), Groth16Error> {
+    if proofs.is_empty() {
+        return Err(Groth16Error::EmptyBatch);
+    }
+    if proofs.len() > MAX_BATCH_SIZE {
+        return Err(Groth16Error::BatchTooLarge);
+    }
+
+    let randomness = batch_randomness(circuit_id, proofs);
+
+    let mut pairing_input = Vec::with_capacity((proofs.len() + 3) * 192);
+    let mut sum_inputs: Option<[u8; 64]> = None;
+    let mut sum_c: Option<[u8; 64]> = None;
+    let mut sum_r = [0u8; 32];
+
+    for (proof, r) in proofs.iter().zip(randomness.iter()) {
+        let prepared = prepare_public_inputs(vk_ic, &proof.public_inputs)?;
+
+        pairing_input.extend_from_slice(&g1_mul(&proof.proof_a, r)?);
+        pairing_input.extend_from_slice(&proof.proof_b);
+
+        let r_inputs = g1_mul(&prepared, r)?;
+        sum_inputs = Some(match sum_inputs {
+            Some(acc) => g1_add(&acc, &r_inputs)?,
+            None => r_inputs,
+        });
+        let r_c = g1_mul(&proof.proof_c, r)?;
+        sum_c = Some(match sum_c {
+            Some(acc) => g1_add(&acc, &r_c)?,
+            None => r_c,
+        });
+        sum_r = add_scalars_be(&sum_r, r);
+    }
+
+    // Non-empty batch, so both sums are set
+    let sum_inputs = sum_inputs.ok_or(Groth16Error::EmptyBatch)?;
+    let sum_c = sum_c.ok_or(Groth16Error::EmptyBatch)?;
+
+    pairing_input.extend_from_slice(&sum_inputs);
+    pairing_input.extend_from_slice(&verifying_key.vk_gamma_g2);
+    pairing_input.extend_from_slice(&sum_c);
+    pairing_input.extend_from_slice(&verifying_key.vk_delta_g2);
+    pairing_input.extend_from_slice(&g1_mul(&verifying_key.vk_alpha_g1, &sum_r)?);
+    pairing_input.extend_from_slice(&verifying_key.vk_beta_g2);
+
+    if !pairing_check(&pairing_input)? {
+        return Err(Groth16Error::ProofVerificationFailed);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,3 @@
+// Shared with clients through the verifier crate, so both sides agree on the
+// codes returned as `ProgramError::Custom`
+pub use groth16_verifier::Groth16Error;
@@ -0,0 +1,19 @@
+use solana_program::{log::sol_log_data, program_error::ProgramError};
+
+use crate::Groth16Error;
+
+pub use proof_verify_events::{ProofEvent, EVENT_TAG};
+
+/// Logs `event` as `Program data: <tag> <borsh event>` for indexers
+pub fn emit(event: &ProofEvent) {
+    let data = borsh::to_vec(event).expect("event serialization");
+    sol_log_data(&[EVENT_TAG, &data]);
+}
+
+/// Logs a `ProofRejected` event for `error` and returns the error to fail the
+/// instruction with. Logs are kept on failed transactions, so indexers still
+/// see the event.
+pub fn reject(error: Groth16Error) -> ProgramError {
+    emit(&ProofEvent::ProofRejected { reason: error.code() });
+    error.into()
+}
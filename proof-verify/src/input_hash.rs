@@ -0,0 +1,78 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use groth16_verifier::bn254::is_less_than_field_size_be;
+use solana_poseidon::{Endianness, Parameters};
+use solana_program::hash::hashv;
+
+use crate::Groth16Error;
+
+/// Most inputs a single Poseidon syscall accepts
+pub const POSEIDON_MAX_INPUTS: usize = 12;
+
+/// Most logical inputs `hash_public_inputs` takes. More would not fit in a
+/// `VerifyHashedInputs` transaction anyway.
+pub const MAX_HASHED_INPUTS: usize = 16;
+
+/// How a list of logical public inputs is committed to the single public
+/// input of a hashed-input circuit. Inputs are 32-byte big-endian field
+/// elements, and the result is one as well.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputHashScheme {
+    /// SHA-256 over the concatenated inputs, keeping the low 253 bits so the
+    /// result is always below the field modulus. The top three bits of the
+    /// digest are cleared, which is not the same as reducing it mod `r`, so
+    /// circuits must truncate the same way.
+    Sha256,
+    /// Circom-compatible Poseidon over BN254. Up to `POSEIDON_MAX_INPUTS`
+    /// inputs are hashed at once; longer lists are chained, hashing the
+    /// running result with the next `POSEIDON_MAX_INPUTS - 1` inputs.
+    Poseidon,
+}
+
+/// Commits `inputs` to one field element with `scheme`. Every input must be
+/// a canonical field element, so each input list has exactly one hash. Takes
+/// 1 to `MAX_HASHED_INPUTS` inputs.
+pub fn hash_public_inputs(
+    scheme: InputHashScheme,
+    inputs: &[[u8; 32]],
+) -> Result<[u8; 32], Groth16Error> {
+    if inputs.is_empty() || inputs.len() > MAX_HASHED_INPUTS {
+        return Err(Groth16Error::InvalidPublicInputsLength);
+    }
+    if !inputs.iter().all(is_less_than_field_size_be) {
+        return Err(Groth16Error::PublicInputGreaterThenFieldSize);
+    }
+
+    match scheme {
+        InputHashScheme::Sha256 => Ok(sha256_to_field(inputs)),
+        InputHashScheme::Poseidon => poseidon_chain(inputs),
+    }
+}
+
+pub(crate) fn sha256_to_field(inputs: &[[u8; 32]]) -> [u8; 32] {
+    let parts: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+    let mut digest = hashv(&parts).to_bytes();
+    // 2^253 < r, so the low 253 bits are always a canonical field element
+    digest[0] &= 0x1f;
+    digest
+}
+
+fn poseidon_chain(inputs: &[[u8; 32]]) -> Result<[u8; 32], Groth16Error> {
+    let (first, rest) = inputs.split_at(inputs.len().min(POSEIDON_MAX_INPUTS));
+    let parts: Vec<&[u8]> = first.iter().map(|input| input.as_slice()).collect();
+    let mut acc = poseidon(&parts)?;
+
+    for chunk in rest.chunks(POSEIDON_MAX_INPUTS - 1) {
+        let mut parts: Vec<&[u8]> = vec![&acc];
+        parts.extend(chunk.iter().map(|input| input.as_slice()));
+        let next = poseidon(&parts)?;
+        acc = next;
+    }
+
+    Ok(acc)
+}
+
+pub(crate) fn poseidon(parts: &[&[u8]]) -> Result<[u8; 32], Groth16Error> {
+    solana_poseidon::hashv(Parameters::Bn254X5, Endianness::BigEndian, parts)
+        .map(|hash| hash.to_bytes())
+        .map_err(|_| Groth16Error::InputHashFailed)
+}
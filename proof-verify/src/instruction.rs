@@ -0,0 +1,167 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    BatchProof, Groth16Error, Groth16VerifierPrepared, Groth16VerifyingKeyPrepared, InputHashScheme,
+};
+
+/// First byte of versioned instruction data. v0 data is a bare Borsh
+/// `ProgramInstruction`, whose first byte is a discriminator well below this.
+pub const VERSIONED_INSTRUCTION_TAG: u8 = 0xff;
+
+/// Layout version written by `ProgramInstruction::pack`. Bump it whenever the
+/// Borsh layout of an existing variant or its payload types changes, and keep
+/// decoding the old versions in `unpack`.
+pub const INSTRUCTION_VERSION: u8 = 1;
+
+/// Instructions understood by proof-verify.
+///
+/// The variant order is the instruction discriminator: never reorder
+/// variants, only append new ones. Build instruction data with `pack` and
+/// parse it with `unpack`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProgramInstruction {
+    /// Accounts: `verifying_key_account`, `[writable] verification_state`, `[signer] verifier`
+    ///
+    /// `proof_data` must use the registered key. The program prepares
+    /// `public_inputs` against the registered `vk_ic` and requires the result
+    /// to match `proof_data`'s prepared input, so their digest is recorded.
+    VerifyProof {
+        proof_data: Groth16VerifierPrepared,
+        public_inputs: Vec<[u8; 32]>,
+    },
+    /// Accounts: `verifying_key_account`, `[writable] verification_state`, `[signer] verifier`, `account_to_check`
+    ///
+    /// Checks and records `public_inputs` like `VerifyProof`.
+    VerifyProofWithBalance {
+        proof_data: Groth16VerifierPrepared,
+        public_inputs: Vec<[u8; 32]>,
+        required_balance: u64,
+        account_to_check: Pubkey,
+    },
+    /// Accounts: `[writable] verification_state`, `[writable, signer] authority`, `system_program`
+    InitVerificationState {
+        circuit_id: [u8; 32],
+    },
+    /// Accounts: `[writable] verification_state`, `[signer] authority`, `[writable] destination`
+    CloseVerificationState,
+    /// Accounts: `verifying_key_account`, `[writable] verification_state`, `[signer] verifier`, `token_account`
+    ///
+    /// Checks and records `public_inputs` like `VerifyProof`, whichever the threshold.
+    VerifyProofWithTokenBalance {
+        proof_data: Groth16VerifierPrepared,
+        public_inputs: Vec<[u8; 32]>,
+        mint: Pubkey,
+        threshold: BalanceThreshold,
+    },
+    /// Accounts: `[writable] verifying_key_account`, `[writable, signer] authority`, `system_program`
+    RegisterVerifyingKey {
+        verifying_key: Groth16VerifyingKeyPrepared,
+        vk_ic: Vec<[u8; 64]>,
+    },
+    /// Accounts: `verifying_key_account`, `[writable] verification_state`, `[signer] verifier`
+    VerifyBatch {
+        proofs: Vec<BatchProof>,
+    },
+    /// Accounts: `verifying_key_account`, `[writable] verification_state`, `[signer] verifier`
+    ///
+    /// `proof.public_inputs` are the logical inputs, at most
+    /// `MAX_HASHED_INPUTS`. The program hashes them with `scheme` into the
+    /// circuit's single public input.
+    VerifyHashedInputs {
+        proof: BatchProof,
+        scheme: InputHashScheme,
+    },
+    /// Accounts: `[writable] root_registry`, `[writable, signer] authority`, `system_program`
+    InitRootRegistry {
+        registry_id: [u8; 32],
+        depth: u8,
+        root: [u8; 32],
+    },
+    /// Accounts: `[writable] root_registry`, `[signer] authority`
+    UpdateRoot {
+        root: [u8; 32],
+    },
+    /// Accounts: `verifying_key_account`, `root_registry`, `[writable] verification_state`, `[signer] verifier`
+    ///
+    /// `proof.public_inputs` must be `[root, member_context(verifier)]`, with
+    /// `root` one of the registry's recent roots. An `IncrementalMerkleTree`
+    /// can stand in for the registry. Either must have the verification
+    /// state's authority as its own.
+    VerifyMembership {
+        proof: BatchProof,
+    },
+    /// Accounts: `[writable] merkle_tree`, `[signer] authority`, `[writable, signer] payer`, `system_program`
+    ///
+    /// The authority, the only account allowed to append, may be another
+    /// program's PDA, so the rent comes from a separate payer.
+    InitMerkleTree {
+        tree_id: [u8; 32],
+        depth: u8,
+    },
+    /// Accounts: `[writable] merkle_tree`, `[signer] authority`
+    ///
+    /// Sets the leaf index as return data, little-endian `u64`.
+    AppendLeaf {
+        leaf: [u8; 32],
+    },
+}
+
+/// Where `VerifyProofWithTokenBalance` takes its required balance from
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum BalanceThreshold {
+    /// Plaintext argument chosen by the caller
+    Plain { required_balance: u64 },
+    /// `public_inputs[index]`, proven by the Groth16 proof. `vk_ic` must be
+    /// the registered key's.
+    PublicInput { index: u8, vk_ic: Vec<[u8; 64]> },
+}
+
+impl ProgramInstruction {
+    /// Stable discriminator, equal to the Borsh variant index
+    pub fn discriminator(&self) -> u8 {
+        match self {
+            ProgramInstruction::VerifyProof { .. } => 0,
+            ProgramInstruction::VerifyProofWithBalance { .. } => 1,
+            ProgramInstruction::InitVerificationState { .. } => 2,
+            ProgramInstruction::CloseVerificationState => 3,
+            ProgramInstruction::VerifyProofWithTokenBalance { .. } => 4,
+            ProgramInstruction::RegisterVerifyingKey { .. } => 5,
+            ProgramInstruction::VerifyBatch { .. } => 6,
+            ProgramInstruction::VerifyHashedInputs { .. } => 7,
+            ProgramInstruction::InitRootRegistry { .. } => 8,
+            ProgramInstruction::UpdateRoot { .. } => 9,
+            ProgramInstruction::VerifyMembership { .. } => 10,
+            ProgramInstruction::InitMerkleTree { .. } => 11,
+            ProgramInstruction::AppendLeaf { .. } => 12,
+        }
+    }
+
+    /// Encodes the instruction as
+    /// `[VERSIONED_INSTRUCTION_TAG, INSTRUCTION_VERSION, discriminator, fields...]`
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = vec![VERSIONED_INSTRUCTION_TAG, INSTRUCTION_VERSION];
+        data.extend(borsh::to_vec(self).expect("instruction serialization"));
+        data
+    }
+
+    /// Decodes versioned instruction data, or bare v0 data from older clients
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        match data {
+            [VERSIONED_INSTRUCTION_TAG, version, payload @ ..] => match *version {
+                // v1 only added the envelope, so its payload is the v0 layout
+                1 => Self::decode_v0(payload),
+                version => {
+                    msg!("Unsupported instruction version {}", version);
+                    Err(Groth16Error::UnsupportedInstructionVersion.into())
+                }
+            },
+            [VERSIONED_INSTRUCTION_TAG] | [] => Err(ProgramError::InvalidInstructionData),
+            _ => Self::decode_v0(data),
+        }
+    }
+
+    fn decode_v0(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+}
@@ -1,258 +1,800 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshSerialize;
 use solana_program::{
-    account_info::AccountInfo, 
+    account_info::{next_account_info, AccountInfo}, 
     entrypoint, 
     entrypoint::ProgramResult, 
-    instruction::Instruction,
     msg, 
-    program::invoke, 
+    program::{invoke_signed, set_return_data}, 
     program_error::ProgramError, 
     pubkey::Pubkey,
     clock::Clock,
+    rent::Rent,
     sysvar::Sysvar,
 };
-use thiserror::Error;
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+
+pub mod batch;
+pub mod error;
+pub mod events;
+pub mod input_hash;
+pub mod instruction;
+pub mod merkle;
+pub mod state;
+pub mod token;
+
+pub use groth16_verifier::{
+    prepare_public_inputs, prepare_public_inputs_unchecked, Groth16VerifierPrepared,
+    Groth16VerifyingKeyPrepared,
+};
 
-// BN254 alt_bn128 pairing syscall program ID
-pub const ALT_BN128_PAIRING: Pubkey = Pubkey::new_from_array([
-    2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-]);
+pub use batch::*;
+pub use error::*;
+pub use events::*;
+pub use input_hash::*;
+pub use instruction::*;
+pub use merkle::*;
+pub use state::*;
+pub use token::*;
 
 // Program entrypoint
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
-#[derive(BorshSerialize, BorshDeserialize)]
-pub enum ProgramInstruction {
-    VerifyProof(Groth16VerifierPrepared),
-    VerifyProofWithBalance {
-        proof_data: Groth16VerifierPrepared,
-        required_balance: u64,
-        account_to_check: Pubkey,
-    },
-}
-
 pub fn process_instruction(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let instruction = ProgramInstruction::try_from_slice(instruction_data)?;
+    let instruction = ProgramInstruction::unpack(instruction_data)?;
     match instruction {
-        ProgramInstruction::VerifyProof(proof_package) => verify_proof(accounts, proof_package),
+        ProgramInstruction::VerifyProof {
+            proof_data,
+            public_inputs,
+        } => verify_proof(program_id, accounts, proof_data, &public_inputs),
         ProgramInstruction::VerifyProofWithBalance {
             proof_data,
+            public_inputs,
             required_balance,
             account_to_check,
-        } => verify_proof_with_balance(accounts, proof_data, required_balance, account_to_check),
+        } => verify_proof_with_balance(
+            program_id,
+            accounts,
+            proof_data,
+            &public_inputs,
+            required_balance,
+            account_to_check,
+        ),
+        ProgramInstruction::InitVerificationState { circuit_id } => {
+            init_verification_state(program_id, accounts, circuit_id)
+        }
+        ProgramInstruction::CloseVerificationState => {
+            close_verification_state(program_id, accounts)
+        }
+        ProgramInstruction::VerifyProofWithTokenBalance {
+            proof_data,
+            public_inputs,
+            mint,
+            threshold,
+        } => verify_proof_with_token_balance(
+            program_id,
+            accounts,
+            proof_data,
+            &public_inputs,
+            &mint,
+            threshold,
+        ),
+        ProgramInstruction::RegisterVerifyingKey {
+            verifying_key,
+            vk_ic,
+        } => register_verifying_key(program_id, accounts, verifying_key, vk_ic),
+        ProgramInstruction::VerifyBatch { proofs } => {
+            verify_batch_instruction(program_id, accounts, &proofs)
+        }
+        ProgramInstruction::VerifyHashedInputs { proof, scheme } => {
+            verify_hashed_inputs(program_id, accounts, proof, scheme)
+        }
+        ProgramInstruction::InitRootRegistry {
+            registry_id,
+            depth,
+            root,
+        } => init_root_registry(program_id, accounts, registry_id, depth, root),
+        ProgramInstruction::UpdateRoot { root } => update_root(program_id, accounts, root),
+        ProgramInstruction::VerifyMembership { proof } => {
+            verify_membership(program_id, accounts, proof)
+        }
+        ProgramInstruction::InitMerkleTree { tree_id, depth } => {
+            init_merkle_tree(program_id, accounts, tree_id, depth)
+        }
+        ProgramInstruction::AppendLeaf { leaf } => append_leaf(program_id, accounts, leaf),
     }
 }
 
-fn verify_proof(accounts: &[AccountInfo], mut groth16_verifier_prepared: Groth16VerifierPrepared) -> ProgramResult {
-    let result = groth16_verifier_prepared.verify(accounts).map_err(|e| {
+/// Checks that `groth16_verifier_prepared` was made against the registered
+/// key, and that its prepared input is `public_inputs` prepared on-chain
+/// against the registered `vk_ic`. Only then can the raw inputs be recorded.
+fn check_prepared_public_inputs(
+    registered: &RegisteredVerifyingKey,
+    groth16_verifier_prepared: &Groth16VerifierPrepared,
+    public_inputs: &[[u8; 32]],
+) -> ProgramResult {
+    if *groth16_verifier_prepared.verifying_key != registered.verifying_key {
+        msg!("Verifying key does not match the registered key");
+        return Err(reject(Groth16Error::CircuitMismatch));
+    }
+    let prepared = prepare_public_inputs(&registered.vk_ic, public_inputs).map_err(reject)?;
+    if prepared != groth16_verifier_prepared.prepared_public_inputs {
+        msg!("Public inputs do not match the prepared input");
+        return Err(reject(Groth16Error::PublicInputsMismatch));
+    }
+    Ok(())
+}
+
+fn verify_proof(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    groth16_verifier_prepared: Groth16VerifierPrepared,
+    public_inputs: &[[u8; 32]],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+    check_prepared_public_inputs(&registered, &groth16_verifier_prepared, public_inputs)?;
+
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
         msg!("Verification error: {:?}", e);
-        ProgramError::InvalidAccountData
+        reject(e)
     })?;
 
     if result {
         msg!("Proof is valid! Inputs verified.");
-        
-        // Update state if a state account is provided
-        if accounts.is_empty() {
-            return Err(ProgramError::NotEnoughAccountKeys);
-        }
-        update_on_chain_state_with_amount(
-            &accounts[0], 
-            &groth16_verifier_prepared.prepared_public_inputs
-        )?;
-        Ok(())
+        record_verification(
+            program_id,
+            state_account,
+            verifier,
+            registered.circuit_id,
+            Some(public_inputs),
+            1,
+        )
     } else {
         msg!("Proof is invalid!");
-        Err(ProgramError::InvalidAccountData)
+        Err(reject(Groth16Error::ProofVerificationFailed))
     }
 }
 
 fn verify_proof_with_balance(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
-    mut groth16_verifier_prepared: Groth16VerifierPrepared,
+    groth16_verifier_prepared: Groth16VerifierPrepared,
+    public_inputs: &[[u8; 32]],
     required_balance: u64,
     account_to_check: Pubkey,
 ) -> ProgramResult {
-    let result = groth16_verifier_prepared.verify(accounts).map_err(|e| {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+    check_prepared_public_inputs(&registered, &groth16_verifier_prepared, public_inputs)?;
+
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
         msg!("Verification error: {:?}", e);
-        ProgramError::InvalidAccountData
+        reject(e)
     })?;
 
     if result {
         msg!("Proof is valid! Inputs verified.");
 
         let account_to_check_info = accounts.iter().find(|a| a.key == &account_to_check)
-            .ok_or(ProgramError::InvalidAccountData)?;
+            .ok_or(Groth16Error::AccountToCheckMissing)?;
 
         let account_balance_rc = account_to_check_info.lamports.clone();
         let account_balance: u64 = **account_balance_rc.borrow();
 
         if account_balance >= required_balance {
             msg!("Account balance is sufficient.");
-            
-            // Update state if a state account is provided (first account)
-            if !accounts.is_empty() {
-                update_on_chain_state_with_amount(
-                    &accounts[0], 
-                    &groth16_verifier_prepared.prepared_public_inputs
-                )?;
-            } else {
-                update_on_chain_state()?;
-            }
-            Ok(())
+            record_verification(
+                program_id,
+                state_account,
+                verifier,
+                registered.circuit_id,
+                Some(public_inputs),
+                1,
+            )
         } else {
             msg!("Account balance is insufficient.");
             Err(ProgramError::InsufficientFunds)
         }
     } else {
         msg!("Proof is invalid!");
-        Err(ProgramError::InvalidAccountData)
+        Err(reject(Groth16Error::ProofVerificationFailed))
     }
 }
 
-fn update_on_chain_state() -> ProgramResult {
-    msg!("Updating on-chain state...");
+fn verify_proof_with_token_balance(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    groth16_verifier_prepared: Groth16VerifierPrepared,
+    public_inputs: &[[u8; 32]],
+    mint: &Pubkey,
+    threshold: BalanceThreshold,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+    let token_account = next_account_info(account_info_iter)?;
+
+    // Both thresholds prepare the inputs on-chain against the registered key,
+    // so both record them under its circuit id
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+    check_prepared_public_inputs(&registered, &groth16_verifier_prepared, public_inputs)?;
+    let required_balance = match &threshold {
+        BalanceThreshold::Plain { required_balance } => *required_balance,
+        BalanceThreshold::PublicInput { index, vk_ic } => {
+            if *vk_ic != registered.vk_ic {
+                msg!("Threshold vk_ic does not match the registered key");
+                return Err(reject(Groth16Error::CircuitMismatch));
+            }
+            let input = public_inputs
+                .get(*index as usize)
+                .ok_or(Groth16Error::InvalidThresholdInput)?;
+            threshold_from_public_input(input)?
+        }
+    };
+
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
+        msg!("Verification error: {:?}", e);
+        reject(e)
+    })?;
+
+    if result {
+        msg!("Proof is valid! Inputs verified.");
+
+        TokenAccountView::load(token_account)?.check_balance(mint, verifier.key, required_balance)?;
+        msg!("Token balance is sufficient.");
+
+        record_verification(
+            program_id,
+            state_account,
+            verifier,
+            registered.circuit_id,
+            Some(public_inputs),
+            1,
+        )
+    } else {
+        msg!("Proof is invalid!");
+        Err(reject(Groth16Error::ProofVerificationFailed))
+    }
+}
+
+fn register_verifying_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    verifying_key: Groth16VerifyingKeyPrepared,
+    vk_ic: Vec<[u8; 64]>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if vk_ic.is_empty() {
+        return Err(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs.into());
+    }
+
+    let circuit_id = circuit_id_with_ic(&verifying_key, &vk_ic);
+    let (expected, bump) = find_verifying_key_address(program_id, authority.key, &circuit_id);
+    if expected != *key_account.key {
+        msg!("Verifying key address does not match its seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !key_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let space = RegisteredVerifyingKey::space(vk_ic.len() - 1);
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            key_account.key,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            program_id,
+        ),
+        &[
+            authority.clone(),
+            key_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[
+            VERIFYING_KEY_SEED,
+            authority.key.as_ref(),
+            circuit_id.as_ref(),
+            &[bump],
+        ]],
+    )?;
+
+    let registered = RegisteredVerifyingKey {
+        is_initialized: true,
+        authority: *authority.key,
+        circuit_id,
+        bump,
+        verifying_key,
+        vk_ic,
+    };
+    registered.serialize(&mut &mut key_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Verifying key registered for {} public inputs", registered.nr_public_inputs());
     Ok(())
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct Groth16VerifyingKeyPrepared {
-    pub vk_alpha_g1: [u8; 64],
-    pub vk_beta_g2: [u8; 128],
-    pub vk_gamma_g2: [u8; 128],
-    pub vk_delta_g2: [u8; 128],
+fn verify_batch_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proofs: &[BatchProof],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+
+    verify_batch(
+        &registered.verifying_key,
+        &registered.vk_ic,
+        &registered.circuit_id,
+        proofs,
+    )
+    .map_err(|e| {
+        msg!("Batch verification error: {:?}", e);
+        reject(e)
+    })?;
+    msg!("Batch of {} proofs is valid!", proofs.len());
+
+    let public_inputs: Vec<[u8; 32]> = proofs
+        .iter()
+        .flat_map(|proof| proof.public_inputs.iter().copied())
+        .collect();
+    record_verification(
+        program_id,
+        state_account,
+        verifier,
+        registered.circuit_id,
+        Some(&public_inputs),
+        proofs.len() as u64,
+    )
+}
+
+fn verify_hashed_inputs(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: BatchProof,
+    scheme: InputHashScheme,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+
+    // The key must have a single IC term past the constant one, for the hash
+    let input_hash = hash_public_inputs(scheme, &proof.public_inputs).map_err(reject)?;
+    let prepared_public_inputs =
+        prepare_public_inputs(&registered.vk_ic, &[input_hash]).map_err(reject)?;
+
+    let groth16_verifier_prepared = Groth16VerifierPrepared {
+        proof_a: proof.proof_a,
+        proof_b: proof.proof_b,
+        proof_c: proof.proof_c,
+        prepared_public_inputs,
+        verifying_key: Box::new(registered.verifying_key),
+    };
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
+        msg!("Verification error: {:?}", e);
+        reject(e)
+    })?;
+    if !result {
+        msg!("Proof is invalid!");
+        return Err(reject(Groth16Error::ProofVerificationFailed));
+    }
+    msg!("Proof over {} hashed inputs is valid!", proof.public_inputs.len());
+
+    record_verification(
+        program_id,
+        state_account,
+        verifier,
+        registered.circuit_id,
+        Some(&proof.public_inputs),
+        1,
+    )
+}
+
+fn init_root_registry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    registry_id: [u8; 32],
+    depth: u8,
+    root: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if depth == 0 || depth > MAX_MERKLE_DEPTH {
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_merkle_root(&root)?;
+
+    let (expected, bump) = find_root_registry_address(program_id, authority.key, &registry_id);
+    if expected != *registry_account.key {
+        msg!("Root registry address does not match its seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !registry_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            registry_account.key,
+            Rent::get()?.minimum_balance(RootRegistry::LEN),
+            RootRegistry::LEN as u64,
+            program_id,
+        ),
+        &[
+            authority.clone(),
+            registry_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[
+            ROOT_REGISTRY_SEED,
+            authority.key.as_ref(),
+            registry_id.as_ref(),
+            &[bump],
+        ]],
+    )?;
+
+    RootRegistry::new(*authority.key, registry_id, bump, depth, root).store(registry_account)?;
+
+    msg!("Root registry initialized for a depth {} tree", depth);
+    Ok(())
 }
 
-/// Verification State - Tracks proof verification history
-#[derive(BorshSerialize, BorshDeserialize)]
-pub struct VerificationState {
-    pub total_verifications: u64,
-    pub last_amount: [u8; 32],  // Last verified tokens_asked value
-    pub last_timestamp: i64,
+fn update_root(program_id: &Pubkey, accounts: &[AccountInfo], root: [u8; 32]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+
+    let mut registry = RootRegistry::load(program_id, registry_account)?;
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if registry.authority != *authority.key {
+        msg!("Only the registry authority can publish roots");
+        return Err(ProgramError::IllegalOwner);
+    }
+    check_merkle_root(&root)?;
+
+    registry.history.push(root);
+    registry.store(registry_account)?;
+
+    msg!("Root #{} published", registry.history.current_index);
+    Ok(())
 }
 
-impl VerificationState {
-    pub const LEN: usize = 8 + 32 + 8;
+fn verify_membership(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: BatchProof,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let key_account = next_account_info(account_info_iter)?;
+    let registry_account = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier = next_account_info(account_info_iter)?;
+
+    let registered = RegisteredVerifyingKey::load(program_id, key_account)?;
+    let (root_authority, history) = load_root_history(program_id, registry_account)?;
+
+    // Anyone can create a registry holding a root of their choice, so only
+    // the roots published by the state's own authority count
+    let state = VerificationState::load(program_id, state_account)?;
+    if root_authority != state.authority {
+        msg!("Root registry is not published by the verification state's authority");
+        return Err(reject(Groth16Error::RootAuthorityMismatch));
+    }
+
+    let [root, context] = proof.public_inputs.as_slice() else {
+        return Err(reject(Groth16Error::InvalidPublicInputsLength));
+    };
+    if !history.contains(root) {
+        msg!("Proof references a root the registry doesn't know");
+        return Err(reject(Groth16Error::UnknownMerkleRoot));
+    }
+    if *context != member_context(verifier.key) {
+        msg!("Membership proof was made for another member");
+        return Err(reject(Groth16Error::PublicInputsMismatch));
+    }
+
+    let prepared_public_inputs =
+        prepare_public_inputs(&registered.vk_ic, &proof.public_inputs).map_err(reject)?;
+    let groth16_verifier_prepared = Groth16VerifierPrepared {
+        proof_a: proof.proof_a,
+        proof_b: proof.proof_b,
+        proof_c: proof.proof_c,
+        prepared_public_inputs,
+        verifying_key: Box::new(registered.verifying_key),
+    };
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
+        msg!("Verification error: {:?}", e);
+        reject(e)
+    })?;
+    if !result {
+        msg!("Proof is invalid!");
+        return Err(reject(Groth16Error::ProofVerificationFailed));
+    }
+    msg!("Membership proof is valid!");
+
+    record_verification(
+        program_id,
+        state_account,
+        verifier,
+        registered.circuit_id,
+        Some(&proof.public_inputs),
+        1,
+    )
 }
 
-fn update_on_chain_state_with_amount(
-    account: &AccountInfo,
-    amount: &[u8; 64],
+fn init_merkle_tree(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tree_id: [u8; 32],
+    depth: u8,
 ) -> ProgramResult {
-    let mut data = account.try_borrow_mut_data()?;
-    let mut state = VerificationState::try_from_slice(&data)?;
-    
-    state.total_verifications += 1;
-    state.last_amount.copy_from_slice(&amount[..32]);
-    state.last_timestamp = Clock::get()?.unix_timestamp;
-    
-    state.serialize(&mut &mut data[..])?;
-    
-    msg!("Verification #{}: Amount verified", state.total_verifications);
+    let account_info_iter = &mut accounts.iter();
+    let tree_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let payer = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer || !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if depth == 0 || depth > MAX_MERKLE_DEPTH {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (expected, bump) = find_merkle_tree_address(program_id, authority.key, &tree_id);
+    if expected != *tree_account.key {
+        msg!("Merkle tree address does not match its seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !tree_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let space = IncrementalMerkleTree::space(depth);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            tree_account.key,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            program_id,
+        ),
+        &[
+            payer.clone(),
+            tree_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[
+            MERKLE_TREE_SEED,
+            authority.key.as_ref(),
+            tree_id.as_ref(),
+            &[bump],
+        ]],
+    )?;
+
+    let tree = IncrementalMerkleTree::new(*authority.key, tree_id, bump, depth)?;
+    tree.store(tree_account)?;
+
+    msg!("Merkle tree of depth {} initialized", depth);
     Ok(())
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
-pub struct Groth16VerifierPrepared {
-    proof_a: [u8; 64],
-    proof_b: [u8; 128],
-    proof_c: [u8; 64],
-    pub prepared_public_inputs: [u8; 64],  // Made public for state updates
-    verifying_key: Box<Groth16VerifyingKeyPrepared>,
+fn append_leaf(program_id: &Pubkey, accounts: &[AccountInfo], leaf: [u8; 32]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let tree_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+
+    let mut tree = IncrementalMerkleTree::load(program_id, tree_account)?;
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if tree.authority != *authority.key {
+        msg!("Only the tree authority can append leaves");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let index = tree.append(leaf)?;
+    tree.store(tree_account)?;
+
+    msg!("Leaf #{} appended", index);
+    emit(&ProofEvent::LeafAppended {
+        tree: *tree_account.key,
+        index,
+        leaf,
+        root: tree.root(),
+    });
+    set_return_data(&index.to_le_bytes());
+    Ok(())
 }
 
-impl Groth16VerifierPrepared {
-    pub fn new(
-        proof_a: &[u8],
-        proof_b: &[u8],
-        proof_c: &[u8],
-        prepared_public_inputs: &[u8],  // ✅ Just the parameter name
-        verifying_key: Box<Groth16VerifyingKeyPrepared>,
-    ) -> Result<Self, Groth16Error> {
-        if proof_a.len() != 64 {
-            return Err(Groth16Error::InvalidG1Length);
-        }
-        if proof_b.len() != 128 {
-            return Err(Groth16Error::InvalidG2Length);
-        }
-        if proof_c.len() != 64 {
-            return Err(Groth16Error::InvalidG1Length);
-        }
-        if prepared_public_inputs.len() != 64 {
-            return Err(Groth16Error::InvalidPublicInputsLength);
-        }
+fn init_verification_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    circuit_id: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let state_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
 
-        let mut proof_a_arr = [0u8; 64];
-        proof_a_arr.copy_from_slice(proof_a);
-        let mut proof_b_arr = [0u8; 128];
-        proof_b_arr.copy_from_slice(proof_b);
-        let mut proof_c_arr = [0u8; 64];
-        proof_c_arr.copy_from_slice(proof_c);
-        let mut prepared_public_inputs_arr = [0u8; 64];
-        prepared_public_inputs_arr.copy_from_slice(prepared_public_inputs);
-
-        Ok(Groth16VerifierPrepared {
-            proof_a: proof_a_arr,
-            proof_b: proof_b_arr,
-            proof_c: proof_c_arr,
-            prepared_public_inputs: prepared_public_inputs_arr,
-            verifying_key,
-        })
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (expected, bump) =
+        find_verification_state_address(program_id, authority.key, &circuit_id);
+    if expected != *state_account.key {
+        msg!("Verification state address does not match its seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !state_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
     }
 
-    pub fn verify(&mut self, accounts: &[AccountInfo]) -> Result<bool, Groth16Error> {
-        let pairing_input = [
-            self.proof_a.as_slice(),
-            self.proof_b.as_slice(),
-            self.prepared_public_inputs.as_slice(),
-            self.verifying_key.vk_gamma_g2.as_slice(),
-            self.proof_c.as_slice(),
-            self.verifying_key.vk_delta_g2.as_slice(),
-            self.verifying_key.vk_alpha_g1.as_slice(),
-            self.verifying_key.vk_beta_g2.as_slice(),
-        ]
-        .concat();
+    let lamports = Rent::get()?.minimum_balance(VerificationState::LEN);
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            state_account.key,
+            lamports,
+            VerificationState::LEN as u64,
+            program_id,
+        ),
+        &[
+            authority.clone(),
+            state_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[
+            VERIFICATION_STATE_SEED,
+            authority.key.as_ref(),
+            circuit_id.as_ref(),
+            &[bump],
+        ]],
+    )?;
+
+    VerificationState::new(*authority.key, circuit_id, bump).store(state_account)?;
+
+    msg!("Verification state initialized");
+    Ok(())
+}
 
-        let ix = Instruction {
-            program_id: ALT_BN128_PAIRING,
-            accounts: vec![],
-            data: pairing_input,
-        };
+fn close_verification_state(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let state_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
 
-        invoke(&ix, accounts).map_err(|_| Groth16Error::PairingVerificationError)?;
+    // Closing into itself would credit the lamports and then zero them
+    if destination.key == state_account.key {
+        msg!("Destination must not be the verification state");
+        return Err(ProgramError::InvalidArgument);
+    }
 
-        Ok(true)
+    let state = VerificationState::load(program_id, state_account)?;
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
+    if state.authority != *authority.key {
+        msg!("Only the state authority can close it");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let lamports = state_account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **state_account.try_borrow_mut_lamports()? = 0;
+
+    state_account.try_borrow_mut_data()?.fill(0);
+    state_account.resize(0)?;
+    state_account.assign(&system_program::ID);
+
+    msg!("Verification state closed");
+    Ok(())
 }
 
-#[derive(Debug, Error, Clone, PartialEq, Eq)]
-pub enum Groth16Error {
-    #[error("Incompatible Verifying Key with number of public inputs")]
-    IncompatibleVerifyingKeyWithNrPublicInputs,
-    #[error("ProofVerificationFailed")]
-    ProofVerificationFailed,
-    #[error("PairingVerificationError")]
-    PairingVerificationError,
-    #[error("PreparingInputsG1AdditionFailed")]
-    PreparingInputsG1AdditionFailed,
-    #[error("PreparingInputsG1MulFailed")]
-    PreparingInputsG1MulFailed,
-    #[error("InvalidG1Length")]
-    InvalidG1Length,
-    #[error("InvalidG2Length")]
-    InvalidG2Length,
-    #[error("InvalidPublicInputsLength")]
-    InvalidPublicInputsLength,
-    #[error("DecompressingG1Failed")]
-    DecompressingG1Failed,
-    #[error("DecompressingG2Failed")]
-    DecompressingG2Failed,
-    #[error("PublicInputGreaterThenFieldSize")]
-    PublicInputGreaterThenFieldSize,
+/// Records a successful verification in `state_account`, after checking that
+/// the account belongs to this program and that the proof was checked against
+/// the circuit the state was created for.
+///
+/// `public_inputs` is only `Some` when the program prepared the inputs itself
+/// from a `vk_ic` and so knows the proof is over them. Instructions that take
+/// an already-prepared input pass `None`, and the digest is left zeroed.
+fn record_verification(
+    program_id: &Pubkey,
+    state_account: &AccountInfo,
+    verifier: &AccountInfo,
+    expected_circuit_id: [u8; 32],
+    public_inputs: Option<&[[u8; 32]]>,
+    verifications: u64,
+) -> ProgramResult {
+    if !verifier.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !state_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut state = VerificationState::load(program_id, state_account)?;
+    if state.circuit_id != expected_circuit_id {
+        msg!("Verifying key does not match the state's circuit");
+        return Err(Groth16Error::CircuitMismatch.into());
+    }
+
+    let clock = Clock::get()?;
+    state.total_verifications = state
+        .total_verifications
+        .checked_add(verifications)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    state.last_public_inputs_digest = public_inputs.map_or([0u8; 32], public_inputs_digest);
+    state.last_verifier = *verifier.key;
+    state.last_slot = clock.slot;
+    state.last_timestamp = clock.unix_timestamp;
+    state.store(state_account)?;
+
+    if public_inputs.is_some() {
+        msg!("Verification #{}: inputs recorded", state.total_verifications);
+    } else {
+        msg!("Verification #{} recorded", state.total_verifications);
+    }
+    emit(&ProofEvent::ProofVerified {
+        circuit_id: state.circuit_id,
+        public_inputs_digest: state.last_public_inputs_digest,
+        verifier: state.last_verifier,
+        slot: state.last_slot,
+    });
+    Ok(())
 }
 
 #[cfg(test)]
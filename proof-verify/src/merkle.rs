@@ -0,0 +1,296 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use groth16_verifier::bn254::is_less_than_field_size_be;
+use solana_program::{account_info::AccountInfo, msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    input_hash::{poseidon, sha256_to_field},
+    Groth16Error,
+};
+
+/// Seed prefix for `IncrementalMerkleTree` PDAs: `[seed, authority, tree_id]`
+pub const MERKLE_TREE_SEED: &[u8] = b"merkle_tree";
+
+/// Seed prefix for `RootRegistry` PDAs: `[seed, authority, registry_id]`
+pub const ROOT_REGISTRY_SEED: &[u8] = b"root_registry";
+
+/// Number of recent roots a proof may reference
+pub const ROOT_HISTORY_SIZE: usize = 32;
+
+/// Deepest tree a registry or membership circuit supports
+pub const MAX_MERKLE_DEPTH: u8 = 32;
+
+/// Ring buffer of the most recent Merkle roots. Proofs are generated against
+/// a root some time before they land, so a root stays valid for the next
+/// `ROOT_HISTORY_SIZE - 1` updates.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RootHistory {
+    pub current_index: u32,
+    pub roots: [[u8; 32]; ROOT_HISTORY_SIZE],
+}
+
+impl RootHistory {
+    pub const LEN: usize = 4 + 32 * ROOT_HISTORY_SIZE;
+
+    pub fn new(root: [u8; 32]) -> Self {
+        let mut roots = [[0u8; 32]; ROOT_HISTORY_SIZE];
+        roots[0] = root;
+        RootHistory { current_index: 0, roots }
+    }
+
+    pub fn current_root(&self) -> [u8; 32] {
+        self.roots[self.current_index as usize % ROOT_HISTORY_SIZE]
+    }
+
+    pub fn push(&mut self, root: [u8; 32]) {
+        self.current_index = (self.current_index + 1) % ROOT_HISTORY_SIZE as u32;
+        self.roots[self.current_index as usize] = root;
+    }
+
+    /// Whether `root` is one of the recent roots. Unused slots are zero, and
+    /// the zero root is never accepted.
+    pub fn contains(&self, root: &[u8; 32]) -> bool {
+        *root != [0u8; 32] && self.roots.contains(root)
+    }
+}
+
+/// Roots of an off-chain Poseidon Merkle tree, for example an allowlist of
+/// KYC'd takers, that membership proofs may reference. The authority
+/// maintains the tree and publishes each new root with `UpdateRoot`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RootRegistry {
+    pub is_initialized: bool,
+    pub authority: Pubkey,        // Allowed to publish roots
+    pub registry_id: [u8; 32],    // Chosen by the authority, one registry per id
+    pub bump: u8,
+    pub depth: u8,                // Depth of the tree the roots belong to
+    pub history: RootHistory,
+}
+
+impl RootRegistry {
+    pub const LEN: usize = 1 + 32 + 32 + 1 + 1 + RootHistory::LEN;
+
+    pub fn new(authority: Pubkey, registry_id: [u8; 32], bump: u8, depth: u8, root: [u8; 32]) -> Self {
+        RootRegistry {
+            is_initialized: true,
+            authority,
+            registry_id,
+            bump,
+            depth,
+            history: RootHistory::new(root),
+        }
+    }
+
+    /// Loads the registry from `account` after checking that it is owned by
+    /// this program, initialized, and sits at the PDA its own fields derive.
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Root registry is not owned by this program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let registry = Self::try_from_slice(&account.try_borrow_data()?)?;
+        if !registry.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let expected = Pubkey::create_program_address(
+            &[
+                ROOT_REGISTRY_SEED,
+                registry.authority.as_ref(),
+                registry.registry_id.as_ref(),
+                &[registry.bump],
+            ],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+        if expected != *account.key {
+            msg!("Root registry address does not match its seeds");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn store(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        self.serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+pub fn find_root_registry_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    registry_id: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ROOT_REGISTRY_SEED, authority.as_ref(), registry_id.as_ref()],
+        program_id,
+    )
+}
+
+/// Checks that `root` can be stored in a registry: a canonical, non-zero
+/// field element
+pub fn check_merkle_root(root: &[u8; 32]) -> Result<(), Groth16Error> {
+    if *root == [0u8; 32] || !is_less_than_field_size_be(root) {
+        return Err(Groth16Error::PublicInputGreaterThenFieldSize);
+    }
+    Ok(())
+}
+
+/// The membership circuit's second public input for `member`: SHA-256 of the
+/// pubkey, keeping the low 253 bits. `VerifyMembership` requires it to be the
+/// verifier's, so a proof can't be replayed by anyone else.
+pub fn member_context(member: &Pubkey) -> [u8; 32] {
+    sha256_to_field(&[member.to_bytes()])
+}
+
+/// Parent of two nodes: Poseidon over `[left, right]`, big-endian
+pub fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> Result<[u8; 32], Groth16Error> {
+    poseidon(&[left.as_slice(), right.as_slice()])
+}
+
+/// Append-only Poseidon Merkle tree kept on-chain, for example the note
+/// commitments of a shielded pool. Only the frontier is stored: the last
+/// left node filled at each height, which is all an append needs. Empty
+/// leaves are zero, and every root the tree has had recently stays in
+/// `history` for membership proofs.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IncrementalMerkleTree {
+    pub is_initialized: bool,
+    pub authority: Pubkey,                // Allowed to append, often another program's PDA
+    pub tree_id: [u8; 32],
+    pub bump: u8,
+    pub depth: u8,
+    pub next_index: u64,                  // Index of the next leaf
+    pub filled_subtrees: Vec<[u8; 32]>,   // Frontier, one node per height
+    pub zeros: Vec<[u8; 32]>,             // Roots of empty subtrees, one per height
+    pub history: RootHistory,
+}
+
+impl IncrementalMerkleTree {
+    pub fn space(depth: u8) -> usize {
+        1 + 32 + 32 + 1 + 1 + 8 + 2 * (4 + 32 * depth as usize) + RootHistory::LEN
+    }
+
+    /// An empty tree of `depth`. Computes the empty subtree roots, `depth`
+    /// Poseidon hashes.
+    pub fn new(authority: Pubkey, tree_id: [u8; 32], bump: u8, depth: u8) -> Result<Self, Groth16Error> {
+        let mut zeros = Vec::with_capacity(depth as usize);
+        let mut zero = [0u8; 32];
+        for _ in 0..depth {
+            zeros.push(zero);
+            zero = hash_pair(&zero, &zero)?;
+        }
+
+        Ok(IncrementalMerkleTree {
+            is_initialized: true,
+            authority,
+            tree_id,
+            bump,
+            depth,
+            next_index: 0,
+            filled_subtrees: zeros.clone(),
+            zeros,
+            history: RootHistory::new(zero),
+        })
+    }
+
+    pub fn capacity(&self) -> u64 {
+        1u64 << self.depth
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.history.current_root()
+    }
+
+    /// Appends `leaf`, a canonical field element, and returns its index. Costs
+    /// `depth` Poseidon hashes.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u64, Groth16Error> {
+        if self.next_index >= self.capacity() {
+            return Err(Groth16Error::MerkleTreeFull);
+        }
+        if !is_less_than_field_size_be(&leaf) {
+            return Err(Groth16Error::PublicInputGreaterThenFieldSize);
+        }
+
+        let leaf_index = self.next_index;
+        let mut position = leaf_index;
+        let mut node = leaf;
+        for h in 0..self.depth as usize {
+            node = if position % 2 == 0 {
+                // Left child: its right sibling is still empty
+                self.filled_subtrees[h] = node;
+                hash_pair(&node, &self.zeros[h])?
+            } else {
+                hash_pair(&self.filled_subtrees[h], &node)?
+            };
+            position /= 2;
+        }
+
+        self.next_index += 1;
+        self.history.push(node);
+        Ok(leaf_index)
+    }
+
+    /// Loads the tree from `account` after checking that it is owned by this
+    /// program, initialized, and sits at the PDA its own fields derive.
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Merkle tree is not owned by this program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let tree = Self::deserialize(&mut &account.try_borrow_data()?[..])?;
+        if !tree.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let expected = Pubkey::create_program_address(
+            &[
+                MERKLE_TREE_SEED,
+                tree.authority.as_ref(),
+                tree.tree_id.as_ref(),
+                &[tree.bump],
+            ],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+        if expected != *account.key {
+            msg!("Merkle tree address does not match its seeds");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(tree)
+    }
+
+    pub fn store(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        self.serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+pub fn find_merkle_tree_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    tree_id: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MERKLE_TREE_SEED, authority.as_ref(), tree_id.as_ref()],
+        program_id,
+    )
+}
+
+/// Recent roots of `account`, either a `RootRegistry` or an
+/// `IncrementalMerkleTree`, and the authority that publishes them. Registries
+/// have a fixed size that no tree has.
+pub fn load_root_history(program_id: &Pubkey, account: &AccountInfo) -> Result<(Pubkey, RootHistory), ProgramError> {
+    if account.data_len() == RootRegistry::LEN {
+        let registry = RootRegistry::load(program_id, account)?;
+        Ok((registry.authority, registry.history))
+    } else {
+        let tree = IncrementalMerkleTree::load(program_id, account)?;
+        Ok((tree.authority, tree.history))
+    }
+}
@@ -0,0 +1,190 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    hash::hashv,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::Groth16VerifyingKeyPrepared;
+
+/// Seed prefix for `VerificationState` PDAs: `[seed, authority, circuit_id]`
+pub const VERIFICATION_STATE_SEED: &[u8] = b"verification_state";
+
+/// Verification State - Tracks proof verification history for one circuit
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerificationState {
+    pub is_initialized: bool,
+    pub authority: Pubkey,               // Allowed to close the account
+    pub circuit_id: [u8; 32],            // Hash of the prepared verifying key
+    pub bump: u8,
+    pub total_verifications: u64,
+    pub last_public_inputs_digest: [u8; 32], // SHA-256 over the checked public inputs, zeroed if unchecked
+    pub last_verifier: Pubkey,           // Signer of the last successful verification
+    pub last_slot: u64,
+    pub last_timestamp: i64,
+}
+
+impl VerificationState {
+    pub const LEN: usize = 1 + 32 + 32 + 1 + 8 + 32 + 32 + 8 + 8;
+
+    pub fn new(authority: Pubkey, circuit_id: [u8; 32], bump: u8) -> Self {
+        VerificationState {
+            is_initialized: true,
+            authority,
+            circuit_id,
+            bump,
+            total_verifications: 0,
+            last_public_inputs_digest: [0u8; 32],
+            last_verifier: Pubkey::default(),
+            last_slot: 0,
+            last_timestamp: 0,
+        }
+    }
+
+    /// Loads the state from `account` after checking that it is owned by this
+    /// program, initialized, and sits at the PDA its own fields derive.
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Verification state is not owned by this program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let state = Self::try_from_slice(&account.try_borrow_data()?)?;
+        if !state.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let expected = Pubkey::create_program_address(
+            &[
+                VERIFICATION_STATE_SEED,
+                state.authority.as_ref(),
+                state.circuit_id.as_ref(),
+                &[state.bump],
+            ],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+        if expected != *account.key {
+            msg!("Verification state address does not match its seeds");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(state)
+    }
+
+    pub fn store(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        self.serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+/// Seed prefix for `RegisteredVerifyingKey` PDAs: `[seed, authority, circuit_id]`
+pub const VERIFYING_KEY_SEED: &[u8] = b"verifying_key";
+
+/// A verifying key stored on-chain so instructions can refer to it instead of
+/// carrying it in every transaction. `circuit_id` is `circuit_id_with_ic`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredVerifyingKey {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub circuit_id: [u8; 32],
+    pub bump: u8,
+    pub verifying_key: Groth16VerifyingKeyPrepared,
+    pub vk_ic: Vec<[u8; 64]>,
+}
+
+impl RegisteredVerifyingKey {
+    pub fn space(nr_public_inputs: usize) -> usize {
+        1 + 32 + 32 + 1 + (64 + 128 + 128 + 128) + 4 + 64 * (nr_public_inputs + 1)
+    }
+
+    pub fn nr_public_inputs(&self) -> usize {
+        self.vk_ic.len().saturating_sub(1)
+    }
+
+    /// Loads the key from `account` after checking that it is owned by this
+    /// program, initialized, and sits at the PDA its own fields derive.
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Verifying key account is not owned by this program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let key = Self::deserialize(&mut &account.try_borrow_data()?[..])?;
+        if !key.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let expected = Pubkey::create_program_address(
+            &[
+                VERIFYING_KEY_SEED,
+                key.authority.as_ref(),
+                key.circuit_id.as_ref(),
+                &[key.bump],
+            ],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+        if expected != *account.key {
+            msg!("Verifying key address does not match its seeds");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(key)
+    }
+}
+
+pub fn find_verifying_key_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    circuit_id: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VERIFYING_KEY_SEED, authority.as_ref(), circuit_id.as_ref()],
+        program_id,
+    )
+}
+
+pub fn find_verification_state_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    circuit_id: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VERIFICATION_STATE_SEED, authority.as_ref(), circuit_id.as_ref()],
+        program_id,
+    )
+}
+
+/// Identifies a circuit by the SHA-256 hash of its prepared verifying key
+pub fn circuit_id(verifying_key: &Groth16VerifyingKeyPrepared) -> [u8; 32] {
+    hashv(&[
+        &verifying_key.vk_alpha_g1,
+        &verifying_key.vk_beta_g2,
+        &verifying_key.vk_gamma_g2,
+        &verifying_key.vk_delta_g2,
+    ])
+    .to_bytes()
+}
+
+/// Circuit id for flows that prepare public inputs on-chain: the prepared
+/// verifying key followed by its `vk_ic` points, so the IC terms are bound too
+pub fn circuit_id_with_ic(verifying_key: &Groth16VerifyingKeyPrepared, vk_ic: &[[u8; 64]]) -> [u8; 32] {
+    let mut parts: Vec<&[u8]> = vec![
+        &verifying_key.vk_alpha_g1,
+        &verifying_key.vk_beta_g2,
+        &verifying_key.vk_gamma_g2,
+        &verifying_key.vk_delta_g2,
+    ];
+    parts.extend(vk_ic.iter().map(|point| point.as_slice()));
+    hashv(&parts).to_bytes()
+}
+
+/// SHA-256 over the concatenated 32-byte public inputs
+pub fn public_inputs_digest(public_inputs: &[[u8; 32]]) -> [u8; 32] {
+    let inputs: Vec<&[u8]> = public_inputs.iter().map(|input| input.as_slice()).collect();
+    hashv(&inputs).to_bytes()
+}
@@ -0,0 +1,94 @@
+use solana_program::{account_info::AccountInfo, pubkey, pubkey::Pubkey};
+
+use crate::Groth16Error;
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EHFLC1qHnbNGi9Lq2RbP");
+
+// Base SPL token account layout, shared by Token and Token-2022
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const MINT_OFFSET: usize = 0;
+const OWNER_OFFSET: usize = 32;
+const AMOUNT_OFFSET: usize = 64;
+const STATE_OFFSET: usize = 108;
+// Token-2022 writes an account type byte right after the base layout when extensions are present
+const ACCOUNT_TYPE_OFFSET: usize = TOKEN_ACCOUNT_LEN;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// The fields of an SPL token account that balance checks need
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAccountView {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+impl TokenAccountView {
+    /// Reads a token account owned by either the Token or the Token-2022 program
+    pub fn load(account: &AccountInfo) -> Result<Self, Groth16Error> {
+        if *account.owner != TOKEN_PROGRAM_ID && *account.owner != TOKEN_2022_PROGRAM_ID {
+            return Err(Groth16Error::InvalidTokenAccount);
+        }
+        let data = account
+            .try_borrow_data()
+            .map_err(|_| Groth16Error::InvalidTokenAccount)?;
+        Self::unpack(&data)
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<Self, Groth16Error> {
+        if data.len() < TOKEN_ACCOUNT_LEN {
+            return Err(Groth16Error::InvalidTokenAccount);
+        }
+        // Anything longer than the base layout must be a Token-2022 account, not a mint
+        if data.len() > TOKEN_ACCOUNT_LEN && data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_ACCOUNT {
+            return Err(Groth16Error::InvalidTokenAccount);
+        }
+        // 0 = uninitialized, 1 = initialized, 2 = frozen
+        if data[STATE_OFFSET] == 0 {
+            return Err(Groth16Error::InvalidTokenAccount);
+        }
+
+        let mut mint = [0u8; 32];
+        mint.copy_from_slice(&data[MINT_OFFSET..MINT_OFFSET + 32]);
+        let mut owner = [0u8; 32];
+        owner.copy_from_slice(&data[OWNER_OFFSET..OWNER_OFFSET + 32]);
+        let mut amount = [0u8; 8];
+        amount.copy_from_slice(&data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8]);
+
+        Ok(TokenAccountView {
+            mint: Pubkey::new_from_array(mint),
+            owner: Pubkey::new_from_array(owner),
+            amount: u64::from_le_bytes(amount),
+        })
+    }
+
+    /// Checks that the account holds at least `required_balance` of `mint` for `owner`
+    pub fn check_balance(
+        &self,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        required_balance: u64,
+    ) -> Result<(), Groth16Error> {
+        if self.mint != *mint {
+            return Err(Groth16Error::TokenMintMismatch);
+        }
+        if self.owner != *owner {
+            return Err(Groth16Error::TokenOwnerMismatch);
+        }
+        if self.amount < required_balance {
+            return Err(Groth16Error::InsufficientTokenBalance);
+        }
+        Ok(())
+    }
+}
+
+/// Reads a public input as a `u64` threshold. Inputs are 32-byte big-endian
+/// field elements, so everything above the low 8 bytes must be zero.
+pub fn threshold_from_public_input(input: &[u8; 32]) -> Result<u64, Groth16Error> {
+    if input[..24].iter().any(|b| *b != 0) {
+        return Err(Groth16Error::InvalidThresholdInput);
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&input[24..]);
+    Ok(u64::from_be_bytes(value))
+}
@@ -0,0 +1,924 @@
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::Groth16;
+use ark_snark::SNARK;
+use ark_std::rand::thread_rng;
+use groth16_test_utils::{fr_bytes, g1_bytes, g2_bytes, InputsKeys, SquareCircuit};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_zk_example::{
+    circuit_id_with_ic, find_root_registry_address, find_verification_state_address,
+    find_verifying_key_address, hash_public_inputs, member_context, prepare_public_inputs,
+    process_instruction, public_inputs_digest, BalanceThreshold, BatchProof, Groth16Error, Groth16VerifierPrepared,
+    Groth16VerifyingKeyPrepared, InputHashScheme, ProgramInstruction, VerificationState,
+    MAX_HASHED_INPUTS, ROOT_HISTORY_SIZE, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+const CIRCUIT_ID: [u8; 32] = [7u8; 32];
+
+struct Fixture {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    program_id: Pubkey,
+    state_pda: Pubkey,
+}
+
+async fn start(program_test: ProgramTest, program_id: Pubkey) -> Fixture {
+    let (banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (state_pda, _) = find_verification_state_address(&program_id, &payer.pubkey(), &CIRCUIT_ID);
+    Fixture {
+        banks_client,
+        payer,
+        recent_blockhash,
+        program_id,
+        state_pda,
+    }
+}
+
+/// A bank with the payer's verification state for `CIRCUIT_ID` already created
+async fn setup() -> Fixture {
+    let program_id = Pubkey::new_unique();
+    let program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let mut fixture = start(program_test, program_id).await;
+
+    let init = init_instruction(&fixture, fixture.state_pda, fixture.payer.pubkey());
+    process(&mut fixture, init, &[]).await.unwrap();
+    fixture
+}
+
+fn init_instruction(fixture: &Fixture, state: Pubkey, authority: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::InitVerificationState { circuit_id: CIRCUIT_ID }.pack(),
+        vec![
+            AccountMeta::new(state, false),
+            AccountMeta::new(authority, authority == fixture.payer.pubkey()),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    )
+}
+
+fn close_instruction(
+    fixture: &Fixture,
+    authority: Pubkey,
+    authority_signs: bool,
+    destination: Pubkey,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::CloseVerificationState.pack(),
+        vec![
+            AccountMeta::new(fixture.state_pda, false),
+            AccountMeta::new_readonly(authority, authority_signs),
+            AccountMeta::new(destination, false),
+        ],
+    )
+}
+
+async fn process(
+    fixture: &mut Fixture,
+    instruction: Instruction,
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let mut all_signers = vec![&fixture.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&fixture.payer.pubkey()),
+        &all_signers,
+        fixture.recent_blockhash,
+    );
+    fixture
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+fn instruction_error(error: InstructionError) -> TransactionError {
+    TransactionError::InstructionError(0, error)
+}
+
+#[tokio::test]
+async fn test_close_verification_state_refunds_the_destination() {
+    let mut fixture = setup().await;
+    let destination = Pubkey::new_unique();
+
+    let close = close_instruction(&fixture, fixture.payer.pubkey(), true, destination);
+    process(&mut fixture, close, &[]).await.unwrap();
+
+    let refunded = fixture.banks_client.get_balance(destination).await.unwrap();
+    assert_eq!(refunded, Rent::default().minimum_balance(VerificationState::LEN));
+    assert!(fixture.banks_client.get_account(fixture.state_pda).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_close_verification_state_requires_the_authority() {
+    let mut fixture = setup().await;
+    let intruder = Keypair::new();
+
+    let close = close_instruction(&fixture, intruder.pubkey(), true, intruder.pubkey());
+    assert_eq!(
+        process(&mut fixture, close, &[&intruder]).await.unwrap_err(),
+        instruction_error(InstructionError::IllegalOwner)
+    );
+}
+
+#[tokio::test]
+async fn test_close_verification_state_requires_a_signature() {
+    let mut fixture = setup().await;
+
+    let close = close_instruction(&fixture, fixture.payer.pubkey(), false, Pubkey::new_unique());
+    assert_eq!(
+        process(&mut fixture, close, &[]).await.unwrap_err(),
+        instruction_error(InstructionError::MissingRequiredSignature)
+    );
+}
+
+#[tokio::test]
+async fn test_close_verification_state_rejects_itself_as_destination() {
+    let mut fixture = setup().await;
+
+    let close = close_instruction(&fixture, fixture.payer.pubkey(), true, fixture.state_pda);
+    assert_eq!(
+        process(&mut fixture, close, &[]).await.unwrap_err(),
+        instruction_error(InstructionError::InvalidArgument)
+    );
+}
+
+#[tokio::test]
+async fn test_close_verification_state_rejects_foreign_accounts() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+
+    // A well-formed state at the right address, but owned by another program
+    let authority = Keypair::new();
+    let (state_pda, bump) = find_verification_state_address(&program_id, &authority.pubkey(), &CIRCUIT_ID);
+    let state = VerificationState::new(authority.pubkey(), CIRCUIT_ID, bump);
+    program_test.add_account(
+        state_pda,
+        Account {
+            lamports: Rent::default().minimum_balance(VerificationState::LEN),
+            data: borsh::to_vec(&state).unwrap(),
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let mut fixture = start(program_test, program_id).await;
+    fixture.state_pda = state_pda;
+
+    let close = close_instruction(&fixture, authority.pubkey(), true, authority.pubkey());
+    assert_eq!(
+        process(&mut fixture, close, &[&authority]).await.unwrap_err(),
+        instruction_error(InstructionError::IncorrectProgramId)
+    );
+}
+
+#[tokio::test]
+async fn test_init_verification_state_rejects_the_wrong_address() {
+    let program_id = Pubkey::new_unique();
+    let program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let mut fixture = start(program_test, program_id).await;
+
+    // The PDA of another circuit
+    let (other_pda, _) = find_verification_state_address(&program_id, &fixture.payer.pubkey(), &[8u8; 32]);
+    let init = init_instruction(&fixture, other_pda, fixture.payer.pubkey());
+    assert_eq!(
+        process(&mut fixture, init, &[]).await.unwrap_err(),
+        instruction_error(InstructionError::InvalidSeeds)
+    );
+}
+
+#[tokio::test]
+async fn test_init_verification_state_requires_a_signature() {
+    let program_id = Pubkey::new_unique();
+    let program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let mut fixture = start(program_test, program_id).await;
+
+    let authority = Pubkey::new_unique();
+    let (state_pda, _) = find_verification_state_address(&program_id, &authority, &CIRCUIT_ID);
+    let init = init_instruction(&fixture, state_pda, authority);
+    assert_eq!(
+        process(&mut fixture, init, &[]).await.unwrap_err(),
+        instruction_error(InstructionError::MissingRequiredSignature)
+    );
+}
+
+/// A registered `SquareCircuit` key, the payer's verification state for it,
+/// and a valid proof of `3 * 3 = 9`
+struct SquareKey {
+    key_pda: Pubkey,
+    state_pda: Pubkey,
+    verifying_key: Groth16VerifyingKeyPrepared,
+    vk_ic: Vec<[u8; 64]>,
+    proof: BatchProof,
+}
+
+async fn register_square_key(fixture: &mut Fixture) -> SquareKey {
+    let rng = &mut thread_rng();
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(SquareCircuit { x: None }, rng).unwrap();
+    let x = Fr::from(3u64);
+    let proof = Groth16::<Bn254>::prove(&pk, SquareCircuit { x: Some(x) }, rng).unwrap();
+    let proof = BatchProof {
+        proof_a: g1_bytes(&-proof.a),
+        proof_b: g2_bytes(&proof.b),
+        proof_c: g1_bytes(&proof.c),
+        public_inputs: vec![fr_bytes(x * x)],
+    };
+
+    let verifying_key = Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: g1_bytes(&vk.alpha_g1),
+        vk_beta_g2: g2_bytes(&vk.beta_g2),
+        vk_gamma_g2: g2_bytes(&vk.gamma_g2),
+        vk_delta_g2: g2_bytes(&vk.delta_g2),
+    };
+    let vk_ic: Vec<[u8; 64]> = vk.gamma_abc_g1.iter().map(g1_bytes).collect();
+    let id = circuit_id_with_ic(&verifying_key, &vk_ic);
+    let payer = fixture.payer.pubkey();
+    let (key_pda, _) = find_verifying_key_address(&fixture.program_id, &payer, &id);
+    let (state_pda, _) = find_verification_state_address(&fixture.program_id, &payer, &id);
+
+    let register = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::RegisterVerifyingKey {
+            verifying_key: verifying_key.clone(),
+            vk_ic: vk_ic.clone(),
+        }
+        .pack(),
+        vec![
+            AccountMeta::new(key_pda, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    process(fixture, register, &[]).await.unwrap();
+    let init = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::InitVerificationState { circuit_id: id }.pack(),
+        vec![
+            AccountMeta::new(state_pda, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    process(fixture, init, &[]).await.unwrap();
+
+    SquareKey {
+        key_pda,
+        state_pda,
+        verifying_key,
+        vk_ic,
+        proof,
+    }
+}
+
+impl SquareKey {
+    /// The proof as a `VerifyProof` payload, its input prepared off-chain
+    fn proof_data(&self) -> Groth16VerifierPrepared {
+        Groth16VerifierPrepared {
+            proof_a: self.proof.proof_a,
+            proof_b: self.proof.proof_b,
+            proof_c: self.proof.proof_c,
+            prepared_public_inputs: prepare_public_inputs(&self.vk_ic, &self.proof.public_inputs).unwrap(),
+            verifying_key: Box::new(self.verifying_key.clone()),
+        }
+    }
+}
+
+fn verify_proof_instruction(
+    fixture: &Fixture,
+    key: &SquareKey,
+    proof_data: Groth16VerifierPrepared,
+    public_inputs: Vec<[u8; 32]>,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyProof { proof_data, public_inputs }.pack(),
+        vec![
+            AccountMeta::new_readonly(key.key_pda, false),
+            AccountMeta::new(key.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+        ],
+    )
+}
+
+fn verify_batch_instruction(fixture: &Fixture, key: &SquareKey, proofs: Vec<BatchProof>) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyBatch { proofs }.pack(),
+        vec![
+            AccountMeta::new_readonly(key.key_pda, false),
+            AccountMeta::new(key.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+        ],
+    )
+}
+
+fn groth16_error(error: Groth16Error) -> TransactionError {
+    instruction_error(InstructionError::Custom(error.code()))
+}
+
+#[tokio::test]
+async fn test_verify_batch_accepts_a_valid_proof() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    let verify = verify_batch_instruction(&fixture, &key, vec![key.proof.clone()]);
+    process(&mut fixture, verify, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_proof_rejects_a_malformed_proof() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    // Not a point on the curve
+    let mut proof_data = key.proof_data();
+    proof_data.proof_a = [1u8; 64];
+    let verify = verify_proof_instruction(&fixture, &key, proof_data, key.proof.public_inputs.clone());
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::PairingVerificationError)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_proof_records_the_inputs_it_prepared() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    let public_inputs = key.proof.public_inputs.clone();
+    let verify = verify_proof_instruction(&fixture, &key, key.proof_data(), public_inputs.clone());
+    process(&mut fixture, verify, &[]).await.unwrap();
+
+    let state = verification_state(&mut fixture, key.state_pda).await;
+    assert_eq!(state.total_verifications, 1);
+    assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&public_inputs));
+}
+
+#[tokio::test]
+async fn test_verify_proof_rejects_inputs_that_are_not_the_proofs() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    // The proof is valid for its own prepared input, but the raw inputs passed
+    // along, which would be recorded, are different
+    let verify = verify_proof_instruction(&fixture, &key, key.proof_data(), vec![fr_bytes(Fr::from(16u64))]);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::PublicInputsMismatch)
+    );
+
+    // A key other than the registered one
+    let mut proof_data = key.proof_data();
+    proof_data.verifying_key.vk_delta_g2 = proof_data.verifying_key.vk_gamma_g2;
+    let verify = verify_proof_instruction(&fixture, &key, proof_data, key.proof.public_inputs.clone());
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::CircuitMismatch)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_proof_with_balance_records_the_inputs_it_prepared() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+    let account_to_check = fixture.payer.pubkey();
+
+    let public_inputs = key.proof.public_inputs.clone();
+    let verify = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyProofWithBalance {
+            proof_data: key.proof_data(),
+            public_inputs: public_inputs.clone(),
+            required_balance: 1,
+            account_to_check,
+        }
+        .pack(),
+        vec![
+            AccountMeta::new_readonly(key.key_pda, false),
+            AccountMeta::new(key.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+            AccountMeta::new_readonly(account_to_check, false),
+        ],
+    );
+    process(&mut fixture, verify, &[]).await.unwrap();
+
+    let state = verification_state(&mut fixture, key.state_pda).await;
+    assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&public_inputs));
+}
+
+#[tokio::test]
+async fn test_verify_batch_rejects_the_wrong_input_count() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    let mut proof = key.proof.clone();
+    proof.public_inputs.push(fr_bytes(Fr::from(16u64)));
+    let verify = verify_batch_instruction(&fixture, &key, vec![proof]);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_batch_rejects_inputs_past_the_field_size() {
+    let mut fixture = setup().await;
+    let key = register_square_key(&mut fixture).await;
+
+    let mut proof = key.proof.clone();
+    proof.public_inputs = vec![[0xff; 32]];
+    let verify = verify_batch_instruction(&fixture, &key, vec![proof]);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::PublicInputGreaterThenFieldSize)
+    );
+}
+
+/// A registered `InputsCircuit` key for `nr_public_inputs` inputs, and the
+/// payer's verification state for it
+struct InputsKey {
+    key_pda: Pubkey,
+    state_pda: Pubkey,
+    keys: InputsKeys,
+}
+
+async fn register_inputs_key(fixture: &mut Fixture, nr_public_inputs: usize) -> InputsKey {
+    let keys = InputsKeys::setup(nr_public_inputs);
+    let vk = keys.verifying_key();
+    let verifying_key = Groth16VerifyingKeyPrepared {
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamma_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+    };
+    let id = circuit_id_with_ic(&verifying_key, &vk.ic);
+    let payer = fixture.payer.pubkey();
+    let (key_pda, _) = find_verifying_key_address(&fixture.program_id, &payer, &id);
+    let (state_pda, _) = find_verification_state_address(&fixture.program_id, &payer, &id);
+
+    let register = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::RegisterVerifyingKey { verifying_key, vk_ic: vk.ic }.pack(),
+        vec![
+            AccountMeta::new(key_pda, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    process(fixture, register, &[]).await.unwrap();
+    let init = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::InitVerificationState { circuit_id: id }.pack(),
+        vec![
+            AccountMeta::new(state_pda, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    process(fixture, init, &[]).await.unwrap();
+
+    InputsKey { key_pda, state_pda, keys }
+}
+
+impl InputsKey {
+    fn prove(&self, public_inputs: Vec<[u8; 32]>) -> BatchProof {
+        let proof = self.keys.prove(&public_inputs);
+        BatchProof {
+            proof_a: proof.a,
+            proof_b: proof.b,
+            proof_c: proof.c,
+            public_inputs,
+        }
+    }
+}
+
+fn root(value: u64) -> [u8; 32] {
+    fr_bytes(Fr::from(value))
+}
+
+/// Creates `authority`'s registry with id `[1; 32]`, holding `initial_root`
+async fn init_root_registry(
+    fixture: &mut Fixture,
+    authority: &Keypair,
+    initial_root: [u8; 32],
+) -> Pubkey {
+    let registry_id = [1u8; 32];
+    let (registry, _) =
+        find_root_registry_address(&fixture.program_id, &authority.pubkey(), &registry_id);
+    let init = Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::InitRootRegistry { registry_id, depth: 20, root: initial_root }.pack(),
+        vec![
+            AccountMeta::new(registry, false),
+            AccountMeta::new(authority.pubkey(), true),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+    );
+    if authority.pubkey() == fixture.payer.pubkey() {
+        process(fixture, init, &[]).await.unwrap();
+    } else {
+        process(fixture, init, &[authority]).await.unwrap();
+    }
+    registry
+}
+
+fn update_root_instruction(
+    fixture: &Fixture,
+    registry: Pubkey,
+    authority: Pubkey,
+    new_root: [u8; 32],
+) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::UpdateRoot { root: new_root }.pack(),
+        vec![AccountMeta::new(registry, false), AccountMeta::new_readonly(authority, true)],
+    )
+}
+
+fn verify_membership_instruction(
+    fixture: &Fixture,
+    key: &InputsKey,
+    registry: Pubkey,
+    proof: BatchProof,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyMembership { proof }.pack(),
+        vec![
+            AccountMeta::new_readonly(key.key_pda, false),
+            AccountMeta::new_readonly(registry, false),
+            AccountMeta::new(key.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+        ],
+    )
+}
+
+async fn verification_state(fixture: &mut Fixture, state: Pubkey) -> VerificationState {
+    let account = fixture.banks_client.get_account(state).await.unwrap().unwrap();
+    borsh::from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_update_root_requires_the_registry_authority() {
+    let mut fixture = setup().await;
+    let payer = fixture.payer.insecure_clone();
+    let registry = init_root_registry(&mut fixture, &payer, root(1)).await;
+    let intruder = Keypair::new();
+
+    let update = update_root_instruction(&fixture, registry, intruder.pubkey(), root(2));
+    assert_eq!(
+        process(&mut fixture, update, &[&intruder]).await.unwrap_err(),
+        instruction_error(InstructionError::IllegalOwner)
+    );
+
+    let update = update_root_instruction(&fixture, registry, payer.pubkey(), root(2));
+    process(&mut fixture, update, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_membership_records_a_known_root() {
+    let mut fixture = setup().await;
+    let payer = fixture.payer.insecure_clone();
+    let registry = init_root_registry(&mut fixture, &payer, root(1)).await;
+    let key = register_inputs_key(&mut fixture, 2).await;
+
+    let public_inputs = vec![root(1), member_context(&payer.pubkey())];
+    let proof = key.prove(public_inputs.clone());
+    let verify = verify_membership_instruction(&fixture, &key, registry, proof);
+    process(&mut fixture, verify, &[]).await.unwrap();
+
+    let state = verification_state(&mut fixture, key.state_pda).await;
+    assert_eq!(state.total_verifications, 1);
+    assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&public_inputs));
+    assert_eq!(state.last_verifier, payer.pubkey());
+}
+
+#[tokio::test]
+async fn test_verify_membership_rejects_unknown_and_stale_roots() {
+    let mut fixture = setup().await;
+    let payer = fixture.payer.insecure_clone();
+    let registry = init_root_registry(&mut fixture, &payer, root(1)).await;
+    let key = register_inputs_key(&mut fixture, 2).await;
+    let context = member_context(&payer.pubkey());
+
+    // A root the registry never published
+    let proof = key.prove(vec![root(2), context]);
+    let verify = verify_membership_instruction(&fixture, &key, registry, proof);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::UnknownMerkleRoot)
+    );
+
+    // The first root, pushed out of the history by newer ones
+    for i in 0..ROOT_HISTORY_SIZE as u64 {
+        let update = update_root_instruction(&fixture, registry, payer.pubkey(), root(100 + i));
+        process(&mut fixture, update, &[]).await.unwrap();
+    }
+    let proof = key.prove(vec![root(1), context]);
+    let verify = verify_membership_instruction(&fixture, &key, registry, proof);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::UnknownMerkleRoot)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_membership_rejects_another_members_proof() {
+    let mut fixture = setup().await;
+    let payer = fixture.payer.insecure_clone();
+    let registry = init_root_registry(&mut fixture, &payer, root(1)).await;
+    let key = register_inputs_key(&mut fixture, 2).await;
+
+    // A valid proof, but for someone else's context
+    let proof = key.prove(vec![root(1), member_context(&Pubkey::new_unique())]);
+    let verify = verify_membership_instruction(&fixture, &key, registry, proof);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::PublicInputsMismatch)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_membership_rejects_a_registry_of_another_authority() {
+    let mut fixture = setup().await;
+    let payer = fixture.payer.insecure_clone();
+    let key = register_inputs_key(&mut fixture, 2).await;
+
+    // The intruder publishes a root of their own making in their own registry
+    let intruder = Keypair::new();
+    let fund = solana_system_interface::instruction::transfer(
+        &payer.pubkey(),
+        &intruder.pubkey(),
+        1_000_000_000,
+    );
+    process(&mut fixture, fund, &[]).await.unwrap();
+    let registry = init_root_registry(&mut fixture, &intruder, root(7)).await;
+
+    let proof = key.prove(vec![root(7), member_context(&payer.pubkey())]);
+    let verify = verify_membership_instruction(&fixture, &key, registry, proof);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::RootAuthorityMismatch)
+    );
+    assert_eq!(verification_state(&mut fixture, key.state_pda).await.total_verifications, 0);
+}
+
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64, token_program: Pubkey) -> Account {
+    let mut data = vec![0u8; 165];
+    data[0..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1; // initialized
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: token_program,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// A bank holding `holder`'s token accounts, and the square key registered
+struct TokenFixture {
+    fixture: Fixture,
+    key: SquareKey,
+    holder: Keypair,
+    mint: Pubkey,
+}
+
+/// Token accounts of `mint` for `holder`, one per `(amount, token_program)`
+async fn setup_token_accounts(accounts: &[(Pubkey, u64, Pubkey)]) -> TokenFixture {
+    let program_id = Pubkey::new_unique();
+    let mut program_test =
+        ProgramTest::new("solana_zk_example", program_id, processor!(process_instruction));
+    let holder = Keypair::new();
+    let mint = Pubkey::new_unique();
+    for (address, amount, token_program) in accounts {
+        program_test.add_account(*address, token_account(&mint, &holder.pubkey(), *amount, *token_program));
+    }
+    let mut fixture = start(program_test, program_id).await;
+    let key = register_square_key(&mut fixture).await;
+    TokenFixture { fixture, key, holder, mint }
+}
+
+impl TokenFixture {
+    fn verify_instruction(&self, token_account: Pubkey, mint: Pubkey, threshold: BalanceThreshold) -> Instruction {
+        Instruction::new_with_bytes(
+            self.fixture.program_id,
+            &ProgramInstruction::VerifyProofWithTokenBalance {
+                proof_data: self.key.proof_data(),
+                public_inputs: self.key.proof.public_inputs.clone(),
+                mint,
+                threshold,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(self.key.key_pda, false),
+                AccountMeta::new(self.key.state_pda, false),
+                AccountMeta::new_readonly(self.holder.pubkey(), true),
+                AccountMeta::new_readonly(token_account, false),
+            ],
+        )
+    }
+
+    async fn verify(
+        &mut self,
+        token_account: Pubkey,
+        mint: Pubkey,
+        threshold: BalanceThreshold,
+    ) -> Result<(), TransactionError> {
+        let instruction = self.verify_instruction(token_account, mint, threshold);
+        let holder = self.holder.insecure_clone();
+        process(&mut self.fixture, instruction, &[&holder]).await
+    }
+}
+
+fn plain(required_balance: u64) -> BalanceThreshold {
+    BalanceThreshold::Plain { required_balance }
+}
+
+#[tokio::test]
+async fn test_verify_proof_with_token_balance_checks_the_token_account() {
+    let spl_account = Pubkey::new_unique();
+    let token_2022_account = Pubkey::new_unique();
+    let foreign_account = Pubkey::new_unique();
+    let mut token = setup_token_accounts(&[
+        (spl_account, 100, TOKEN_PROGRAM_ID),
+        (token_2022_account, 100, TOKEN_2022_PROGRAM_ID),
+        // A token account layout, but under a program that isn't SPL Token
+        (foreign_account, 100, Pubkey::new_unique()),
+    ])
+    .await;
+    let mint = token.mint;
+
+    assert_eq!(
+        token.verify(foreign_account, mint, plain(1)).await.unwrap_err(),
+        groth16_error(Groth16Error::InvalidTokenAccount)
+    );
+    assert_eq!(
+        token.verify(spl_account, Pubkey::new_unique(), plain(1)).await.unwrap_err(),
+        groth16_error(Groth16Error::TokenMintMismatch)
+    );
+    assert_eq!(
+        token.verify(spl_account, mint, plain(101)).await.unwrap_err(),
+        groth16_error(Groth16Error::InsufficientTokenBalance)
+    );
+
+    token.verify(spl_account, mint, plain(100)).await.unwrap();
+    token.verify(token_2022_account, mint, plain(100)).await.unwrap();
+    let state = verification_state(&mut token.fixture, token.key.state_pda).await;
+    assert_eq!(state.total_verifications, 2);
+    assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&token.key.proof.public_inputs));
+}
+
+#[tokio::test]
+async fn test_verify_proof_with_token_balance_requires_the_verifier_to_own_the_account() {
+    let token_account = Pubkey::new_unique();
+    let mut token = setup_token_accounts(&[(token_account, 100, TOKEN_PROGRAM_ID)]).await;
+
+    // The payer signs as the verifier, but the account is the holder's
+    let mut instruction = token.verify_instruction(token_account, token.mint, plain(1));
+    instruction.accounts[2] = AccountMeta::new_readonly(token.fixture.payer.pubkey(), true);
+    assert_eq!(
+        process(&mut token.fixture, instruction, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::TokenOwnerMismatch)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_proof_with_token_balance_reads_a_proven_threshold() {
+    // The square proof's single public input, 9, is the threshold
+    let enough = Pubkey::new_unique();
+    let short = Pubkey::new_unique();
+    let mut token = setup_token_accounts(&[(enough, 9, TOKEN_PROGRAM_ID), (short, 8, TOKEN_PROGRAM_ID)]).await;
+    let mint = token.mint;
+    let proven = BalanceThreshold::PublicInput { index: 0, vk_ic: token.key.vk_ic.clone() };
+
+    assert_eq!(
+        token.verify(short, mint, proven.clone()).await.unwrap_err(),
+        groth16_error(Groth16Error::InsufficientTokenBalance)
+    );
+    assert_eq!(
+        token
+            .verify(enough, mint, BalanceThreshold::PublicInput { index: 1, vk_ic: token.key.vk_ic.clone() })
+            .await
+            .unwrap_err(),
+        groth16_error(Groth16Error::InvalidThresholdInput)
+    );
+    let mut other_vk_ic = token.key.vk_ic.clone();
+    other_vk_ic.reverse();
+    assert_eq!(
+        token
+            .verify(enough, mint, BalanceThreshold::PublicInput { index: 0, vk_ic: other_vk_ic })
+            .await
+            .unwrap_err(),
+        groth16_error(Groth16Error::CircuitMismatch)
+    );
+
+    token.verify(enough, mint, proven).await.unwrap();
+    let state = verification_state(&mut token.fixture, token.key.state_pda).await;
+    assert_eq!(state.total_verifications, 1);
+    assert_eq!(state.last_verifier, token.holder.pubkey());
+    assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&[fr_bytes(Fr::from(9u64))]));
+}
+
+fn verify_hashed_inputs_instruction(
+    fixture: &Fixture,
+    key: &InputsKey,
+    proof: BatchProof,
+    scheme: InputHashScheme,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        fixture.program_id,
+        &ProgramInstruction::VerifyHashedInputs { proof, scheme }.pack(),
+        vec![
+            AccountMeta::new_readonly(key.key_pda, false),
+            AccountMeta::new(key.state_pda, false),
+            AccountMeta::new_readonly(fixture.payer.pubkey(), true),
+        ],
+    )
+}
+
+/// A proof over the hash of `logical_inputs`, carrying the logical inputs
+fn prove_hashed(key: &InputsKey, scheme: InputHashScheme, logical_inputs: &[[u8; 32]]) -> BatchProof {
+    let mut proof = key.prove(vec![hash_public_inputs(scheme, logical_inputs).unwrap()]);
+    proof.public_inputs = logical_inputs.to_vec();
+    proof
+}
+
+fn logical_inputs(count: u64) -> Vec<[u8; 32]> {
+    (1..=count).map(|i| fr_bytes(Fr::from(i * 1_000_003))).collect()
+}
+
+#[tokio::test]
+async fn test_verify_hashed_inputs_accepts_a_matching_hash() {
+    let mut fixture = setup().await;
+    let key = register_inputs_key(&mut fixture, 1).await;
+
+    // 13 inputs also covers the Poseidon chaining
+    let inputs = logical_inputs(13);
+    for (scheme, verifications) in [(InputHashScheme::Sha256, 1), (InputHashScheme::Poseidon, 2)] {
+        let proof = prove_hashed(&key, scheme, &inputs);
+        let verify = verify_hashed_inputs_instruction(&fixture, &key, proof, scheme);
+        process(&mut fixture, verify, &[]).await.unwrap();
+
+        let state = verification_state(&mut fixture, key.state_pda).await;
+        assert_eq!(state.total_verifications, verifications);
+        assert_eq!(state.last_public_inputs_digest, public_inputs_digest(&inputs));
+    }
+}
+
+#[tokio::test]
+async fn test_verify_hashed_inputs_rejects_a_mismatched_hash() {
+    let mut fixture = setup().await;
+    let key = register_inputs_key(&mut fixture, 1).await;
+    let inputs = logical_inputs(3);
+
+    // One logical input changed after proving
+    let mut proof = prove_hashed(&key, InputHashScheme::Sha256, &inputs);
+    proof.public_inputs[2] = fr_bytes(Fr::from(7u64));
+    let verify = verify_hashed_inputs_instruction(&fixture, &key, proof, InputHashScheme::Sha256);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::ProofVerificationFailed)
+    );
+
+    // Hashed with the other scheme
+    let proof = prove_hashed(&key, InputHashScheme::Sha256, &inputs);
+    let verify = verify_hashed_inputs_instruction(&fixture, &key, proof, InputHashScheme::Poseidon);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::ProofVerificationFailed)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_hashed_inputs_rejects_too_many_inputs() {
+    let mut fixture = setup().await;
+    let key = register_inputs_key(&mut fixture, 1).await;
+
+    // A valid proof over the hash of the first MAX_HASHED_INPUTS inputs
+    let inputs = logical_inputs(MAX_HASHED_INPUTS as u64 + 1);
+    let mut proof = prove_hashed(&key, InputHashScheme::Poseidon, &inputs[..MAX_HASHED_INPUTS]);
+    proof.public_inputs = inputs;
+    let verify = verify_hashed_inputs_instruction(&fixture, &key, proof, InputHashScheme::Poseidon);
+    assert_eq!(
+        process(&mut fixture, verify, &[]).await.unwrap_err(),
+        groth16_error(Groth16Error::InvalidPublicInputsLength)
+    );
+}
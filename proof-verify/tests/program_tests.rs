@@ -2,23 +2,67 @@
 mod program_tests {
     use solana_zk_example::*;
     use borsh::BorshDeserialize;
+    use solana_program::{instruction::InstructionError, program_error::ProgramError, pubkey::Pubkey};
+    use solana_transaction_error::TransactionError;
     
     #[test]
     fn test_verification_state_serialization() {
-        let state = VerificationState {
-            total_verifications: 10,
-            last_amount: [1u8; 32],
-            last_timestamp: 1234567890,
-        };
+        let mut state = VerificationState::new(Pubkey::new_unique(), [7u8; 32], 254);
+        state.total_verifications = 10;
+        state.last_public_inputs_digest = [1u8; 32];
+        state.last_verifier = Pubkey::new_unique();
+        state.last_slot = 42;
+        state.last_timestamp = 1234567890;
         
         let serialized = borsh::to_vec(&state).unwrap();
         assert_eq!(serialized.len(), VerificationState::LEN);
         
         let deserialized = VerificationState::try_from_slice(&serialized).unwrap();
+        assert_eq!(deserialized, state);
         assert_eq!(deserialized.total_verifications, 10);
         assert_eq!(deserialized.last_timestamp, 1234567890);
     }
     
+    #[test]
+    fn test_verification_state_address_is_per_authority_and_circuit() {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        
+        let (address, _) = find_verification_state_address(&program_id, &authority, &[1u8; 32]);
+        let (same, _) = find_verification_state_address(&program_id, &authority, &[1u8; 32]);
+        let (other_circuit, _) = find_verification_state_address(&program_id, &authority, &[2u8; 32]);
+        let (other_authority, _) =
+            find_verification_state_address(&program_id, &Pubkey::new_unique(), &[1u8; 32]);
+        
+        assert_eq!(address, same);
+        assert_ne!(address, other_circuit);
+        assert_ne!(address, other_authority);
+    }
+    
+    #[test]
+    fn test_circuit_id_depends_on_verifying_key() {
+        let vk = Groth16VerifyingKeyPrepared {
+            vk_alpha_g1: [0u8; 64],
+            vk_beta_g2: [0u8; 128],
+            vk_gamma_g2: [0u8; 128],
+            vk_delta_g2: [0u8; 128],
+        };
+        let mut other_vk = vk.clone();
+        other_vk.vk_delta_g2[0] = 1;
+        
+        assert_eq!(circuit_id(&vk), circuit_id(&vk.clone()));
+        assert_ne!(circuit_id(&vk), circuit_id(&other_vk));
+    }
+    
+    #[test]
+    fn test_public_inputs_digest_is_order_sensitive() {
+        let a = [1u8; 32];
+        let b = [2u8; 32];
+        
+        assert_eq!(public_inputs_digest(&[a, b]), public_inputs_digest(&[a, b]));
+        assert_ne!(public_inputs_digest(&[a, b]), public_inputs_digest(&[b, a]));
+    }
+    
     #[test]
     fn test_groth16_verifying_key_prepared() {
         let vk = Groth16VerifyingKeyPrepared {
@@ -50,11 +94,79 @@ mod program_tests {
             vk,
         ).unwrap();
         
-        let instruction = ProgramInstruction::VerifyProof(verifier);
+        let instruction = ProgramInstruction::VerifyProof {
+            proof_data: verifier,
+            public_inputs: vec![[0u8; 32]],
+        };
         let serialized = borsh::to_vec(&instruction).unwrap();
         assert!(serialized.len() > 0);
     }
     
+    #[test]
+    fn test_verification_state_instruction_serialization() {
+        let init = ProgramInstruction::InitVerificationState { circuit_id: [3u8; 32] };
+        let serialized = borsh::to_vec(&init).unwrap();
+        assert_eq!(serialized[0], 2);
+        assert_eq!(&serialized[1..], &[3u8; 32]);
+        
+        let close = borsh::to_vec(&ProgramInstruction::CloseVerificationState).unwrap();
+        assert_eq!(close, vec![3]);
+    }
+    
+    #[test]
+    fn test_versioned_instruction_roundtrip() {
+        let instruction = ProgramInstruction::InitVerificationState { circuit_id: [3u8; 32] };
+        let packed = instruction.pack();
+        
+        assert_eq!(packed[0], VERSIONED_INSTRUCTION_TAG);
+        assert_eq!(packed[1], INSTRUCTION_VERSION);
+        assert_eq!(packed[2], instruction.discriminator());
+        assert_eq!(ProgramInstruction::unpack(&packed).unwrap(), instruction);
+    }
+    
+    #[test]
+    fn test_unpack_accepts_v0_layout() {
+        let close = ProgramInstruction::CloseVerificationState;
+        let v0 = borsh::to_vec(&close).unwrap();
+        assert_eq!(ProgramInstruction::unpack(&v0).unwrap(), close);
+        
+        let batch = ProgramInstruction::VerifyBatch { proofs: vec![] };
+        assert_eq!(ProgramInstruction::unpack(&borsh::to_vec(&batch).unwrap()).unwrap(), batch);
+    }
+    
+    #[test]
+    fn test_discriminator_matches_borsh_variant_index() {
+        let instructions = vec![
+            ProgramInstruction::InitVerificationState { circuit_id: [0u8; 32] },
+            ProgramInstruction::CloseVerificationState,
+            ProgramInstruction::RegisterVerifyingKey {
+                verifying_key: zero_vk(),
+                vk_ic: vec![[0u8; 64]],
+            },
+            ProgramInstruction::VerifyBatch { proofs: vec![] },
+        ];
+        for instruction in instructions {
+            assert_eq!(borsh::to_vec(&instruction).unwrap()[0], instruction.discriminator());
+        }
+    }
+    
+    #[test]
+    fn test_unpack_rejects_unknown_version_and_empty_data() {
+        let mut packed = ProgramInstruction::CloseVerificationState.pack();
+        packed[1] = INSTRUCTION_VERSION + 1;
+        assert_eq!(
+            ProgramInstruction::unpack(&packed),
+            Err(Groth16Error::UnsupportedInstructionVersion.into())
+        );
+        
+        assert_eq!(ProgramInstruction::unpack(&[]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(
+            ProgramInstruction::unpack(&[VERSIONED_INSTRUCTION_TAG]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(ProgramInstruction::unpack(&[42]), Err(ProgramError::InvalidInstructionData));
+    }
+    
     #[test]
     fn test_groth16_error_types() {
         assert_eq!(
@@ -66,4 +178,413 @@ mod program_tests {
             "InvalidG2Length"
         );
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_groth16_error_codes_are_stable() {
+        assert_eq!(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs.code(), 0);
+        assert_eq!(Groth16Error::ProofVerificationFailed.code(), 1);
+        assert_eq!(Groth16Error::PairingVerificationError.code(), 2);
+        assert_eq!(Groth16Error::InvalidG1Length.code(), 5);
+        assert_eq!(Groth16Error::PublicInputGreaterThenFieldSize.code(), 10);
+        assert_eq!(Groth16Error::AccountToCheckMissing.code(), 11);
+        assert_eq!(Groth16Error::CircuitMismatch.code(), 12);
+        
+        for (i, e) in Groth16Error::ALL.iter().enumerate() {
+            assert_eq!(e.code(), i as u32, "{:?} is out of order", e);
+        }
+    }
+    
+    #[test]
+    fn test_groth16_error_program_error_roundtrip() {
+        for e in Groth16Error::ALL {
+            let program_error = ProgramError::from(e);
+            assert_eq!(program_error, ProgramError::Custom(e.code()));
+            assert_eq!(Groth16Error::from_program_error(&program_error), Some(e));
+        }
+        
+        assert_eq!(Groth16Error::from_program_error(&ProgramError::InsufficientFunds), None);
+        assert_eq!(Groth16Error::from_program_error(&ProgramError::Custom(999)), None);
+    }
+    
+    #[test]
+    fn test_groth16_error_from_transaction_error() {
+        let err = TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(Groth16Error::PairingVerificationError.code()),
+        );
+        assert_eq!(
+            Groth16Error::from_transaction_error(&err),
+            Some((1, Groth16Error::PairingVerificationError))
+        );
+        
+        let missing_account = TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys);
+        assert_eq!(Groth16Error::from_transaction_error(&missing_account), None);
+        assert_eq!(
+            Groth16Error::from_instruction_error(&InstructionError::InvalidInstructionData),
+            None
+        );
+        assert_eq!(Groth16Error::from_transaction_error(&TransactionError::AccountNotFound), None);
+    }
+    
+    fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 165];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1; // initialized
+        data
+    }
+    
+    #[test]
+    fn test_token_account_unpack() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        
+        let view = TokenAccountView::unpack(&token_account_data(&mint, &owner, 500)).unwrap();
+        assert_eq!(view, TokenAccountView { mint, owner, amount: 500 });
+        
+        // Token-2022 account with extensions: account type byte follows the base layout
+        let mut extended = token_account_data(&mint, &owner, 500);
+        extended.extend_from_slice(&[2, 0, 0]);
+        assert_eq!(TokenAccountView::unpack(&extended).unwrap(), view);
+    }
+    
+    #[test]
+    fn test_token_account_unpack_rejects_invalid_data() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        
+        let mut uninitialized = token_account_data(&mint, &owner, 500);
+        uninitialized[108] = 0;
+        assert_eq!(TokenAccountView::unpack(&uninitialized), Err(Groth16Error::InvalidTokenAccount));
+        
+        let mut mint_with_extensions = token_account_data(&mint, &owner, 500);
+        mint_with_extensions.extend_from_slice(&[1, 0, 0]);
+        assert_eq!(TokenAccountView::unpack(&mint_with_extensions), Err(Groth16Error::InvalidTokenAccount));
+        
+        assert_eq!(TokenAccountView::unpack(&[0u8; 82]), Err(Groth16Error::InvalidTokenAccount));
+    }
+    
+    #[test]
+    fn test_token_balance_checks() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let view = TokenAccountView { mint, owner, amount: 500 };
+        
+        assert_eq!(view.check_balance(&mint, &owner, 500), Ok(()));
+        assert_eq!(view.check_balance(&mint, &owner, 501), Err(Groth16Error::InsufficientTokenBalance));
+        assert_eq!(view.check_balance(&Pubkey::new_unique(), &owner, 1), Err(Groth16Error::TokenMintMismatch));
+        assert_eq!(view.check_balance(&mint, &Pubkey::new_unique(), 1), Err(Groth16Error::TokenOwnerMismatch));
+    }
+    
+    #[test]
+    fn test_threshold_from_public_input() {
+        let mut input = [0u8; 32];
+        input[24..].copy_from_slice(&1_500u64.to_be_bytes());
+        assert_eq!(threshold_from_public_input(&input), Ok(1_500));
+        
+        input[0] = 1;
+        assert_eq!(threshold_from_public_input(&input), Err(Groth16Error::InvalidThresholdInput));
+    }
+    
+    #[test]
+    fn test_prepare_public_inputs_checks_input_count() {
+        let result = prepare_public_inputs(&[[0u8; 64]], &[[0u8; 32]]);
+        assert_eq!(result, Err(Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs));
+    }
+    
+    #[test]
+    fn test_balance_threshold_serialization() {
+        let threshold = BalanceThreshold::PublicInput { index: 0, vk_ic: vec![[1u8; 64]; 2] };
+        let serialized = borsh::to_vec(&threshold).unwrap();
+        assert_eq!(BalanceThreshold::try_from_slice(&serialized).unwrap(), threshold);
+        
+        let plain = borsh::to_vec(&BalanceThreshold::Plain { required_balance: 7 }).unwrap();
+        assert_eq!(plain, [&[0u8][..], &7u64.to_le_bytes()[..]].concat());
+    }
+    
+    fn zero_vk() -> Groth16VerifyingKeyPrepared {
+        Groth16VerifyingKeyPrepared {
+            vk_alpha_g1: [0u8; 64],
+            vk_beta_g2: [0u8; 128],
+            vk_gamma_g2: [0u8; 128],
+            vk_delta_g2: [0u8; 128],
+        }
+    }
+    
+    fn batch_proof(seed: u8) -> BatchProof {
+        BatchProof {
+            proof_a: [seed; 64],
+            proof_b: [seed; 128],
+            proof_c: [seed; 64],
+            public_inputs: vec![[seed; 32]],
+        }
+    }
+    
+    #[test]
+    fn test_registered_verifying_key_space() {
+        let registered = RegisteredVerifyingKey {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            circuit_id: [1u8; 32],
+            bump: 255,
+            verifying_key: zero_vk(),
+            vk_ic: vec![[0u8; 64]; 3],
+        };
+        
+        assert_eq!(borsh::to_vec(&registered).unwrap().len(), RegisteredVerifyingKey::space(2));
+        assert_eq!(registered.nr_public_inputs(), 2);
+    }
+    
+    #[test]
+    fn test_batch_randomness_is_bound_to_every_proof() {
+        let proofs = vec![batch_proof(1), batch_proof(2), batch_proof(3)];
+        let randomness = batch_randomness(&[0u8; 32], &proofs);
+        
+        assert_eq!(randomness.len(), 3);
+        assert_eq!(randomness, batch_randomness(&[0u8; 32], &proofs));
+        assert_ne!(randomness[0], randomness[1]);
+        for r in &randomness {
+            assert!(r[..16].iter().all(|b| *b == 0), "coefficients are 128-bit");
+            assert!(r.iter().any(|b| *b != 0));
+        }
+        
+        let mut tampered = proofs.clone();
+        tampered[2].public_inputs[0][31] ^= 1;
+        assert_ne!(batch_randomness(&[0u8; 32], &tampered)[0], randomness[0]);
+        assert_ne!(batch_randomness(&[1u8; 32], &proofs)[0], randomness[0]);
+    }
+    
+    #[test]
+    fn test_add_scalars_be_carries() {
+        let mut a = [0u8; 32];
+        a[31] = 0xff;
+        a[30] = 0xff;
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        
+        let mut expected = [0u8; 32];
+        expected[29] = 1;
+        assert_eq!(add_scalars_be(&a, &one), expected);
+    }
+    
+    #[test]
+    fn test_verify_batch_rejects_empty_and_oversized_batches() {
+        let vk = zero_vk();
+        let vk_ic = vec![[0u8; 64]; 2];
+        
+        assert_eq!(verify_batch(&vk, &vk_ic, &[0u8; 32], &[]), Err(Groth16Error::EmptyBatch));
+        
+        let too_many: Vec<BatchProof> = (0..=MAX_BATCH_SIZE as u8).map(batch_proof).collect();
+        assert_eq!(verify_batch(&vk, &vk_ic, &[0u8; 32], &too_many), Err(Groth16Error::BatchTooLarge));
+    }
+    
+    #[test]
+    fn test_verify_batch_instruction_serialization() {
+        let instruction = ProgramInstruction::VerifyBatch { proofs: vec![batch_proof(1), batch_proof(2)] };
+        let serialized = borsh::to_vec(&instruction).unwrap();
+        assert_eq!(serialized[0], 6);
+        
+        match ProgramInstruction::try_from_slice(&serialized).unwrap() {
+            ProgramInstruction::VerifyBatch { proofs } => assert_eq!(proofs, vec![batch_proof(1), batch_proof(2)]),
+            _ => panic!("expected VerifyBatch"),
+        }
+    }
+    
+    fn field_input(value: u64) -> [u8; 32] {
+        let mut input = [0u8; 32];
+        input[24..].copy_from_slice(&value.to_be_bytes());
+        input
+    }
+    
+    #[test]
+    fn test_sha256_input_hash_is_a_field_element() {
+        let inputs: Vec<[u8; 32]> = (1..=MAX_HASHED_INPUTS as u64).map(field_input).collect();
+        let hash = hash_public_inputs(InputHashScheme::Sha256, &inputs).unwrap();
+        
+        let parts: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+        let mut expected = solana_program::hash::hashv(&parts).to_bytes();
+        expected[0] &= 0x1f;
+        assert_eq!(hash, expected);
+        
+        // Order matters
+        let mut swapped = inputs.clone();
+        swapped.swap(0, 1);
+        assert_ne!(hash_public_inputs(InputHashScheme::Sha256, &swapped).unwrap(), hash);
+    }
+    
+    #[test]
+    fn test_poseidon_input_hash_chains_long_inputs() {
+        use solana_poseidon::{hashv, Endianness, Parameters};
+        
+        let poseidon = |parts: &[&[u8]]| hashv(Parameters::Bn254X5, Endianness::BigEndian, parts).unwrap().to_bytes();
+        
+        let inputs: Vec<[u8; 32]> = (1..=POSEIDON_MAX_INPUTS as u64 + 1).map(field_input).collect();
+        
+        let short: Vec<&[u8]> = inputs[..3].iter().map(|input| input.as_slice()).collect();
+        assert_eq!(hash_public_inputs(InputHashScheme::Poseidon, &inputs[..3]).unwrap(), poseidon(&short));
+        
+        // 13 inputs: the first 12 in one call, then the result with the last one
+        let first: Vec<&[u8]> = inputs[..POSEIDON_MAX_INPUTS].iter().map(|input| input.as_slice()).collect();
+        let acc = poseidon(&first);
+        let expected = poseidon(&[&acc, &inputs[POSEIDON_MAX_INPUTS]]);
+        assert_eq!(hash_public_inputs(InputHashScheme::Poseidon, &inputs).unwrap(), expected);
+    }
+    
+    #[test]
+    fn test_input_hash_rejects_empty_and_non_canonical_inputs() {
+        for scheme in [InputHashScheme::Sha256, InputHashScheme::Poseidon] {
+            assert_eq!(hash_public_inputs(scheme, &[]), Err(Groth16Error::InvalidPublicInputsLength));
+            assert_eq!(
+                hash_public_inputs(scheme, &[field_input(1); MAX_HASHED_INPUTS + 1]),
+                Err(Groth16Error::InvalidPublicInputsLength)
+            );
+            assert_eq!(
+                hash_public_inputs(scheme, &[field_input(1), [0xff; 32]]),
+                Err(Groth16Error::PublicInputGreaterThenFieldSize)
+            );
+        }
+    }
+    
+    #[test]
+    fn test_verify_hashed_inputs_instruction_roundtrip() {
+        let instruction = ProgramInstruction::VerifyHashedInputs {
+            proof: batch_proof(3),
+            scheme: InputHashScheme::Poseidon,
+        };
+        assert_eq!(instruction.discriminator(), 7);
+        assert_eq!(borsh::to_vec(&instruction).unwrap()[0], 7);
+        assert_eq!(ProgramInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+    }
+    
+    #[test]
+    fn test_root_history_keeps_recent_roots() {
+        let mut history = RootHistory::new(field_input(1));
+        assert_eq!(history.current_root(), field_input(1));
+        assert!(history.contains(&field_input(1)));
+        assert!(!history.contains(&[0u8; 32]), "empty slots are never valid roots");
+        
+        for i in 2..=ROOT_HISTORY_SIZE as u64 {
+            history.push(field_input(i));
+        }
+        assert_eq!(history.current_root(), field_input(ROOT_HISTORY_SIZE as u64));
+        assert!(history.contains(&field_input(1)));
+        
+        // The next root overwrites the oldest one
+        history.push(field_input(ROOT_HISTORY_SIZE as u64 + 1));
+        assert_eq!(history.current_index, 0);
+        assert!(!history.contains(&field_input(1)));
+        assert!(history.contains(&field_input(2)));
+    }
+    
+    #[test]
+    fn test_root_registry_serialization() {
+        let authority = Pubkey::new_unique();
+        let registry = RootRegistry::new(authority, [7u8; 32], 254, 20, field_input(9));
+        let serialized = borsh::to_vec(&registry).unwrap();
+        assert_eq!(serialized.len(), RootRegistry::LEN);
+        assert_eq!(RootRegistry::try_from_slice(&serialized).unwrap(), registry);
+        
+        let program_id = Pubkey::new_unique();
+        let (address, _) = find_root_registry_address(&program_id, &authority, &[7u8; 32]);
+        assert_ne!(address, find_root_registry_address(&program_id, &authority, &[8u8; 32]).0);
+    }
+    
+    #[test]
+    fn test_merkle_roots_must_be_nonzero_field_elements() {
+        assert!(check_merkle_root(&field_input(1)).is_ok());
+        assert_eq!(check_merkle_root(&[0u8; 32]), Err(Groth16Error::PublicInputGreaterThenFieldSize));
+        assert_eq!(check_merkle_root(&[0xff; 32]), Err(Groth16Error::PublicInputGreaterThenFieldSize));
+    }
+    
+    #[test]
+    fn test_member_context_is_a_field_element_per_member() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(member_context(&alice)[0] & 0xe0, 0);
+        assert_eq!(member_context(&alice), member_context(&alice));
+        assert_ne!(member_context(&alice), member_context(&bob));
+    }
+    
+    #[test]
+    fn test_root_registry_instructions_roundtrip() {
+        let instructions = vec![
+            ProgramInstruction::InitRootRegistry {
+                registry_id: [1u8; 32],
+                depth: 20,
+                root: field_input(5),
+            },
+            ProgramInstruction::UpdateRoot { root: field_input(6) },
+            ProgramInstruction::VerifyMembership { proof: batch_proof(4) },
+        ];
+        for (instruction, discriminator) in instructions.into_iter().zip(8u8..) {
+            assert_eq!(instruction.discriminator(), discriminator);
+            assert_eq!(borsh::to_vec(&instruction).unwrap()[0], discriminator);
+            assert_eq!(ProgramInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+        }
+        assert_eq!(Groth16Error::UnknownMerkleRoot.code(), 23);
+        assert_eq!(Groth16Error::RootAuthorityMismatch.code(), 25);
+    }
+    
+    // Root of the tree over `leaves`, recomputing every level
+    fn naive_root(depth: u8, leaves: &[[u8; 32]]) -> [u8; 32] {
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, [0u8; 32]);
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| hash_pair(&pair[0], &pair[1]).unwrap()).collect();
+        }
+        level[0]
+    }
+    
+    #[test]
+    fn test_incremental_merkle_tree_matches_full_tree() {
+        let depth = 4;
+        let mut tree = IncrementalMerkleTree::new(Pubkey::new_unique(), [1u8; 32], 255, depth).unwrap();
+        assert_eq!(tree.root(), naive_root(depth, &[]));
+        
+        let mut leaves = Vec::new();
+        for i in 0..11u64 {
+            let leaf = field_input(100 + i);
+            assert_eq!(tree.append(leaf), Ok(i));
+            leaves.push(leaf);
+            assert_eq!(tree.root(), naive_root(depth, &leaves));
+        }
+        
+        // Every intermediate root is still known
+        assert!(tree.history.contains(&naive_root(depth, &leaves[..1])));
+        assert_eq!(tree.next_index, 11);
+    }
+    
+    #[test]
+    fn test_incremental_merkle_tree_rejects_full_tree_and_bad_leaves() {
+        let mut tree = IncrementalMerkleTree::new(Pubkey::new_unique(), [1u8; 32], 255, 2).unwrap();
+        assert_eq!(tree.append([0xff; 32]), Err(Groth16Error::PublicInputGreaterThenFieldSize));
+        for i in 0..4 {
+            tree.append(field_input(i)).unwrap();
+        }
+        assert_eq!(tree.append(field_input(4)), Err(Groth16Error::MerkleTreeFull));
+        assert_eq!(tree.next_index, tree.capacity());
+    }
+    
+    #[test]
+    fn test_incremental_merkle_tree_space() {
+        for depth in [1, 20, MAX_MERKLE_DEPTH] {
+            let tree = IncrementalMerkleTree::new(Pubkey::new_unique(), [2u8; 32], 254, depth).unwrap();
+            let serialized = borsh::to_vec(&tree).unwrap();
+            assert_eq!(serialized.len(), IncrementalMerkleTree::space(depth));
+            assert_ne!(serialized.len(), RootRegistry::LEN, "load_root_history tells them apart by size");
+        }
+    }
+    
+    #[test]
+    fn test_merkle_tree_instructions_roundtrip() {
+        let instructions = vec![
+            ProgramInstruction::InitMerkleTree { tree_id: [3u8; 32], depth: 20 },
+            ProgramInstruction::AppendLeaf { leaf: field_input(8) },
+        ];
+        for (instruction, discriminator) in instructions.into_iter().zip(11u8..) {
+            assert_eq!(instruction.discriminator(), discriminator);
+            assert_eq!(ProgramInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+        }
+        assert_eq!(Groth16Error::MerkleTreeFull.code(), 24);
+    }
+}
@@ -0,0 +1,34 @@
+use solana_program::program_error::ProgramError;
+
+/// Failures specific to the pool. Codes start at 100 so they don't overlap
+/// the `Groth16Error` codes the pool also returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    NullifierAlreadySpent = 100,
+    TokenMintMismatch = 101,
+    InvalidVerifyingKey = 102,
+    UnsupportedTokenProgram = 103,
+}
+
+impl PoolError {
+    pub const ALL: [PoolError; 4] = [
+        PoolError::NullifierAlreadySpent,
+        PoolError::TokenMintMismatch,
+        PoolError::InvalidVerifyingKey,
+        PoolError::UnsupportedTokenProgram,
+    ];
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|e| e.code() == code)
+    }
+}
+
+impl From<PoolError> for ProgramError {
+    fn from(e: PoolError) -> Self {
+        ProgramError::Custom(e.code())
+    }
+}
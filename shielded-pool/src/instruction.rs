@@ -0,0 +1,89 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError};
+use solana_zk_example::{Groth16Error, Groth16VerifyingKeyPrepared, VERSIONED_INSTRUCTION_TAG};
+
+use crate::WITHDRAW_PUBLIC_INPUTS;
+
+/// Layout version written by `PoolInstruction::pack`, behind proof-verify's
+/// `VERSIONED_INSTRUCTION_TAG`. Bump it whenever the Borsh layout of an
+/// existing variant changes, and keep decoding the old versions in `unpack`.
+pub const INSTRUCTION_VERSION: u8 = 1;
+
+/// Instructions understood by the shielded pool.
+///
+/// The variant order is the instruction discriminator: never reorder
+/// variants, only append new ones. Build instruction data with `pack` and
+/// decode it with `unpack`, which also accept the bare v0 layout.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PoolInstruction {
+    /// Accounts: `[writable] pool`, `[writable] merkle_tree`, `[writable] vault`, `mint`,
+    /// `[writable, signer] authority`, `token_program`, `system_program`
+    ///
+    /// `verifying_key` and `vk_ic` are the withdraw circuit's for `depth`.
+    InitPool {
+        denomination: u64,
+        depth: u8,
+        verifying_key: Groth16VerifyingKeyPrepared,
+        vk_ic: [[u8; 64]; WITHDRAW_PUBLIC_INPUTS + 1],
+    },
+    /// Accounts: `pool`, `[writable] merkle_tree`, `[writable] vault`,
+    /// `[writable] depositor_token_account`, `[signer] depositor`, `token_program`
+    ///
+    /// Moves `denomination` tokens into the vault and appends `commitment`.
+    /// Sets the leaf index as return data, little-endian `u64`.
+    Deposit {
+        commitment: [u8; 32],
+    },
+    /// Accounts: `pool`, `merkle_tree`, `[writable] nullifier`, `[writable] vault`,
+    /// `[writable] recipient_token_account`, `[writable, signer] payer`, `token_program`,
+    /// `system_program`
+    ///
+    /// Anyone can submit it, for example a relayer paying the fees: the proof
+    /// fixes the recipient token account.
+    Withdraw {
+        proof_a: [u8; 64],
+        proof_b: [u8; 128],
+        proof_c: [u8; 64],
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+    },
+}
+
+impl PoolInstruction {
+    /// Borsh discriminator of the variant, the first byte of its v0 layout
+    pub fn discriminator(&self) -> u8 {
+        match self {
+            PoolInstruction::InitPool { .. } => 0,
+            PoolInstruction::Deposit { .. } => 1,
+            PoolInstruction::Withdraw { .. } => 2,
+        }
+    }
+
+    /// Encodes the instruction as
+    /// `[VERSIONED_INSTRUCTION_TAG, INSTRUCTION_VERSION, discriminator, fields...]`
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = vec![VERSIONED_INSTRUCTION_TAG, INSTRUCTION_VERSION];
+        data.extend(borsh::to_vec(self).expect("instruction serialization"));
+        data
+    }
+
+    /// Decodes versioned instruction data, or bare v0 data from older clients
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        match data {
+            [VERSIONED_INSTRUCTION_TAG, version, payload @ ..] => match *version {
+                // v1 only added the envelope, so its payload is the v0 layout
+                1 => Self::decode_v0(payload),
+                version => {
+                    msg!("Unsupported instruction version {}", version);
+                    Err(Groth16Error::UnsupportedInstructionVersion.into())
+                }
+            },
+            [VERSIONED_INSTRUCTION_TAG] | [] => Err(ProgramError::InvalidInstructionData),
+            _ => Self::decode_v0(data),
+        }
+    }
+
+    fn decode_v0(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+}
@@ -0,0 +1,415 @@
+//! Fixed-denomination shielded pool for SPL tokens.
+//!
+//! Users deposit under a Poseidon note commitment and later withdraw to any
+//! token account with a Groth16 proof that they hold one of the deposited
+//! notes. The notes live in proof-verify's `IncrementalMerkleTree`, proofs go
+//! through the shared verifier, and each withdrawn note leaves a nullifier PDA
+//! behind so it can't be withdrawn twice.
+
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use solana_zk_example::{
+    emit, member_context, prepare_public_inputs, reject, Groth16Error,
+    Groth16VerifierPrepared, Groth16VerifyingKeyPrepared, IncrementalMerkleTree, ProofEvent,
+    TokenAccountView, MAX_MERKLE_DEPTH, MERKLE_TREE_SEED, TOKEN_PROGRAM_ID,
+};
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+pub mod token;
+
+pub use error::*;
+pub use instruction::*;
+pub use state::*;
+
+// Program entrypoint
+#[cfg(not(feature = "no-entrypoint"))]
+entrypoint!(process_instruction);
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match PoolInstruction::unpack(instruction_data)? {
+        PoolInstruction::InitPool {
+            denomination,
+            depth,
+            verifying_key,
+            vk_ic,
+        } => init_pool(program_id, accounts, denomination, depth, verifying_key, vk_ic),
+        PoolInstruction::Deposit { commitment } => deposit(program_id, accounts, commitment),
+        PoolInstruction::Withdraw {
+            proof_a,
+            proof_b,
+            proof_c,
+            root,
+            nullifier_hash,
+        } => withdraw(
+            program_id,
+            accounts,
+            proof_a,
+            proof_b,
+            proof_c,
+            root,
+            nullifier_hash,
+        ),
+    }
+}
+
+fn init_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    denomination: u64,
+    depth: u8,
+    verifying_key: Groth16VerifyingKeyPrepared,
+    vk_ic: [[u8; 64]; WITHDRAW_PUBLIC_INPUTS + 1],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account = next_account_info(account_info_iter)?;
+    let tree_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let mint = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // The vault and every transfer go through the classic token program, so
+    // Token-2022 mints, whose extensions it knows nothing about, are refused
+    if *token_program.key != TOKEN_PROGRAM_ID || *mint.owner != TOKEN_PROGRAM_ID {
+        msg!("Only mints of the classic token program are supported");
+        return Err(PoolError::UnsupportedTokenProgram.into());
+    }
+    if denomination == 0 || depth == 0 || depth > MAX_MERKLE_DEPTH {
+        return Err(ProgramError::InvalidArgument);
+    }
+    // An all-zero key would accept all-zero proofs
+    if vk_ic.iter().any(|point| *point == [0u8; 64]) || verifying_key.vk_alpha_g1 == [0u8; 64] {
+        return Err(PoolError::InvalidVerifyingKey.into());
+    }
+
+    let (pool_address, bump) = find_pool_address(program_id, authority.key, mint.key, denomination);
+    let (tree_address, tree_bump) = find_pool_tree_address(program_id, &pool_address);
+    let (vault_address, vault_bump) = find_vault_address(program_id, &pool_address);
+    if pool_address != *pool_account.key
+        || tree_address != *tree_account.key
+        || vault_address != *vault_account.key
+    {
+        msg!("Pool accounts do not match their seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !pool_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let rent = Rent::get()?;
+    let pool_seeds: &[&[u8]] = &[
+        POOL_SEED,
+        authority.key.as_ref(),
+        mint.key.as_ref(),
+        &denomination.to_le_bytes(),
+        &[bump],
+    ];
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            pool_account.key,
+            rent.minimum_balance(Pool::LEN),
+            Pool::LEN as u64,
+            program_id,
+        ),
+        &[authority.clone(), pool_account.clone(), system_program_account.clone()],
+        &[pool_seeds],
+    )?;
+
+    let tree_space = IncrementalMerkleTree::space(depth);
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            tree_account.key,
+            rent.minimum_balance(tree_space),
+            tree_space as u64,
+            program_id,
+        ),
+        &[authority.clone(), tree_account.clone(), system_program_account.clone()],
+        &[&[MERKLE_TREE_SEED, pool_address.as_ref(), pool_address.as_ref(), &[tree_bump]]],
+    )?;
+    let tree = IncrementalMerkleTree::new(pool_address, pool_address.to_bytes(), tree_bump, depth)?;
+    tree.store(tree_account)?;
+
+    // The vault is a token account at a PDA, owned by the pool
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            vault_account.key,
+            rent.minimum_balance(token::TOKEN_ACCOUNT_LEN),
+            token::TOKEN_ACCOUNT_LEN as u64,
+            token_program.key,
+        ),
+        &[authority.clone(), vault_account.clone(), system_program_account.clone()],
+        &[&[VAULT_SEED, pool_address.as_ref(), &[vault_bump]]],
+    )?;
+    invoke(
+        &token::initialize_account3(token_program.key, vault_account.key, mint.key, &pool_address),
+        &[vault_account.clone(), mint.clone(), token_program.clone()],
+    )?;
+
+    Pool {
+        is_initialized: true,
+        authority: *authority.key,
+        mint: *mint.key,
+        denomination,
+        depth,
+        bump,
+        vault_bump,
+        merkle_tree: tree_address,
+        verifying_key,
+        vk_ic,
+    }
+    .store(pool_account)?;
+
+    msg!("Pool of {} initialized with a depth {} tree", denomination, depth);
+    Ok(())
+}
+
+fn deposit(program_id: &Pubkey, accounts: &[AccountInfo], commitment: [u8; 32]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account = next_account_info(account_info_iter)?;
+    let tree_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let depositor_token_account = next_account_info(account_info_iter)?;
+    let depositor = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    let pool = Pool::load(program_id, pool_account)?;
+    check_pool_accounts(program_id, &pool, pool_account.key, tree_account, vault_account, token_program)?;
+    if !depositor.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    invoke(
+        &token::transfer(
+            token_program.key,
+            depositor_token_account.key,
+            vault_account.key,
+            depositor.key,
+            pool.denomination,
+        ),
+        &[
+            depositor_token_account.clone(),
+            vault_account.clone(),
+            depositor.clone(),
+            token_program.clone(),
+        ],
+    )?;
+
+    let mut tree = IncrementalMerkleTree::load(program_id, tree_account)?;
+    let index = tree.append(commitment)?;
+    tree.store(tree_account)?;
+
+    msg!("Deposit #{} accepted", index);
+    emit(&ProofEvent::LeafAppended {
+        tree: *tree_account.key,
+        index,
+        leaf: commitment,
+        root: tree.root(),
+    });
+    set_return_data(&index.to_le_bytes());
+    Ok(())
+}
+
+fn withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof_a: [u8; 64],
+    proof_b: [u8; 128],
+    proof_c: [u8; 64],
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_account = next_account_info(account_info_iter)?;
+    let tree_account = next_account_info(account_info_iter)?;
+    let nullifier_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let recipient_token_account = next_account_info(account_info_iter)?;
+    let payer = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let pool = Pool::load(program_id, pool_account)?;
+    check_pool_accounts(program_id, &pool, pool_account.key, tree_account, vault_account, token_program)?;
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *system_program_account.key != system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if TokenAccountView::load(recipient_token_account)?.mint != pool.mint {
+        return Err(PoolError::TokenMintMismatch.into());
+    }
+
+    let history = IncrementalMerkleTree::load(program_id, tree_account)?.history;
+    if !history.contains(&root) {
+        msg!("Proof references a root the pool doesn't know");
+        return Err(reject(Groth16Error::UnknownMerkleRoot));
+    }
+
+    let (nullifier_address, nullifier_bump) = find_nullifier_address(program_id, pool_account.key, &nullifier_hash);
+    if nullifier_address != *nullifier_account.key {
+        msg!("Nullifier address does not match its seeds");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !nullifier_account.data_is_empty() {
+        msg!("Note already withdrawn");
+        return Err(PoolError::NullifierAlreadySpent.into());
+    }
+
+    let public_inputs = [root, nullifier_hash, member_context(recipient_token_account.key)];
+    let groth16_verifier_prepared = Groth16VerifierPrepared {
+        proof_a,
+        proof_b,
+        proof_c,
+        prepared_public_inputs: prepare_public_inputs(&pool.vk_ic, &public_inputs).map_err(reject)?,
+        verifying_key: Box::new(pool.verifying_key),
+    };
+    let result = groth16_verifier_prepared.verify().map_err(|e| {
+        msg!("Verification error: {:?}", e);
+        reject(e)
+    })?;
+    if !result {
+        msg!("Proof is invalid!");
+        return Err(reject(Groth16Error::ProofVerificationFailed));
+    }
+
+    // Spend the note before paying out
+    create_nullifier_account(
+        program_id,
+        payer,
+        nullifier_account,
+        system_program_account,
+        &[NULLIFIER_SEED, pool_account.key.as_ref(), nullifier_hash.as_ref(), &[nullifier_bump]],
+    )?;
+    SpentNullifier {
+        pool: *pool_account.key,
+        nullifier_hash,
+    }
+    .serialize(&mut &mut nullifier_account.try_borrow_mut_data()?[..])?;
+
+    invoke_signed(
+        &token::transfer(
+            token_program.key,
+            vault_account.key,
+            recipient_token_account.key,
+            pool_account.key,
+            pool.denomination,
+        ),
+        &[
+            vault_account.clone(),
+            recipient_token_account.clone(),
+            pool_account.clone(),
+            token_program.clone(),
+        ],
+        &[&[
+            POOL_SEED,
+            pool.authority.as_ref(),
+            pool.mint.as_ref(),
+            &pool.denomination.to_le_bytes(),
+            &[pool.bump],
+        ]],
+    )?;
+
+    msg!("Withdrawal of {} completed", pool.denomination);
+    Ok(())
+}
+
+/// Creates the nullifier PDA. Its address is public, so anyone can send it
+/// lamports first, and `create_account` fails on an account that already has
+/// some. A funded account with no data is still unspent, so top it up to rent
+/// exemption and allocate and assign it instead.
+fn create_nullifier_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    nullifier_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?.minimum_balance(SpentNullifier::LEN);
+    let lamports = nullifier_account.lamports();
+    if lamports == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                nullifier_account.key,
+                rent,
+                SpentNullifier::LEN as u64,
+                program_id,
+            ),
+            &[payer.clone(), nullifier_account.clone(), system_program_account.clone()],
+            &[seeds],
+        );
+    }
+
+    if lamports < rent {
+        invoke(
+            &system_instruction::transfer(payer.key, nullifier_account.key, rent - lamports),
+            &[payer.clone(), nullifier_account.clone(), system_program_account.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(nullifier_account.key, SpentNullifier::LEN as u64),
+        &[nullifier_account.clone(), system_program_account.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(nullifier_account.key, program_id),
+        &[nullifier_account.clone(), system_program_account.clone()],
+        &[seeds],
+    )
+}
+
+/// Checks the tree, vault and token program passed alongside `pool`
+fn check_pool_accounts(
+    program_id: &Pubkey,
+    pool: &Pool,
+    pool_address: &Pubkey,
+    tree_account: &AccountInfo,
+    vault_account: &AccountInfo,
+    token_program: &AccountInfo,
+) -> ProgramResult {
+    if *tree_account.key != pool.merkle_tree {
+        msg!("Merkle tree does not belong to the pool");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let vault_address = Pubkey::create_program_address(
+        &[VAULT_SEED, pool_address.as_ref(), &[pool.vault_bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)?;
+    if vault_address != *vault_account.key {
+        msg!("Vault does not belong to the pool");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if *token_program.key != TOKEN_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, msg, program_error::ProgramError, pubkey::Pubkey};
+use solana_zk_example::{find_merkle_tree_address, Groth16VerifyingKeyPrepared};
+
+/// Seed prefix for `Pool` PDAs: `[seed, authority, mint, denomination]`
+pub const POOL_SEED: &[u8] = b"pool";
+
+/// Seed prefix for the vault token account: `[seed, pool]`
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// Seed prefix for `SpentNullifier` PDAs: `[seed, pool, nullifier_hash]`
+pub const NULLIFIER_SEED: &[u8] = b"nullifier";
+
+/// Public inputs of the withdraw circuit: `[root, nullifier_hash, recipient]`
+pub const WITHDRAW_PUBLIC_INPUTS: usize = 3;
+
+/// A pool of fixed-denomination deposits of one mint. Deposits append a note
+/// commitment to the pool's `IncrementalMerkleTree`, and withdrawals prove
+/// one of them against `verifying_key` without saying which.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Pool {
+    pub is_initialized: bool,
+    pub authority: Pubkey,        // Created the pool and chose its verifying key
+    pub mint: Pubkey,
+    pub denomination: u64,        // Every deposit and withdrawal moves exactly this much
+    pub depth: u8,                // Of the tree, and of the withdraw circuit
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub merkle_tree: Pubkey,
+    pub verifying_key: Groth16VerifyingKeyPrepared,
+    pub vk_ic: [[u8; 64]; WITHDRAW_PUBLIC_INPUTS + 1],
+}
+
+impl Pool {
+    pub const LEN: usize =
+        1 + 32 + 32 + 8 + 1 + 1 + 1 + 32 + (64 + 128 + 128 + 128) + 64 * (WITHDRAW_PUBLIC_INPUTS + 1);
+
+    /// Loads the pool from `account` after checking that it is owned by this
+    /// program, initialized, and sits at the PDA its own fields derive.
+    pub fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Pool is not owned by this program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let pool = Self::try_from_slice(&account.try_borrow_data()?)?;
+        if !pool.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let expected = Pubkey::create_program_address(
+            &[
+                POOL_SEED,
+                pool.authority.as_ref(),
+                pool.mint.as_ref(),
+                &pool.denomination.to_le_bytes(),
+                &[pool.bump],
+            ],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+        if expected != *account.key {
+            msg!("Pool address does not match its seeds");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn store(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        self.serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+/// Marks a note as withdrawn. Its only purpose is to exist: creating it a
+/// second time fails.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpentNullifier {
+    pub pool: Pubkey,
+    pub nullifier_hash: [u8; 32],
+}
+
+impl SpentNullifier {
+    pub const LEN: usize = 32 + 32;
+}
+
+pub fn find_pool_address(program_id: &Pubkey, authority: &Pubkey, mint: &Pubkey, denomination: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[POOL_SEED, authority.as_ref(), mint.as_ref(), &denomination.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn find_vault_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, pool.as_ref()], program_id)
+}
+
+pub fn find_nullifier_address(program_id: &Pubkey, pool: &Pubkey, nullifier_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NULLIFIER_SEED, pool.as_ref(), nullifier_hash.as_ref()], program_id)
+}
+
+/// The pool's tree: an `IncrementalMerkleTree` owned by this program, with
+/// the pool as authority and the pool's address as tree id
+pub fn find_pool_tree_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    find_merkle_tree_address(program_id, pool, &pool.to_bytes())
+}
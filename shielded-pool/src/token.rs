@@ -0,0 +1,46 @@
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+// Instruction tags shared by Token and Token-2022
+const TRANSFER: u8 = 3;
+const INITIALIZE_ACCOUNT_3: u8 = 18;
+
+/// Base SPL token account size, the vault's
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// `Transfer { amount }` from `source` to `destination`, signed by `authority`
+pub fn transfer(
+    token_program: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut data = vec![TRANSFER];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+/// `InitializeAccount3 { owner }`, which needs no rent sysvar
+pub fn initialize_account3(token_program: &Pubkey, account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+    let mut data = vec![INITIALIZE_ACCOUNT_3];
+    data.extend_from_slice(owner.as_ref());
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data,
+    }
+}
@@ -0,0 +1,474 @@
+use ark_bn254::Bn254;
+use ark_groth16::{ProvingKey, VerifyingKey};
+use borsh::BorshDeserialize;
+use rand::thread_rng;
+use shielded_pool::{
+    find_nullifier_address, find_pool_address, find_pool_tree_address, find_vault_address, Pool, PoolError,
+    PoolInstruction, SpentNullifier, INSTRUCTION_VERSION,
+};
+use litesvm::LiteSVM;
+use solana_program::program_error::ProgramError;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_zk_client_example::{
+    byte_utils::{g1_to_be_bytes, g2_to_be_bytes},
+    hashed_inputs::fr_to_be_bytes,
+    merkle::IncrementalTreeMirror,
+    prove::{generate_proof_package, setup},
+    shielded::{Note, WithdrawCircuit},
+};
+use solana_zk_example::{
+    Groth16Error, Groth16VerifyingKeyPrepared, IncrementalMerkleTree, ROOT_HISTORY_SIZE, TOKEN_2022_PROGRAM_ID,
+    TOKEN_PROGRAM_ID, VERSIONED_INSTRUCTION_TAG,
+};
+
+const DEPTH: usize = 8;
+const DENOMINATION: u64 = 1_000;
+const RECIPIENTS: usize = 3;
+
+// Built with `cargo build-sbf --manifest-path shielded-pool/Cargo.toml`
+const PROGRAM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/shielded_pool.so");
+
+// SPL token mint, 82 bytes: no authorities, 6 decimals, initialized
+fn mint_data() -> Vec<u8> {
+    let mut data = vec![0u8; 82];
+    data[36..44].copy_from_slice(&u64::MAX.to_le_bytes());
+    data[44] = 6;
+    data[45] = 1;
+    data
+}
+
+// SPL token account, 165 bytes: initialized, no delegate or close authority
+fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0u8; 165];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+    data
+}
+
+fn token_program_account(data: Vec<u8>) -> Account {
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: TOKEN_PROGRAM_ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn custom_error(code: u32) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(code))
+}
+
+struct Fixture {
+    svm: LiteSVM,
+    payer: Keypair,
+    depositor: Keypair,
+    program_id: Pubkey,
+    pool: Pubkey,
+    tree: Pubkey,
+    vault: Pubkey,
+    depositor_tokens: Pubkey,
+    // Token accounts of the pool's mint, owned by nobody in the test
+    recipients: Vec<Pubkey>,
+    other_mint_tokens: Pubkey,
+    proving_key: ProvingKey<Bn254>,
+    verifying_key: VerifyingKey<Bn254>,
+    mirror: IncrementalTreeMirror,
+}
+
+fn setup_pool() -> Fixture {
+    let (proving_key, verifying_key) = setup(
+        false,
+        WithdrawCircuit {
+            depth: DEPTH,
+            note: None,
+            path: None,
+            root: None,
+            nullifier_hash: None,
+            recipient: None,
+        },
+    );
+
+    let program_id = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+    svm.add_program_from_file(program_id, PROGRAM_PATH).unwrap();
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 10_000_000_000).unwrap();
+
+    let depositor = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let other_mint = Pubkey::new_unique();
+    let depositor_tokens = Pubkey::new_unique();
+    let other_mint_tokens = Pubkey::new_unique();
+    let recipients: Vec<Pubkey> = (0..RECIPIENTS).map(|_| Pubkey::new_unique()).collect();
+
+    svm.set_account(mint, token_program_account(mint_data())).unwrap();
+    svm.set_account(other_mint, token_program_account(mint_data())).unwrap();
+    svm.set_account(
+        depositor_tokens,
+        token_program_account(token_account_data(&mint, &depositor.pubkey(), 100 * DENOMINATION)),
+    )
+    .unwrap();
+    svm.set_account(
+        other_mint_tokens,
+        token_program_account(token_account_data(&other_mint, &Pubkey::new_unique(), 0)),
+    )
+    .unwrap();
+    for recipient in &recipients {
+        svm.set_account(
+            *recipient,
+            token_program_account(token_account_data(&mint, &Pubkey::new_unique(), 0)),
+        )
+        .unwrap();
+    }
+
+    let (pool, _) = find_pool_address(&program_id, &payer.pubkey(), &mint, DENOMINATION);
+    let (tree, _) = find_pool_tree_address(&program_id, &pool);
+    let (vault, _) = find_vault_address(&program_id, &pool);
+
+    let mut fixture = Fixture {
+        svm,
+        payer,
+        depositor,
+        program_id,
+        pool,
+        tree,
+        vault,
+        depositor_tokens,
+        recipients,
+        other_mint_tokens,
+        proving_key,
+        verifying_key,
+        mirror: IncrementalTreeMirror::new(DEPTH).unwrap(),
+    };
+
+    let init = fixture.init_pool_instruction(mint, TOKEN_PROGRAM_ID);
+    fixture.process(init, &[]).unwrap();
+    fixture
+}
+
+impl Fixture {
+    /// `InitPool` for `mint` with the fixture's withdraw key, paid by `payer`
+    fn init_pool_instruction(&self, mint: Pubkey, token_program: Pubkey) -> Instruction {
+        let (pool, _) = find_pool_address(&self.program_id, &self.payer.pubkey(), &mint, DENOMINATION);
+        let (tree, _) = find_pool_tree_address(&self.program_id, &pool);
+        let (vault, _) = find_vault_address(&self.program_id, &pool);
+        Instruction::new_with_bytes(
+            self.program_id,
+            &PoolInstruction::InitPool {
+                denomination: DENOMINATION,
+                depth: DEPTH as u8,
+                verifying_key: Groth16VerifyingKeyPrepared {
+                    vk_alpha_g1: g1_to_be_bytes(&self.verifying_key.alpha_g1),
+                    vk_beta_g2: g2_to_be_bytes(&self.verifying_key.beta_g2),
+                    vk_gamma_g2: g2_to_be_bytes(&self.verifying_key.gamma_g2),
+                    vk_delta_g2: g2_to_be_bytes(&self.verifying_key.delta_g2),
+                },
+                vk_ic: self
+                    .verifying_key
+                    .gamma_abc_g1
+                    .iter()
+                    .map(g1_to_be_bytes)
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            }
+            .pack(),
+            vec![
+                AccountMeta::new(pool, false),
+                AccountMeta::new(tree, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+        )
+    }
+
+    fn process(&mut self, instruction: Instruction, signers: &[&Keypair]) -> Result<(), TransactionError> {
+        let signers: Vec<&Keypair> = [&[&self.payer][..], signers].concat();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &signers[..],
+            self.svm.latest_blockhash(),
+        );
+        let result = self.svm.send_transaction(transaction).map(|_| ()).map_err(|failed| failed.err);
+        // A new blockhash, so retrying an identical instruction isn't a duplicate
+        self.svm.expire_blockhash();
+        result
+    }
+
+    fn token_amount(&self, account: Pubkey) -> u64 {
+        let account = self.svm.get_account(&account).unwrap();
+        u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+    }
+
+    /// Deposits a fresh note and returns it with its leaf index
+    fn deposit(&mut self) -> (Note, u64) {
+        let note = Note::random(&mut thread_rng());
+        let instruction = Instruction::new_with_bytes(
+            self.program_id,
+            &PoolInstruction::Deposit {
+                commitment: note.commitment_bytes(),
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(self.pool, false),
+                AccountMeta::new(self.tree, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.depositor_tokens, false),
+                AccountMeta::new_readonly(self.depositor.pubkey(), true),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            ],
+        );
+        let depositor = self.depositor.insecure_clone();
+        self.process(instruction, &[&depositor]).unwrap();
+
+        let index = self.mirror.append(note.commitment()).unwrap();
+        (note, index)
+    }
+
+    /// Proves the withdrawal of the note at `index` to `recipient` against
+    /// the mirror's current root
+    fn withdraw_instruction(&self, note: Note, index: u64, recipient: Pubkey) -> Instruction {
+        let circuit = WithdrawCircuit::new(self.mirror.tree(), index, note, &recipient).unwrap();
+        let public_inputs = circuit.public_inputs().unwrap();
+        let (_, _, package) =
+            generate_proof_package(&self.proving_key, &self.verifying_key, circuit.clone(), &public_inputs).unwrap();
+
+        let nullifier_hash = note.nullifier_hash_bytes();
+        let (nullifier, _) = find_nullifier_address(&self.program_id, &self.pool, &nullifier_hash);
+        Instruction::new_with_bytes(
+            self.program_id,
+            &PoolInstruction::Withdraw {
+                proof_a: g1_to_be_bytes(&-package.proof.a),
+                proof_b: g2_to_be_bytes(&package.proof.b),
+                proof_c: g1_to_be_bytes(&package.proof.c),
+                root: fr_to_be_bytes(circuit.root.unwrap()),
+                nullifier_hash,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(self.pool, false),
+                AccountMeta::new_readonly(self.tree, false),
+                AccountMeta::new(nullifier, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+        )
+    }
+}
+
+#[test]
+fn test_init_pool_creates_pool_tree_and_vault() {
+    let fixture = setup_pool();
+
+    let account = fixture.svm.get_account(&fixture.pool).unwrap();
+    assert_eq!(account.data.len(), Pool::LEN);
+    let pool = Pool::try_from_slice(&account.data).unwrap();
+    assert_eq!(pool.denomination, DENOMINATION);
+    assert_eq!(pool.depth as usize, DEPTH);
+    assert_eq!(pool.merkle_tree, fixture.tree);
+
+    let vault = fixture.svm.get_account(&fixture.vault).unwrap();
+    assert_eq!(vault.owner, TOKEN_PROGRAM_ID);
+    assert_eq!(&vault.data[32..64], fixture.pool.as_ref(), "the pool owns the vault");
+}
+
+#[test]
+fn test_init_pool_rejects_token_2022() {
+    let mut fixture = setup_pool();
+
+    // A Token-2022 mint
+    let mint = Pubkey::new_unique();
+    let mut mint_account = token_program_account(mint_data());
+    mint_account.owner = TOKEN_2022_PROGRAM_ID;
+    fixture.svm.set_account(mint, mint_account).unwrap();
+    let init = fixture.init_pool_instruction(mint, TOKEN_2022_PROGRAM_ID);
+    assert_eq!(
+        fixture.process(init, &[]),
+        Err(custom_error(PoolError::UnsupportedTokenProgram.code()))
+    );
+
+    // A classic mint passed with the Token-2022 program
+    let mint = Pubkey::new_unique();
+    fixture.svm.set_account(mint, token_program_account(mint_data())).unwrap();
+    let init = fixture.init_pool_instruction(mint, TOKEN_2022_PROGRAM_ID);
+    assert_eq!(
+        fixture.process(init, &[]),
+        Err(custom_error(PoolError::UnsupportedTokenProgram.code()))
+    );
+
+    let init = fixture.init_pool_instruction(mint, TOKEN_PROGRAM_ID);
+    fixture.process(init, &[]).unwrap();
+}
+
+#[test]
+fn test_instruction_envelope() {
+    let instruction = PoolInstruction::Deposit { commitment: [7u8; 32] };
+    let data = instruction.pack();
+    assert_eq!(data[..3], [VERSIONED_INSTRUCTION_TAG, INSTRUCTION_VERSION, instruction.discriminator()]);
+    assert_eq!(PoolInstruction::unpack(&data).unwrap(), instruction);
+
+    // Bare v0 data still decodes
+    let bare = borsh::to_vec(&instruction).unwrap();
+    assert_eq!(PoolInstruction::unpack(&bare).unwrap(), instruction);
+
+    assert_eq!(
+        PoolInstruction::unpack(&[VERSIONED_INSTRUCTION_TAG]),
+        Err(ProgramError::InvalidInstructionData)
+    );
+}
+
+#[test]
+fn test_unknown_instruction_version_is_rejected() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+
+    let mut withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    withdraw.data[1] = INSTRUCTION_VERSION + 1;
+    assert_eq!(
+        fixture.process(withdraw, &[]),
+        Err(custom_error(Groth16Error::UnsupportedInstructionVersion.code()))
+    );
+}
+
+#[test]
+fn test_deposit_moves_tokens_and_appends_commitment() {
+    let mut fixture = setup_pool();
+
+    for expected_index in 0..3 {
+        let (_, index) = fixture.deposit();
+        assert_eq!(index, expected_index);
+    }
+    assert_eq!(fixture.token_amount(fixture.vault), 3 * DENOMINATION);
+    assert_eq!(fixture.token_amount(fixture.depositor_tokens), 97 * DENOMINATION);
+
+    let account = fixture.svm.get_account(&fixture.tree).unwrap();
+    let tree = IncrementalMerkleTree::try_from_slice(&account.data).unwrap();
+    assert_eq!(tree.next_index, 3);
+    assert_eq!(tree.root(), fr_to_be_bytes(fixture.mirror.root()));
+}
+
+#[test]
+fn test_withdraw_pays_recipient_and_spends_nullifier() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+    fixture.deposit();
+
+    let recipient = fixture.recipients[0];
+    let withdraw = fixture.withdraw_instruction(note, index, recipient);
+    fixture.process(withdraw, &[]).unwrap();
+
+    assert_eq!(fixture.token_amount(recipient), DENOMINATION);
+    assert_eq!(fixture.token_amount(fixture.vault), DENOMINATION);
+
+    let (nullifier, _) = find_nullifier_address(&fixture.program_id, &fixture.pool, &note.nullifier_hash_bytes());
+    let account = fixture.svm.get_account(&nullifier).unwrap();
+    let spent = SpentNullifier::try_from_slice(&account.data).unwrap();
+    assert_eq!(spent.pool, fixture.pool);
+    assert_eq!(spent.nullifier_hash, note.nullifier_hash_bytes());
+}
+
+#[test]
+fn test_double_spend_is_rejected() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+    fixture.deposit();
+
+    let withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    fixture.process(withdraw, &[]).unwrap();
+
+    // A fresh proof for the same note, to another recipient
+    let again = fixture.withdraw_instruction(note, index, fixture.recipients[1]);
+    assert_eq!(
+        fixture.process(again, &[]),
+        Err(custom_error(PoolError::NullifierAlreadySpent.code()))
+    );
+    assert_eq!(fixture.token_amount(fixture.recipients[1]), 0);
+    assert_eq!(fixture.token_amount(fixture.vault), DENOMINATION);
+}
+
+#[test]
+fn test_prefunded_nullifier_does_not_lock_the_note() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+
+    // Anyone can send lamports to the nullifier address before the withdrawal
+    let (nullifier, _) = find_nullifier_address(&fixture.program_id, &fixture.pool, &note.nullifier_hash_bytes());
+    let prefund = solana_system_interface::instruction::transfer(&fixture.payer.pubkey(), &nullifier, 1);
+    fixture.process(prefund, &[]).unwrap();
+
+    let withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    fixture.process(withdraw, &[]).unwrap();
+    assert_eq!(fixture.token_amount(fixture.recipients[0]), DENOMINATION);
+
+    let account = fixture.svm.get_account(&nullifier).unwrap();
+    assert_eq!(account.owner, fixture.program_id);
+    assert_eq!(SpentNullifier::try_from_slice(&account.data).unwrap().nullifier_hash, note.nullifier_hash_bytes());
+
+    // And the note is still spent only once
+    let again = fixture.withdraw_instruction(note, index, fixture.recipients[1]);
+    assert_eq!(
+        fixture.process(again, &[]),
+        Err(custom_error(PoolError::NullifierAlreadySpent.code()))
+    );
+}
+
+#[test]
+fn test_stale_root_is_rejected() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+    let withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    let stale_root = fixture.mirror.root();
+
+    // Push the proof's root out of the history
+    for _ in 0..ROOT_HISTORY_SIZE {
+        fixture.deposit();
+    }
+    assert!(!fixture.mirror.is_known_root(stale_root));
+    assert_eq!(
+        fixture.process(withdraw, &[]),
+        Err(custom_error(Groth16Error::UnknownMerkleRoot.code()))
+    );
+
+    // The same note still withdraws against a recent root
+    let withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    fixture.process(withdraw, &[]).unwrap();
+    assert_eq!(fixture.token_amount(fixture.recipients[0]), DENOMINATION);
+}
+
+#[test]
+fn test_withdraw_is_bound_to_its_recipient() {
+    let mut fixture = setup_pool();
+    let (note, index) = fixture.deposit();
+
+    // A relayer swapping the recipient account invalidates the proof
+    let mut withdraw = fixture.withdraw_instruction(note, index, fixture.recipients[0]);
+    withdraw.accounts[4].pubkey = fixture.recipients[1];
+    assert_eq!(
+        fixture.process(withdraw, &[]),
+        Err(custom_error(Groth16Error::ProofVerificationFailed.code()))
+    );
+
+    // Recipients must hold the pool's mint
+    let other_mint_tokens = fixture.other_mint_tokens;
+    let withdraw = fixture.withdraw_instruction(note, index, other_mint_tokens);
+    assert_eq!(
+        fixture.process(withdraw, &[]),
+        Err(custom_error(PoolError::TokenMintMismatch.code()))
+    );
+}